@@ -1,19 +1,28 @@
-use std::path::PathBuf;
-use std::process::Command;
+use std::collections::{BTreeSet, HashSet};
+use std::io::IsTerminal;
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitCode};
 use std::sync::Arc;
 use std::time::Duration;
 
-use clap::{Parser, Subcommand};
+use clap::parser::ValueSource;
+use clap::{ArgMatches, CommandFactory, FromArgMatches, Parser, Subcommand};
+use futures::stream::{self, Stream, StreamExt};
 use owo_colors::OwoColorize;
 
 use pahe::prelude::*;
 use pahe_downloader::*;
 
-use crate::args::*;
+use crate::args::{EpisodeSelection, *};
+use crate::config::Config;
 use crate::constants::*;
 use crate::episode::*;
+use crate::history::*;
 use crate::logger::*;
+use crate::notify;
 use crate::progress::*;
+use crate::prompt::*;
+use crate::utils::*;
 
 #[derive(Debug, Parser)]
 #[command(author, version, about)]
@@ -29,7 +38,7 @@ pub struct Cli {
 pub enum Commands {
     /// Resolve and print episodes
     #[clap(alias("r"))]
-    Resolve(ResolveArgs),
+    Resolve(ResolveCommandArgs),
 
     /// Download a series or episodes
     #[clap(alias("d"))]
@@ -38,38 +47,161 @@ pub enum Commands {
     /// Play a series or episodes
     #[clap(alias("p"))]
     Play(PlayArgs),
+
+    /// List a series' episodes without resolving them
+    #[clap(alias("l"))]
+    List(ListArgs),
+
+    /// Print a series' metadata without touching its episodes
+    #[clap(alias("m"))]
+    Metadata(MetadataArgs),
+
+    /// Search for a series by name, pick a match and resolve it
+    #[clap(alias("s"))]
+    Search(SearchArgs),
+
+    /// Print a shell completion script to stdout
+    #[command(hide = true)]
+    Completions(CompletionsArgs),
 }
 
 #[derive(Debug)]
 pub struct App {
     cli: Cli,
     logger: Arc<CliLogger>,
+    /// fired by the first ctrl-c; stops running downloads and keeps their partial files.
+    cancel: CancellationToken,
 }
 
 impl App {
     pub fn new() -> Self {
-        let cli = Cli::parse();
-        let log_level = match &cli.command {
-            Some(Commands::Resolve(args)) => &args.app_args.log_level,
-            Some(Commands::Download(args)) => &args.resolve.app_args.log_level,
-            Some(Commands::Play(args)) => &args.resolve.app_args.log_level,
-            None => &cli.download_args.resolve.app_args.log_level,
+        let matches = Cli::command().get_matches();
+        let mut cli = Cli::from_arg_matches(&matches).unwrap_or_else(|err| err.exit());
+        apply_config(&mut cli, &matches);
+
+        let app_args = match &cli.command {
+            Some(Commands::Resolve(args)) => &args.resolve.app_args,
+            Some(Commands::Download(args)) => &args.resolve.app_args,
+            Some(Commands::Play(args)) => &args.resolve.app_args,
+            Some(Commands::List(args)) => &args.series_args.app_args,
+            Some(Commands::Metadata(args)) => &args.series_args.app_args,
+            Some(Commands::Search(args)) => &args.app_args,
+            Some(Commands::Completions(_)) | None => &cli.download_args.resolve.app_args,
+        };
+        let output = match &cli.command {
+            Some(Commands::Download(args)) => args.output.as_deref(),
+            None => cli.download_args.output.as_deref(),
+            _ => None,
+        };
+        let format = match &cli.command {
+            Some(Commands::Resolve(args)) => args.format,
+            Some(Commands::List(args)) => args.format,
+            Some(Commands::Metadata(args)) => args.format,
+            Some(Commands::Search(args)) => args.format,
+            _ => OutputFormat::Text,
         };
-        let logger = Arc::new(CliLogger::new(log_level));
-        init_tracing(Arc::clone(&logger));
-        Self { cli, logger }
-    }
-
-    pub async fn run(&self) {
-        println!("{}", self.banner());
-        if let Err(err) = match &self.cli.command {
-            Some(Commands::Resolve(args)) => self.resolve(args.clone()).await,
-            Some(Commands::Download(args)) => self.download(args.clone()).await,
-            Some(Commands::Play(args)) => self.play(args.clone()).await,
-            None => self.download(self.cli.download_args.clone()).await,
-        } {
+        let use_stderr = output == Some(STDOUT_OUTPUT) || format == OutputFormat::Json;
+        let is_terminal = if use_stderr {
+            std::io::stderr().is_terminal()
+        } else {
+            std::io::stdout().is_terminal()
+        };
+        let color = color_enabled(
+            app_args.no_color,
+            std::env::var("NO_COLOR").ok().as_deref(),
+            is_terminal,
+        );
+        let logger = Arc::new(
+            CliLogger::new(&app_args.log_level)
+                .with_stderr(use_stderr)
+                .with_color(color),
+        );
+        let log_file = app_args.log_file.as_ref().and_then(|path| {
+            std::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .inspect_err(|err| {
+                    logger.warn(format!(
+                        "failed to open log file {}: {err}; continuing without it",
+                        path.display()
+                    ))
+                })
+                .ok()
+                .map(|file| (file, app_args.log_format))
+        });
+        init_tracing(Arc::clone(&logger), log_file);
+        Self {
+            cli,
+            logger,
+            cancel: CancellationToken::new(),
+        }
+    }
+
+    pub async fn run(&self) -> ExitCode {
+        // the script goes to stdout as-is, so it must not be preceded by the banner
+        if let Some(Commands::Completions(args)) = &self.cli.command {
+            write_completions(args.shell, &mut std::io::stdout());
+            return ExitCode::SUCCESS;
+        }
+
+        self.logger.println(self.banner());
+        tokio::spawn(watch_interrupts(
+            ctrl_c_signals(),
+            self.cancel.clone(),
+            || std::process::exit(INTERRUPTED_EXIT_CODE.into()),
+        ));
+
+        let command = async {
+            match &self.cli.command {
+                Some(Commands::Resolve(args)) => self.resolve(args.clone()).await,
+                Some(Commands::Download(args)) => self.download(args.clone()).await,
+                Some(Commands::Play(args)) => self.play(args.clone()).await,
+                Some(Commands::List(args)) => self.list(args).await,
+                Some(Commands::Metadata(args)) => self.metadata(args).await,
+                Some(Commands::Search(args)) => self.search(args.clone()).await,
+                Some(Commands::Completions(_)) => unreachable!("handled before the banner"),
+                None => self.download(self.cli.download_args.clone()).await,
+            }
+        };
+        // downloads watch the token themselves and wind down in-flight episodes before
+        // returning, so the command keeps running for a while after the first ctrl-c
+        let gave_up = async {
+            self.cancel.cancelled().await;
+            tokio::time::sleep(INTERRUPT_GRACE).await;
+        };
+        let result = tokio::select! {
+            biased;
+            result = command => Some(result),
+            () = gave_up => None,
+        };
+
+        if self.cancel.is_cancelled() {
+            if result.is_none() {
+                self.logger
+                    .warn("gave up waiting for the interrupted command to stop");
+            }
+            self.logger
+                .warn("interrupted; partial downloads are kept and resume on the next run");
+            return ExitCode::from(INTERRUPTED_EXIT_CODE);
+        }
+        if let Some(Err(err)) = result {
             self.logger.as_ref().failed(format!("{err}"));
+            return ExitCode::FAILURE;
+        }
+
+        ExitCode::SUCCESS
+    }
+
+    fn client_options(&self, args: &AppArgs) -> Result<ClientOptions> {
+        if args.insecure {
+            self.logger.warn(format!(
+                "{} tls certificate verification is disabled; anyone on the network path can read and tamper with traffic",
+                "--insecure:".red().bold()
+            ));
         }
+
+        load_client_options(args)
     }
 
     fn banner(&self) -> String {
@@ -81,25 +213,220 @@ impl App {
         )
     }
 
-    pub async fn resolve(&self, args: ResolveArgs) -> Result<()> {
+    pub async fn resolve(&self, args: ResolveCommandArgs) -> Result<()> {
         let logger = self.logger.as_ref();
-        let resolves = resolve_episode_urls(args, logger).await?;
+        let probe = args.resolve.probe;
+        let options = self.client_options(&args.resolve.app_args)?;
+        let client = http_client(&options, None)?;
+        let format = args.format;
+        let resolved = resolve_episode_urls(args.resolve, &options, logger).await?;
+        let mut failures = resolved.failures;
+
+        if let Some(path) = &args.output_links {
+            let entries: Vec<String> = resolved
+                .urls
+                .iter()
+                .map(|episode| {
+                    let out = args
+                        .output_template
+                        .as_deref()
+                        .map(|template| render_episode_template(template, episode))
+                        .filter(|name| !name.is_empty());
+                    format_aria2_entry(episode, options.user_agent(), out.as_deref())
+                })
+                .collect();
+            std::fs::write(path, entries.concat()).map_err(|err| {
+                PaheError::Message(format!("failed to write {}: {err}", path.display()))
+            })?;
+            logger.success(format!(
+                "wrote {} link(s) to {}",
+                entries.len(),
+                path.display().yellow()
+            ));
+        }
 
-        for episode in resolves.iter() {
+        if let Some(path) = &args.playlist {
+            std::fs::write(path, format_m3u_playlist(&resolved.urls)).map_err(|err| {
+                PaheError::Message(format!("failed to write {}: {err}", path.display()))
+            })?;
             logger.success(format!(
-                "{} {}: \n  {}: {}\n  {}: {}",
-                "episode".dimmed(),
-                episode.index.bold(),
-                "url".dimmed(),
-                episode.url.yellow(),
-                "referer".dimmed(),
-                episode.referer
+                "wrote {} episode(s) to {}",
+                resolved.urls.len(),
+                path.display().yellow()
             ));
         }
 
+        if args.play {
+            for episode in &resolved.urls {
+                let link = DirectLink {
+                    referer: episode.referer.clone(),
+                    direct_link: episode.url.clone(),
+                };
+                let player_args = player_args(&args.player, &link, options.user_agent())?;
+                logger.success(format!(
+                    "playing\n  {}: {}",
+                    "episode".dimmed(),
+                    episode.index
+                ));
+                spawn_player(&args.player, &player_args, logger)?;
+            }
+            return report_failures(&failures, logger);
+        }
+
+        if format == OutputFormat::Json {
+            let json = serde_json::to_string_pretty(&resolved.urls)
+                .map_err(|err| PaheError::Message(format!("failed to encode json: {err}")))?;
+            println!("{json}");
+        }
+
+        for episode in resolved.urls.iter() {
+            if format == OutputFormat::Text {
+                logger.success(format!(
+                    "{} {}: \n  {}: {}\n  {}: {}",
+                    "episode".dimmed(),
+                    episode.index.bold(),
+                    "url".dimmed(),
+                    episode.url.yellow(),
+                    "referer".dimmed(),
+                    episode.referer
+                ));
+            }
+
+            if !probe {
+                continue;
+            }
+
+            let probed = logger
+                .while_loading(
+                    format!("probing episode {}", episode.index.yellow()),
+                    probe_with_client(&client, &episode.referer, &episode.url),
+                )
+                .await;
+            match probed {
+                Ok(result) => logger.success(format!(
+                    "{} {}: {} {}",
+                    "probe".dimmed(),
+                    episode.index.bold(),
+                    result.status.green(),
+                    result
+                        .total_bytes
+                        .map(format_bytes)
+                        .unwrap_or_else(|| "unknown size".to_string())
+                )),
+                Err(err) => {
+                    logger.failed(format!("{} {}: {err}", "probe".dimmed(), episode.index));
+                    failures.push(EpisodeFailure {
+                        index: episode.index,
+                        error: PaheError::Message(format!("probe failed: {err}")),
+                    });
+                }
+            }
+        }
+
+        report_failures(&failures, logger)
+    }
+
+    pub async fn list(&self, args: &ListArgs) -> Result<()> {
+        let logger = self.logger.as_ref();
+        let options = self.client_options(&args.series_args.app_args)?;
+        let pahe = connect_series(&args.series_args, &options, logger)?;
+        let (info, episodes) =
+            list_episodes(&pahe, &args.series_args, &args.episodes, logger).await?;
+
+        match args.format {
+            OutputFormat::Json => {
+                let json = serde_json::to_string_pretty(&episodes)
+                    .map_err(|err| PaheError::Message(format!("failed to encode json: {err}")))?;
+                println!("{json}");
+            }
+            OutputFormat::Text => {
+                let title = info.title.as_deref().unwrap_or("unknown").trim();
+                logger.success(format!(
+                    "{} {} ({} episodes)",
+                    "series".dimmed(),
+                    title.bold(),
+                    episodes.len()
+                ));
+                logger.println(format_episode_table(&episodes));
+            }
+        }
+
+        Ok(())
+    }
+
+    pub async fn metadata(&self, args: &MetadataArgs) -> Result<()> {
+        let logger = self.logger.as_ref();
+        let options = self.client_options(&args.series_args.app_args)?;
+        let metadata = series_metadata(&args.series_args, &options, logger).await?;
+
+        match args.format {
+            OutputFormat::Json => {
+                let json = serde_json::to_string_pretty(&metadata)
+                    .map_err(|err| PaheError::Message(format!("failed to encode json: {err}")))?;
+                println!("{json}");
+            }
+            OutputFormat::Text => {
+                let rows = [
+                    ("title", metadata.title),
+                    ("type", metadata.kind),
+                    ("status", metadata.status),
+                    ("season", metadata.season),
+                    ("year", metadata.year.map(|year| year.to_string())),
+                    ("episodes", metadata.episodes.map(|count| count.to_string())),
+                    ("genres", Some(metadata.genres.join(", "))),
+                    ("poster", metadata.poster),
+                ];
+                logger.success(
+                    rows.into_iter()
+                        .filter_map(|(key, value)| {
+                            value
+                                .filter(|value| !value.is_empty())
+                                .map(|value| format!("{}: {value}", key.dimmed()))
+                        })
+                        .collect::<Vec<_>>()
+                        .join("\n  "),
+                );
+                if let Some(synopsis) = metadata.synopsis {
+                    logger.println(synopsis);
+                }
+            }
+        }
+
         Ok(())
     }
 
+    pub async fn search(&self, args: SearchArgs) -> Result<()> {
+        let logger = self.logger.as_ref();
+        let options = self.client_options(&args.app_args)?;
+        let mut resolve = args.resolve_args();
+        let results = search_series(
+            &args.query,
+            &args.connection,
+            &args.app_args.domain,
+            &options,
+            logger,
+        )
+        .await?;
+        let picked = prompt_for_search_result(results)?;
+        logger.success(format!(
+            "{} {}",
+            "series".dimmed(),
+            describe_search_result(&picked).bold()
+        ));
+
+        resolve.series = Some(picked.session);
+        self.resolve(ResolveCommandArgs {
+            output_links: None,
+            output_template: None,
+            playlist: None,
+            play: false,
+            player: "mpv".to_string(),
+            format: args.format,
+            resolve,
+        })
+        .await
+    }
+
     pub async fn play(&self, args: PlayArgs) -> Result<()> {
         let logger = self.logger.as_ref();
         let player = args.player.as_deref();
@@ -116,6 +443,7 @@ impl App {
                         "mpv"
                     }
                 }
+                "vlc" => "vlc",
                 idk => {
                     return Err(PaheError::Message(format!("unsupported player: {}", idk)));
                 }
@@ -124,51 +452,151 @@ impl App {
             return Err(PaheError::Message("player not specified".to_string()));
         };
 
-        let urls = resolve_episode_urls(args.resolve, logger).await?;
+        let options = self.client_options(&args.resolve.app_args)?;
+        let resolved = resolve_episode_urls(args.resolve, &options, logger).await?;
 
-        for episode_url in urls {
-            let mut command = Command::new(binary);
-
-            match player {
-                Some("mpv") => {
+        for episode_url in resolved.urls {
+            let command_args = match player {
+                Some(player) => {
                     logger.success(format!(
                         "playing\n  {}: {}",
                         "episode".dimmed(),
                         episode_url.index
                     ));
 
-                    command
-                        .arg(format!(
-                            "--http-header-fields=Referer: {}",
-                            episode_url.referer
-                        ))
-                        .arg(&episode_url.url);
-
-                    logger.debug("player", format!("command: {:?}", command));
-                }
-                Some(other) => {
-                    return Err(PaheError::Message(format!("unsupported player: {}", other)));
+                    let link = DirectLink {
+                        referer: episode_url.referer,
+                        direct_link: episode_url.url,
+                    };
+                    player_args(player, &link, options.user_agent())?
                 }
-                None => {
-                    for arg in &args.player_args {
-                        command.arg(
-                            arg.replace("%R", episode_url.referer.as_str())
-                                .replace("%S", episode_url.url.as_str()),
-                        );
-                    }
+                None => args
+                    .player_args
+                    .iter()
+                    .map(|arg| {
+                        arg.replace("%R", episode_url.referer.as_str())
+                            .replace("%S", episode_url.url.as_str())
+                    })
+                    .collect(),
+            };
 
-                    logger.debug("player", format!("command: {:?}", command));
-                }
+            spawn_player(binary, &command_args, logger)?;
+        }
+
+        report_failures(&resolved.failures, logger)
+    }
+
+    pub async fn download(&self, mut args: DownloadArgs) -> Result<()> {
+        if args.only_new {
+            // the gaps are looked for across the whole series unless a batch entry narrows it
+            args.resolve.episodes = EpisodeSelection::All;
+        }
+
+        match args.batch.clone() {
+            Some(batch) => self.download_batch(&batch, args).await,
+            None => self.download_series(args).await,
+        }
+    }
+
+    /// positions of the selected episodes that have no file in `--dir` matching the naming
+    /// template yet.
+    async fn new_episodes(
+        &self,
+        args: &DownloadArgs,
+        options: &ClientOptions,
+    ) -> Result<BTreeSet<i32>> {
+        let template = args
+            .output_template
+            .as_deref()
+            .or(args.name_template.as_deref());
+        let pattern = episode_file_pattern(template)?;
+        let dir = args.dir.as_deref().unwrap_or(Path::new("."));
+        let present = present_episode_numbers(dir, &pattern)?;
+
+        let positions: Vec<i32> = match &args.resolve.episodes {
+            EpisodeSelection::Episodes(wanted) => wanted.iter().copied().collect(),
+            EpisodeSelection::All => Vec::new(),
+            EpisodeSelection::Session { .. } | EpisodeSelection::PlayLink(_) => {
+                return Err(PaheError::Message(
+                    "--only-new needs an episode range, not a session".to_string(),
+                ));
             }
+        };
+        let logger = self.logger.as_ref();
+        let series_args = args.resolve.series_args();
+        let pahe = connect_series(&series_args, options, logger)?;
+        let (_, listed) =
+            list_episodes(&pahe, &series_args, &args.resolve.episodes, logger).await?;
+        let numbers: Vec<u32> = listed.iter().map(|episode| episode.episode).collect();
+
+        // `missing_episodes` counts from the first listed episode; map back onto the selection
+        Ok(missing_episodes(&numbers, &present)
+            .into_iter()
+            .map(|position| match positions.get(position as usize - 1) {
+                Some(&selected) => selected,
+                None => position,
+            })
+            .collect())
+    }
 
-            let mut child = command.spawn()?;
-            child.wait()?;
+    /// downloads every series listed in `batch` in turn, carrying on past failed ones.
+    async fn download_batch(&self, batch: &Path, args: DownloadArgs) -> Result<()> {
+        let logger = self.logger.as_ref();
+        let text = std::fs::read_to_string(batch).map_err(|err| {
+            PaheError::Message(format!("failed to read {}: {err}", batch.display()))
+        })?;
+        let entries = parse_batch_file(&text).map_err(|err| {
+            PaheError::Message(format!("invalid batch file {}: {err}", batch.display()))
+        })?;
+
+        let mut failed = Vec::new();
+        for (position, entry) in entries.iter().enumerate() {
+            if self.cancel.is_cancelled() {
+                break;
+            }
+            logger.success(format!(
+                "{} {}/{}: {}",
+                "series".dimmed(),
+                position + 1,
+                entries.len(),
+                entry.series.yellow()
+            ));
+
+            let mut entry_args = args.clone();
+            entry_args.batch = None;
+            entry_args.resolve.series = Some(entry.series.clone());
+            if let Some(episodes) = &entry.episodes {
+                entry_args.resolve.episodes = episodes.clone();
+            }
+
+            if let Err(err) = self.download_series(entry_args).await {
+                logger.failed(format!("{}: {err}", entry.series));
+                failed.push((entry.series.as_str(), err));
+            }
         }
 
-        Ok(())
+        if failed.is_empty() {
+            logger.success(format!("batch finished: {} series", entries.len()));
+            return Ok(());
+        }
+
+        let lines = failed
+            .iter()
+            .map(|(series, err)| format!("{}: {err}", series.yellow()))
+            .collect::<Vec<_>>()
+            .join("\n  ");
+        logger.failed(format!(
+            "{} of {} series failed:\n  {lines}",
+            failed.len(),
+            entries.len()
+        ));
+        Err(PaheError::Message(format!(
+            "{} series failed",
+            failed.len()
+        )))
     }
 
-    pub async fn download(&self, args: DownloadArgs) -> Result<()> {
+    async fn download_series(&self, mut args: DownloadArgs) -> Result<()> {
         let logger = self.logger.as_ref();
         if args.resolve.stream {
             return Err(PaheError::Message(
@@ -176,123 +604,1423 @@ impl App {
             ));
         }
 
-        let urls = resolve_episode_urls(args.resolve, logger).await?;
-
-        for episode_url in urls {
-            let file_name: PathBuf = match &args.output {
-                Some(path) => path.into(),
-                None => {
-                    let guessed = logger
-                        .while_loading(
-                            "inferring output filename",
-                            suggest_filename(&episode_url.referer, &episode_url.url),
-                        )
-                        .await
-                        .map_err(|err| {
-                            PaheError::Message(format!("failed to infer output filename: {err}"))
-                        })?;
-                    guessed.into()
-                }
-            };
+        if args.resolve.probe {
+            return Err(PaheError::Message(
+                "--probe is only supported with the `resolve` command".to_string(),
+            ));
+        }
 
-            let output = match &args.dir {
-                Some(dir) => dir.join(file_name),
-                None => file_name,
-            };
+        let options = self.client_options(&args.resolve.app_args)?;
+        if args.only_new {
+            let wanted = self.new_episodes(&args, &options).await?;
+            logger.success(format!("{} new episodes", wanted.len().yellow()));
+            if wanted.is_empty() {
+                return Ok(());
+            }
+            args.resolve.episodes = EpisodeSelection::Episodes(wanted);
+        }
 
-            let output_str = output.to_string_lossy().into_owned();
-            let mut progress_renderer =
-                DownloadProgressRenderer::new(logger.level >= LogLevel::Info);
-            let (events_tx, mut events_rx) = tokio::sync::mpsc::unbounded_channel();
-            let mut tick = tokio::time::interval(Duration::from_millis(80));
-            let mut download_fut = std::pin::pin!(download(
-                DownloadRequest::new(episode_url.referer, episode_url.url, output)
-                    .connections(args.connections),
-                move |event| {
-                    let _ = events_tx.send(event);
-                },
+        let keep_going = args.resolve.keep_going;
+        let client = http_client(
+            &options,
+            Some(Duration::from_secs(args.resolve.connection.timeout)),
+        )?;
+        let resolved = resolve_episode_urls(args.resolve.clone(), &options, logger).await?;
+        let mut failures = resolved.failures;
+        let pahe = resolved.pahe.as_ref();
+
+        if args.sha256.is_some() && resolved.urls.len() > 1 {
+            return Err(PaheError::Message(
+                "--sha256 can only be used when downloading a single episode".to_string(),
+            ));
+        }
+
+        if args.output.as_deref() == Some(STDOUT_OUTPUT) && resolved.urls.len() > 1 {
+            return Err(PaheError::Message(
+                "--output - can only be used when downloading a single episode".to_string(),
             ));
+        }
 
-            let download_result = loop {
-                tokio::select! {
-                    result = &mut download_fut => break result,
-                    maybe_event = events_rx.recv() => {
-                        if let Some(event) = maybe_event {
-                            progress_renderer.handle(event);
-                        }
+        name_picked_variants(&mut args, &resolved.urls)?;
+
+        if args.dry_run {
+            let plan = logger
+                .while_loading(
+                    "planning downloads",
+                    plan_downloads(&client, &args, &resolved.urls),
+                )
+                .await?;
+            logger.success(format_plan(&plan));
+            return report_failures(&failures, logger);
+        }
+
+        let total = self.estimate_batch_size(&client, &resolved.urls).await;
+        if let Some(threshold) = args.confirm_above
+            && total > threshold
+            && !args.yes
+        {
+            let message = format!(
+                "batch is ~{}, above the {} threshold",
+                format_bytes(total),
+                format_bytes(threshold)
+            );
+            if !args.resolve.app_args.interactive {
+                return Err(PaheError::Message(format!(
+                    "{message}; pass --yes to proceed"
+                )));
+            }
+            if !confirm(&format!("{message}. continue?"))? {
+                return Err(PaheError::Message("download cancelled".to_string()));
+            }
+        }
+
+        let series = resolved
+            .urls
+            .first()
+            .and_then(|episode_url| episode_url.series_title.clone());
+        let mut batch = BatchProgress::new(resolved.urls.len());
+        if args.parallel_episodes > 1 && resolved.urls.len() > 1 {
+            failures.extend(
+                self.download_in_parallel(&client, &args, pahe, resolved.urls, &mut batch)
+                    .await?,
+            );
+            self.notify_batch(&args, series.as_deref(), &batch, failures.len());
+            report_failures(&failures, logger)?;
+            logger.success("download complete");
+            return Ok(());
+        }
+
+        for episode_url in resolved.urls {
+            let index = episode_url.index;
+            match self
+                .download_episode(&client, &args, pahe, episode_url)
+                .await
+            {
+                Ok(EpisodeDownload::Saved(output, stats)) => {
+                    logger.success(format!("done {}", output.yellow()));
+                    batch.finish(Some(&stats));
+                }
+                Ok(EpisodeDownload::Skipped(_)) => batch.finish(None),
+                Err(error) if keep_going => {
+                    logger.failed(format!("episode {}: {error}", index.yellow()));
+                    failures.push(EpisodeFailure { index, error });
+                    batch.finish(None);
+                }
+                Err(error) => return Err(error),
+            }
+            if batch.remaining() > 0 {
+                logger.note(batch.summary());
+            }
+        }
+
+        self.notify_batch(&args, series.as_deref(), &batch, failures.len());
+        report_failures(&failures, logger)?;
+        logger.success("download complete");
+        Ok(())
+    }
+
+    /// sends the end-of-series notification asked for with `--notify`.
+    fn notify_batch(
+        &self,
+        args: &DownloadArgs,
+        series: Option<&str>,
+        batch: &BatchProgress,
+        failed: usize,
+    ) {
+        if args.notify == Some(NotifyWhen::Batch) {
+            notify::send(
+                &notify::batch_notification(series, batch, failed),
+                &self.logger,
+            );
+        }
+    }
+
+    /// downloads up to `--parallel-episodes` episodes at once, one progress block per episode.
+    ///
+    /// without `--keep-going`, the first failure stops the batch: no further episodes are
+    /// started, the ones in flight are cancelled and reported, and then the error is returned.
+    async fn download_in_parallel(
+        &self,
+        client: &reqwest::Client,
+        args: &DownloadArgs,
+        pahe: Option<&PaheClient>,
+        urls: Vec<EpisodeURL>,
+        batch: &mut BatchProgress,
+    ) -> Result<Vec<EpisodeFailure>> {
+        let logger = self.logger.as_ref();
+        let labels = urls
+            .iter()
+            .map(|episode| format!("episode {}", episode.index))
+            .collect();
+        let mut renderer = MultiProgressRenderer::new(logger.level >= LogLevel::Info, labels)
+            .with_color(logger.color);
+        let (events_tx, mut events_rx) = tokio::sync::mpsc::unbounded_channel();
+        let mut tick = tokio::time::interval(Duration::from_millis(80));
+        let cancel = self.cancel.child_token();
+
+        let downloads = stream::iter(urls.into_iter().enumerate())
+            .take_while(|_| std::future::ready(!cancel.is_cancelled()))
+            .map(|(slot, episode_url)| {
+                let events_tx = events_tx.clone();
+                let cancel = &cancel;
+                async move {
+                    let index = episode_url.index;
+                    let download = self.download_episode_in_slot(
+                        client,
+                        args,
+                        pahe,
+                        episode_url,
+                        move |event| {
+                            let _ = events_tx.send((slot, event));
+                        },
+                    );
+                    let result = tokio::select! {
+                        biased;
+                        _ = cancel.cancelled() => Err(PaheError::Message(
+                            if self.cancel.is_cancelled() {
+                                "interrupted".to_string()
+                            } else {
+                                "cancelled after another episode failed".to_string()
+                            },
+                        )),
+                        result = download => result,
+                    };
+                    (slot, index, result)
+                }
+            })
+            .buffer_unordered(args.parallel_episodes.into());
+        let mut downloads = std::pin::pin!(downloads);
+        let mut failures = Vec::new();
+        let mut first_error = None;
+
+        loop {
+            tokio::select! {
+                finished = downloads.next() => {
+                    let Some((slot, index, result)) = finished else { break };
+                    while let Ok((slot, event)) = events_rx.try_recv() {
+                        renderer.handle(slot, event);
                     }
-                    _ = tick.tick() => {
-                        progress_renderer.tick();
+                    let summary = match result {
+                        Ok(EpisodeDownload::Saved(output, stats)) => {
+                            batch.finish(Some(&stats));
+                            format!("{} episode {index}: done {}", "✓".green(), output.yellow())
+                        }
+                        Ok(EpisodeDownload::Skipped(reason)) => {
+                            batch.finish(None);
+                            format!("{} episode {index}: skipped ({reason})", "-".dimmed())
+                        }
+                        Err(error) if first_error.is_some() => {
+                            batch.finish(None);
+                            format!("{} episode {index}: {error}", "✗".red())
+                        }
+                        Err(error) if args.resolve.keep_going => {
+                            batch.finish(None);
+                            let summary = format!("{} episode {index}: {error}", "✗".red());
+                            failures.push(EpisodeFailure { index, error });
+                            summary
+                        }
+                        Err(error) => {
+                            batch.finish(None);
+                            cancel.cancel();
+                            let summary = format!("{} episode {index}: {error}", "✗".red());
+                            first_error = Some(error);
+                            summary
+                        }
+                    };
+                    renderer.finish(slot, summary);
+                    renderer.set_footer(batch.summary());
+                }
+                Some((slot, event)) = events_rx.recv() => renderer.handle(slot, event),
+                _ = tick.tick() => renderer.tick(),
+            }
+        }
+
+        match first_error {
+            Some(error) => Err(error),
+            None => Ok(failures),
+        }
+    }
+
+    /// like [`App::download_episode`], but reports progress through `on_event` and logs nothing.
+    async fn download_episode_in_slot(
+        &self,
+        client: &reqwest::Client,
+        args: &DownloadArgs,
+        pahe: Option<&PaheClient>,
+        episode_url: EpisodeURL,
+        on_event: impl FnMut(DownloadEvent) + Send + Clone,
+    ) -> Result<EpisodeDownload> {
+        let output = episode_output_path(client, args, &episode_url).await?;
+        if !args.force && output_is_complete(client, &episode_url, &output).await {
+            return Ok(EpisodeDownload::Skipped("already complete"));
+        }
+        let Some(output) = resolve_collision(&output, args.on_collision, Path::exists) else {
+            return Ok(EpisodeDownload::Skipped("already exists"));
+        };
+
+        let output_str = output.to_string_lossy().into_owned();
+        let stats = self
+            .fetch_episode(client, args, pahe, episode_url, output, on_event)
+            .await?;
+        Ok(EpisodeDownload::Saved(output_str, stats))
+    }
+
+    /// downloads `episode_url` to `output` and appends the result to the download history.
+    async fn fetch_episode(
+        &self,
+        client: &reqwest::Client,
+        args: &DownloadArgs,
+        pahe: Option<&PaheClient>,
+        episode_url: EpisodeURL,
+        output: PathBuf,
+        on_event: impl FnMut(DownloadEvent) + Send + Clone,
+    ) -> Result<DownloadStats> {
+        let stats = self
+            .transfer_episode(
+                client,
+                args,
+                pahe,
+                episode_url.clone(),
+                output.clone(),
+                on_event,
+            )
+            .await?;
+
+        if let Some(path) = args.history.clone().or_else(default_history_path) {
+            let entry = HistoryEntry::new(&episode_url, &output, &stats);
+            if let Err(error) = append_history(&path, &entry) {
+                self.logger.warn(error.to_string());
+            }
+        }
+        if args.notify == Some(NotifyWhen::Episode) {
+            let notification = notify::episode_notification(
+                episode_url.series_title.as_deref(),
+                episode_url.index,
+                &output.to_string_lossy(),
+            );
+            notify::send(&notification, &self.logger);
+        }
+        if let Some(command) = &args.on_complete {
+            self.run_on_complete(command, args.on_complete_wait, &episode_url, &output)
+                .await;
+        }
+        Ok(stats)
+    }
+
+    /// spawns `--on-complete` for a finished episode. a failing command is logged, never
+    /// returned; without `wait` its exit is reported whenever it comes.
+    async fn run_on_complete(
+        &self,
+        command: &str,
+        wait: bool,
+        episode_url: &EpisodeURL,
+        output: &Path,
+    ) {
+        let args = on_complete_args(command, output, episode_url);
+        let Some((program, rest)) = args.split_first() else {
+            return;
+        };
+        let mut command = tokio::process::Command::new(program);
+        command.args(rest).stdin(std::process::Stdio::null());
+        self.logger
+            .debug("on-complete", format!("command: {:?}", command));
+
+        let index = episode_url.index;
+        let mut child = match command.spawn() {
+            Ok(child) => child,
+            Err(err) => {
+                self.logger
+                    .warn(format!("episode {index}: failed to run `{program}`: {err}"));
+                return;
+            }
+        };
+        let logger = self.logger.clone();
+        let program = program.clone();
+        let report = async move {
+            match child.wait().await {
+                Ok(status) if !status.success() => {
+                    logger.warn(format!("episode {index}: `{program}` exited with {status}"));
+                }
+                Ok(_) => {}
+                Err(err) => logger.warn(format!(
+                    "episode {index}: waiting on `{program}` failed: {err}"
+                )),
+            }
+        };
+        if wait {
+            report.await;
+        } else {
+            tokio::spawn(report);
+        }
+    }
+
+    /// the transfer behind [`App::fetch_episode`]; when kwik has already rotated the link,
+    /// its variant is resolved again and the download retried once.
+    async fn transfer_episode(
+        &self,
+        client: &reqwest::Client,
+        args: &DownloadArgs,
+        pahe: Option<&PaheClient>,
+        mut episode_url: EpisodeURL,
+        output: PathBuf,
+        on_event: impl FnMut(DownloadEvent) + Send + Clone,
+    ) -> Result<DownloadStats> {
+        let request = download_request(args, episode_url.clone(), output.clone(), &self.cancel);
+        let error = match download_with_client(client, request, on_event.clone()).await {
+            Ok(summary) => return Ok(summary.stats),
+            Err(error) => error,
+        };
+        let (Some(pahe), Some(variant)) = (pahe, &episode_url.variant) else {
+            return Err(download_failed(error));
+        };
+        if !link_expired(&error) {
+            return Err(download_failed(error));
+        }
+
+        self.logger.debug(
+            "download",
+            format!(
+                "episode {} link expired ({error}); resolving it again",
+                episode_url.index
+            ),
+        );
+        let fresh = pahe.resolve_download(variant).await?;
+        episode_url.referer = fresh.referer;
+        episode_url.url = fresh.direct_link;
+        let request = download_request(args, episode_url, output, &self.cancel);
+        download_with_client(client, request, on_event)
+            .await
+            .map(|summary| summary.stats)
+            .map_err(download_failed)
+    }
+
+    /// probes the resolved links, [`PROBE_CONCURRENCY`] at a time, and prints per-episode and
+    /// total sizes.
+    async fn estimate_batch_size(&self, client: &reqwest::Client, urls: &[EpisodeURL]) -> u64 {
+        let logger = self.logger.as_ref();
+        let probes = stream::iter(urls)
+            .map(|episode| async move {
+                let size = probe_with_client(client, &episode.referer, &episode.url)
+                    .await
+                    .ok()
+                    .and_then(|probe| probe.total_bytes);
+                (episode.index, size)
+            })
+            .buffered(PROBE_CONCURRENCY)
+            .collect::<Vec<_>>();
+
+        let sizes = logger.while_loading("estimating batch size", probes).await;
+
+        let total: u64 = sizes.iter().filter_map(|(_, size)| *size).sum();
+        let unknown = sizes.iter().filter(|(_, size)| size.is_none()).count();
+        let lines = sizes
+            .iter()
+            .map(|(index, size)| {
+                format!(
+                    "{} {}: {}",
+                    "episode".dimmed(),
+                    index,
+                    size.map(format_bytes)
+                        .unwrap_or_else(|| "unknown".to_string())
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n  ");
+        let unknown_note = if unknown > 0 {
+            format!(" ({unknown} unknown)")
+        } else {
+            String::new()
+        };
+
+        logger.success(format!(
+            "batch is ~{} across {} file(s){unknown_note}\n  {lines}",
+            format_bytes(total).yellow(),
+            sizes.len()
+        ));
+        total
+    }
+
+    async fn download_episode(
+        &self,
+        client: &reqwest::Client,
+        args: &DownloadArgs,
+        pahe: Option<&PaheClient>,
+        episode_url: EpisodeURL,
+    ) -> Result<EpisodeDownload> {
+        let logger = self.logger.as_ref();
+        if args.output.as_deref() == Some(STDOUT_OUTPUT) {
+            return self.download_to_stdout(client, args, episode_url).await;
+        }
+
+        let output = logger
+            .while_loading(
+                "inferring output filename",
+                episode_output_path(client, args, &episode_url),
+            )
+            .await?;
+
+        if !args.force
+            && logger
+                .while_loading(
+                    "checking existing file",
+                    output_is_complete(client, &episode_url, &output),
+                )
+                .await
+        {
+            logger.success(format!(
+                "skipped {} (already complete)",
+                output.to_string_lossy().yellow()
+            ));
+            return Ok(EpisodeDownload::Skipped("already complete"));
+        }
+
+        let Some(output) = resolve_collision(&output, args.on_collision, Path::exists) else {
+            logger.success(format!(
+                "skipped {} (already exists)",
+                output.to_string_lossy().yellow()
+            ));
+            return Ok(EpisodeDownload::Skipped("already exists"));
+        };
+
+        let output_str = output.to_string_lossy().into_owned();
+        let mut progress_renderer =
+            DownloadProgressRenderer::new(logger.level >= LogLevel::Info).with_color(logger.color);
+        let (events_tx, mut events_rx) = tokio::sync::mpsc::unbounded_channel();
+        let mut tick = tokio::time::interval(Duration::from_millis(80));
+        let mut download_fut = std::pin::pin!(self.fetch_episode(
+            client,
+            args,
+            pahe,
+            episode_url,
+            output,
+            move |event| {
+                let _ = events_tx.send(event);
+            },
+        ));
+
+        let download_result = loop {
+            tokio::select! {
+                result = &mut download_fut => break result,
+                maybe_event = events_rx.recv() => {
+                    if let Some(event) = maybe_event {
+                        progress_renderer.handle(event);
                     }
                 }
+                _ = tick.tick() => {
+                    progress_renderer.tick();
+                }
+            }
+        };
+
+        while let Ok(event) = events_rx.try_recv() {
+            progress_renderer.handle(event);
+        }
+
+        let stats = download_result?;
+        Ok(EpisodeDownload::Saved(output_str, stats))
+    }
+
+    /// pipes the episode to stdout (`--output -`); all logging already goes to stderr.
+    async fn download_to_stdout(
+        &self,
+        client: &reqwest::Client,
+        args: &DownloadArgs,
+        episode_url: EpisodeURL,
+    ) -> Result<EpisodeDownload> {
+        let request = download_request(
+            args,
+            episode_url,
+            PathBuf::from(STDOUT_OUTPUT),
+            &self.cancel,
+        );
+        let mut stdout = tokio::io::stdout();
+        let stats = self
+            .logger
+            .while_loading(
+                "streaming to stdout",
+                download_to_writer_with_client(client, request, &mut stdout, |_| {}),
+            )
+            .await
+            .map_err(download_failed)?;
+
+        let output = format!("stdout ({})", format_bytes(stats.bytes));
+        Ok(EpisodeDownload::Saved(output, stats))
+    }
+}
+
+/// how one episode's download ended.
+enum EpisodeDownload {
+    Saved(String, DownloadStats),
+    Skipped(&'static str),
+}
+
+/// every ctrl-c the process receives.
+fn ctrl_c_signals() -> impl Stream<Item = ()> + Unpin {
+    Box::pin(stream::unfold((), |()| async {
+        tokio::signal::ctrl_c().await.ok().map(|()| ((), ()))
+    }))
+}
+
+/// cancels `cancel` on the first signal and calls `force_quit` on the second.
+async fn watch_interrupts(
+    mut signals: impl Stream<Item = ()> + Unpin,
+    cancel: CancellationToken,
+    force_quit: impl FnOnce(),
+) {
+    if signals.next().await.is_none() {
+        return;
+    }
+    cancel.cancel();
+
+    if signals.next().await.is_some() {
+        force_quit();
+    }
+}
+
+/// fills the parsed arguments with defaults from the config file, exiting on a broken one.
+fn apply_config(cli: &mut Cli, matches: &ArgMatches) {
+    let leaf = matches.subcommand().map_or(matches, |(_, sub)| sub);
+    let explicit = |id: &str| {
+        matches!(
+            leaf.value_source(id),
+            Some(ValueSource::CommandLine | ValueSource::EnvVariable)
+        )
+    };
+
+    let config_path = match &cli.command {
+        Some(Commands::Download(args)) => &args.resolve.app_args.config,
+        Some(Commands::Resolve(args)) => &args.resolve.app_args.config,
+        Some(Commands::Play(args)) => &args.resolve.app_args.config,
+        Some(Commands::List(args)) => &args.series_args.app_args.config,
+        Some(Commands::Metadata(args)) => &args.series_args.app_args.config,
+        Some(Commands::Search(args)) => &args.app_args.config,
+        Some(Commands::Completions(_)) => return,
+        None => &cli.download_args.resolve.app_args.config,
+    };
+    let config = Config::load(config_path.as_deref()).unwrap_or_else(|err| {
+        clap::Error::raw(clap::error::ErrorKind::InvalidValue, format!("{err}\n")).exit()
+    });
+
+    match &mut cli.command {
+        Some(Commands::Download(args)) => config.apply_download(args, explicit),
+        Some(Commands::Resolve(args)) => config.apply_resolve(&mut args.resolve, explicit),
+        Some(Commands::Play(args)) => config.apply_resolve(&mut args.resolve, explicit),
+        Some(Commands::List(args)) => config.apply_connection(
+            &mut args.series_args.connection,
+            &mut args.series_args.app_args,
+            explicit,
+        ),
+        Some(Commands::Metadata(args)) => config.apply_connection(
+            &mut args.series_args.connection,
+            &mut args.series_args.app_args,
+            explicit,
+        ),
+        Some(Commands::Search(args)) => {
+            config.apply_connection(&mut args.connection, &mut args.app_args, explicit)
+        }
+        Some(Commands::Completions(_)) => {}
+        None => config.apply_download(&mut cli.download_args, explicit),
+    }
+}
+
+/// writes the completion script for `shell`, named after the binary that was invoked.
+fn write_completions(shell: clap_complete::Shell, out: &mut dyn std::io::Write) {
+    let bin_name = std::env::args()
+        .next()
+        .as_deref()
+        .map(Path::new)
+        .and_then(Path::file_stem)
+        .and_then(|stem| stem.to_str())
+        .map(String::from)
+        .unwrap_or_else(|| "pahe-cli".to_string());
+    clap_complete::generate(shell, &mut Cli::command(), bin_name, out);
+}
+
+/// arguments that make `player` stream `link` with the headers kwik checks.
+fn player_args(player: &str, link: &DirectLink, user_agent: &str) -> Result<Vec<String>> {
+    let args = match player {
+        "mpv" => vec![
+            format!("--referrer={}", link.referer),
+            format!("--user-agent={user_agent}"),
+            format!("--http-header-fields=Referer: {}", link.referer),
+        ],
+        "vlc" => vec![
+            format!("--http-referrer={}", link.referer),
+            format!("--http-user-agent={user_agent}"),
+        ],
+        other => {
+            return Err(PaheError::Message(format!("unsupported player: {other}")));
+        }
+    };
+
+    Ok(args
+        .into_iter()
+        .chain(std::iter::once(link.direct_link.clone()))
+        .collect())
+}
+
+/// splits `--on-complete` into arguments and fills in the finished episode.
+///
+/// placeholders are replaced after splitting, so a file name with spaces stays one argument.
+fn on_complete_args(command: &str, file: &Path, episode_url: &EpisodeURL) -> Vec<String> {
+    let file = file.to_string_lossy();
+    let title = episode_url.series_title.as_deref().unwrap_or_default();
+    let episode = episode_url.index.to_string();
+    split_command_line(command)
+        .unwrap_or_default()
+        .into_iter()
+        .map(|arg| {
+            arg.replace("{file}", &file)
+                .replace("{title}", title)
+                .replace("{episode}", &episode)
+        })
+        .collect()
+}
+
+/// runs `binary` with `args` and waits for it to exit.
+fn spawn_player(binary: &str, args: &[String], logger: &CliLogger) -> Result<()> {
+    let mut command = Command::new(binary);
+    command.args(args);
+    logger.debug("player", format!("command: {:?}", command));
+
+    let mut child = command.spawn().map_err(|err| match err.kind() {
+        std::io::ErrorKind::NotFound => PaheError::Message(format!(
+            "player `{binary}` was not found; install it or pick another with --player"
+        )),
+        _ => PaheError::CommandError(err),
+    })?;
+    child.wait()?;
+    Ok(())
+}
+
+/// a file `--dry-run` would write.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct PlannedFile {
+    episode: u32,
+    path: PathBuf,
+    /// from probing the direct link; `None` when the server didn't say.
+    size: Option<u64>,
+}
+
+/// works out where each episode would be saved, leaving out the ones a download would skip,
+/// without touching the file system.
+async fn plan_downloads(
+    client: &reqwest::Client,
+    args: &DownloadArgs,
+    urls: &[EpisodeURL],
+) -> Result<Vec<PlannedFile>> {
+    let planned = stream::iter(urls)
+        .map(|episode_url| async move {
+            let output = episode_output_path(client, args, episode_url).await?;
+            if !args.force && output_is_complete(client, episode_url, &output).await {
+                return Ok(None);
+            }
+            let Some(path) = resolve_collision(&output, args.on_collision, Path::exists) else {
+                return Ok(None);
             };
+            let size = probe_with_client(client, &episode_url.referer, &episode_url.url)
+                .await
+                .ok()
+                .and_then(|probe| probe.total_bytes);
+            Ok(Some(PlannedFile {
+                episode: episode_url.index,
+                path,
+                size,
+            }))
+        })
+        .buffered(PROBE_CONCURRENCY)
+        .collect::<Vec<Result<_>>>()
+        .await;
+
+    planned.into_iter().filter_map(Result::transpose).collect()
+}
+
+/// one line per planned file followed by the total, e.g. `episode 3: Frieren - 03.mp4 (1.20 GB)`.
+fn format_plan(plan: &[PlannedFile]) -> String {
+    let total: u64 = plan.iter().filter_map(|file| file.size).sum();
+    let unknown = plan.iter().filter(|file| file.size.is_none()).count();
+    let mut lines: Vec<String> = plan
+        .iter()
+        .map(|file| {
+            let size = file
+                .size
+                .map(format_bytes)
+                .unwrap_or_else(|| "unknown size".to_string());
+            format!("episode {}: {} ({size})", file.episode, file.path.display())
+        })
+        .collect();
+    let unknown_note = if unknown > 0 {
+        format!(" ({unknown} unknown)")
+    } else {
+        String::new()
+    };
+    lines.push(format!(
+        "dry run: would write {} file(s), ~{}{unknown_note}",
+        plan.len(),
+        format_bytes(total)
+    ));
+    lines.join("\n")
+}
 
-            while let Ok(event) = events_rx.try_recv() {
-                progress_renderer.handle(event);
+/// whether `output` exists and matches the size the server reports for the episode.
+async fn output_is_complete(
+    client: &reqwest::Client,
+    episode_url: &EpisodeURL,
+    output: &Path,
+) -> bool {
+    let Ok(metadata) = tokio::fs::metadata(output).await else {
+        return false;
+    };
+    let remote_size = probe_with_client(client, &episode_url.referer, &episode_url.url)
+        .await
+        .ok()
+        .and_then(|probe| probe.total_bytes);
+
+    is_already_complete(metadata.is_file(), metadata.len(), remote_size)
+}
+
+/// picks the output path from `--output`, the templates or the server's file name, under `--dir`.
+async fn episode_output_path(
+    client: &reqwest::Client,
+    args: &DownloadArgs,
+    episode_url: &EpisodeURL,
+) -> Result<PathBuf> {
+    let templated = args
+        .output_template
+        .as_deref()
+        .map(|template| render_episode_template(template, episode_url))
+        .filter(|name| !name.is_empty());
+    let file_name: PathBuf = match (&args.output, templated) {
+        (Some(path), _) => path.into(),
+        (None, Some(name)) => name.into(),
+        (None, None) => {
+            let guessed =
+                suggest_filename_with_client(client, &episode_url.referer, &episode_url.url)
+                    .await
+                    .map_err(|err| {
+                        PaheError::Message(format!("failed to infer output filename: {err}"))
+                    })?;
+            match &args.name_template {
+                Some(template) => templated_file_name(template, episode_url, &guessed),
+                None => guessed.into(),
             }
+        }
+    };
 
-            download_result.map_err(|err| PaheError::Message(format!("download failed: {err}")))?;
-            logger.success(format!("done {}", output_str.yellow()));
+    Ok(match &args.dir {
+        Some(dir) => dir.join(file_name),
+        None => file_name,
+    })
+}
+
+/// keeps the variants hand-picked for one episode from landing on the same file: without a
+/// naming option they are named by [`VARIANT_NAME_TEMPLATE`], and an `--output` or template
+/// that names two of them alike is refused before anything is downloaded.
+fn name_picked_variants(args: &mut DownloadArgs, urls: &[EpisodeURL]) -> Result<()> {
+    let mut episodes = HashSet::new();
+    if urls
+        .iter()
+        .all(|episode_url| episodes.insert(episode_url.index))
+    {
+        return Ok(());
+    }
+
+    if args.output.is_none() && args.output_template.is_none() && args.name_template.is_none() {
+        args.name_template = Some(VARIANT_NAME_TEMPLATE.to_string());
+        return Ok(());
+    }
+
+    let template = args
+        .output_template
+        .as_ref()
+        .or(args.name_template.as_ref());
+    let mut names = HashSet::new();
+    for episode_url in urls {
+        let name = args
+            .output
+            .clone()
+            .or_else(|| template.map(|template| render_episode_template(template, episode_url)));
+        if !names.insert((episode_url.index, name)) {
+            return Err(PaheError::Message(format!(
+                "the variants picked for episode {} would be saved under the same name; \
+                 use a template with {{quality}} and {{lang}}",
+                episode_url.index
+            )));
         }
+    }
+    Ok(())
+}
 
-        logger.success("download complete");
-        Ok(())
+fn download_failed(error: DownloaderError) -> PaheError {
+    PaheError::Message(format!("download failed: {error}"))
+}
+
+/// kwik direct links rotate; a `403`, `404` or `410` on download means this one expired.
+fn link_expired(error: &DownloaderError) -> bool {
+    matches!(
+        error,
+        DownloaderError::HttpStatus { status, .. }
+            if matches!(status.as_u16(), 403 | 404 | 410)
+    )
+}
+
+fn download_request(
+    args: &DownloadArgs,
+    episode_url: EpisodeURL,
+    output: PathBuf,
+    cancel: &CancellationToken,
+) -> DownloadRequest {
+    // kwik's cdn may check the origin of the page that links to it, not just the referer
+    let origin = reqwest::Url::parse(&episode_url.referer)
+        .ok()
+        .map(|referer| referer.origin())
+        .filter(|origin| origin.is_tuple())
+        .and_then(|origin| {
+            reqwest::header::HeaderValue::from_str(&origin.ascii_serialization()).ok()
+        });
+    let mut request = DownloadRequest::new(episode_url.referer, episode_url.url, output);
+    if let Some(origin) = origin {
+        request = request.header(reqwest::header::ORIGIN, origin);
+    }
+    let request = request
+        .cancellation(cancel.clone())
+        .connections(args.connections)
+        .max_bytes_per_sec(args.limit_rate)
+        .read_timeout(Duration::from_secs(args.resolve.connection.timeout))
+        .stall_timeout(Duration::from_secs(args.stall_timeout));
+
+    match &args.sha256 {
+        Some(digest) => request.expected_sha256(digest),
+        None => request,
     }
 }
 
+/// renders `--name-template` for an episode, keeping the extension of the server's file name.
+fn templated_file_name(template: &str, episode_url: &EpisodeURL, guessed: &str) -> PathBuf {
+    let stem = render_episode_template(template, episode_url);
+    if stem.is_empty() {
+        return guessed.into();
+    }
+
+    match Path::new(guessed).extension() {
+        Some(extension) => PathBuf::from(format!("{stem}.{}", extension.to_string_lossy())),
+        None => PathBuf::from(stem),
+    }
+}
+
+/// fills a file name template with the resolved episode's metadata.
+fn render_episode_template(template: &str, episode_url: &EpisodeURL) -> String {
+    let episode = episode_url.index.to_string();
+    let quality = episode_url.quality.to_string();
+    render_name_template(
+        template,
+        &[
+            ("series", episode_url.series_title.as_deref()),
+            ("title", episode_url.series_title.as_deref()),
+            ("episode", Some(&episode)),
+            ("episode_title", episode_url.episode_title.as_deref()),
+            ("quality", Some(&quality)),
+            ("lang", Some(&episode_url.language)),
+        ],
+    )
+}
+
 #[cfg(test)]
 mod tests {
+    use std::path::{Path, PathBuf};
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    use clap::Parser;
+    use pahe::client::{EpisodeInfo, EpisodeVariant};
+    use pahe_downloader::{CancellationToken, DownloaderError};
+
+    use pahe::prelude::DirectLink;
+
+    use super::{
+        Cli, Commands, PlannedFile, format_plan, link_expired, name_picked_variants,
+        on_complete_args, plan_downloads, player_args, render_episode_template, watch_interrupts,
+        write_completions,
+    };
+    use crate::args::{CollisionPolicy, EpisodeSelection};
     use crate::constants::*;
+    use crate::episode::EpisodeURL;
     use crate::utils::*;
 
     #[test]
-    fn normalize_series_link_accepts_anime_link() {
-        let input =
-            format!("https://{ANIMEPAHE_DOMAIN}/anime/123e4567-e89b-12d3-a456-426614174000");
-        let normalized = normalize_series_link(&input).expect("anime link should be valid");
+    fn episodes_counted_from_the_end_parse_as_flag_values() {
+        for selection in ["-1", "-3..-1"] {
+            let cli = Cli::try_parse_from(["pahe", "download", "--series", "x", "-e", selection])
+                .unwrap_or_else(|err| panic!("`-e {selection}` should parse: {err}"));
+            let Some(Commands::Download(args)) = cli.command else {
+                panic!("expected the download command");
+            };
+            assert_eq!(args.resolve.episodes.to_string(), selection);
+        }
+
+        let cli = Cli::try_parse_from(["pahe", "--episodes", "-2..-1", "--series", "x"])
+            .expect("a bare download should parse too");
+        assert!(cli.download_args.resolve.episodes.counts_from_end());
+    }
+
+    #[test]
+    fn resolve_collision_applies_policy() {
+        let taken = [
+            PathBuf::from("out/ep01.mp4"),
+            PathBuf::from("out/ep01 (1).mp4"),
+        ];
+        let exists = |path: &Path| taken.iter().any(|taken| taken == path);
+        let path = Path::new("out/ep01.mp4");
+
+        assert_eq!(
+            resolve_collision(path, CollisionPolicy::Rename, exists),
+            Some(PathBuf::from("out/ep01 (2).mp4"))
+        );
+        assert_eq!(resolve_collision(path, CollisionPolicy::Skip, exists), None);
+        assert_eq!(
+            resolve_collision(path, CollisionPolicy::Overwrite, exists),
+            Some(path.to_path_buf())
+        );
         assert_eq!(
-            normalized,
-            format!("https://{ANIMEPAHE_DOMAIN}/anime/123e4567-e89b-12d3-a456-426614174000")
+            resolve_collision(Path::new("out/ep02.mp4"), CollisionPolicy::Skip, exists),
+            Some(PathBuf::from("out/ep02.mp4"))
         );
     }
 
     #[test]
-    fn normalize_series_link_accepts_anime_id() {
-        let input = "123e4567-e89b-12d3-a456-426614174000";
-        let normalized = normalize_series_link(input).expect("anime id should be valid");
+    fn only_expired_link_statuses_trigger_a_re_resolve() {
+        let status = |code: u16| DownloaderError::HttpStatus {
+            context: "downloading https://cdn.example/ep.mp4".to_string(),
+            status: reqwest::StatusCode::from_u16(code).unwrap(),
+        };
+
+        assert!(link_expired(&status(403)));
+        assert!(link_expired(&status(404)));
+        assert!(!link_expired(&status(500)));
+        assert!(!link_expired(&DownloaderError::Cancelled));
+    }
+
+    #[test]
+    fn describe_variant_strips_markup() {
+        let variant = EpisodeVariant::from_anchor_html(
+            "https://pahe.win/abc",
+            "SubsPlease &middot; 1080p <span class=\"badge\">BD</span>",
+        );
         assert_eq!(
-            normalized,
-            format!("https://{ANIMEPAHE_DOMAIN}/anime/123e4567-e89b-12d3-a456-426614174000")
+            describe_variant(&variant),
+            "1080p jp BD — SubsPlease · 1080p BD"
         );
     }
 
     #[test]
-    fn normalize_series_link_accepts_anime_and_session_id_pair() {
-        let input = "123e4567-e89b-12d3-a456-426614174000/3cf1e5860ff5e9f766b36241c4dd6d48de3ef45d41183ecd079e1772aeb27c3c";
-        let normalized =
-            normalize_series_link(input).expect("anime/session id pair should be valid");
+    fn describe_variant_shows_declared_size() {
+        let variant = EpisodeVariant::from_anchor_html(
+            "https://pahe.win/abc",
+            "SubsPlease &middot; 720p (140MB) <span class=\"badge\">eng</span>",
+        );
         assert_eq!(
-            normalized,
-            format!("https://{ANIMEPAHE_DOMAIN}/anime/123e4567-e89b-12d3-a456-426614174000")
+            describe_variant(&variant),
+            "720p en · 140.00 MB — SubsPlease · 720p (140MB) eng"
         );
     }
 
     #[test]
-    fn normalize_series_link_accepts_play_link() {
+    fn describe_episode_shows_air_day_and_filler() {
+        let episode = EpisodeInfo {
+            episode: 3,
+            title: Some("Pilot".to_string()),
+            aired_at: Some("2023-10-06 14:20:43".to_string()),
+            filler: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            describe_episode(&episode),
+            "episode 3 - Pilot · 2023-10-06 · filler"
+        );
+        assert_eq!(
+            describe_episode(&EpisodeInfo {
+                episode: 4,
+                ..Default::default()
+            }),
+            "episode 4"
+        );
+    }
+
+    #[test]
+    fn normalize_series_id_accepts_anime_link() {
+        let input =
+            format!("https://{ANIMEPAHE_DOMAIN}/anime/123e4567-e89b-12d3-a456-426614174000");
+        let normalized = normalize_series_id(&input).expect("anime link should be valid");
+        assert_eq!(normalized, "123e4567-e89b-12d3-a456-426614174000");
+    }
+
+    #[test]
+    fn normalize_series_id_accepts_anime_id() {
+        let input = "123e4567-e89b-12d3-a456-426614174000";
+        let normalized = normalize_series_id(input).expect("anime id should be valid");
+        assert_eq!(normalized, "123e4567-e89b-12d3-a456-426614174000");
+    }
+
+    #[test]
+    fn normalize_series_id_accepts_anime_and_session_id_pair() {
+        let input = "123e4567-e89b-12d3-a456-426614174000/3cf1e5860ff5e9f766b36241c4dd6d48de3ef45d41183ecd079e1772aeb27c3c";
+        let normalized = normalize_series_id(input).expect("anime/session id pair should be valid");
+        assert_eq!(normalized, "123e4567-e89b-12d3-a456-426614174000");
+    }
+
+    #[test]
+    fn normalize_series_id_accepts_play_link() {
         let input = format!(
             "https://{ANIMEPAHE_DOMAIN}/play/123e4567-e89b-12d3-a456-426614174000/3cf1e5860ff5e9f766b36241c4dd6d48de3ef45d41183ecd079e1772aeb27c3c"
         );
-        let normalized = normalize_series_link(&input).expect("play link should be valid");
+        let normalized = normalize_series_id(&input).expect("play link should be valid");
+        assert_eq!(normalized, "123e4567-e89b-12d3-a456-426614174000");
+    }
+
+    #[test]
+    fn render_name_template_fills_and_sanitizes() {
+        let rendered = render_name_template(
+            "{series} - {episode} - {episode_title}",
+            &[
+                ("series", Some("Frieren")),
+                ("episode", Some("12")),
+                ("episode_title", Some("Friend: A/B?")),
+            ],
+        );
+        assert_eq!(rendered, "Frieren - 12 - Friend A B");
+    }
+
+    #[test]
+    fn render_name_template_collapses_missing_title() {
+        let template = "{series} - {episode} - {episode_title}";
+        let values = |title| {
+            [
+                ("series", Some("Frieren")),
+                ("episode", Some("12")),
+                ("episode_title", title),
+            ]
+        };
+
+        assert_eq!(
+            render_name_template(template, &values(None)),
+            "Frieren - 12"
+        );
+        assert_eq!(
+            render_name_template(template, &values(Some("  "))),
+            "Frieren - 12"
+        );
+        assert_eq!(
+            render_name_template(
+                "{episode_title} - {episode}",
+                &[("episode_title", None), ("episode", Some("3"))]
+            ),
+            "3"
+        );
+    }
+
+    #[test]
+    fn render_name_template_pads_numbers() {
+        let values = [("episode", Some("7")), ("quality", Some("1080"))];
+
+        assert_eq!(
+            render_name_template("S01E{episode:02} [{quality}p]", &values),
+            "S01E07 [1080p]"
+        );
+        assert_eq!(
+            render_name_template("{episode:03}", &[("episode", Some("112"))]),
+            "112"
+        );
+        assert_eq!(
+            render_name_template("{unknown:02}", &values),
+            "{unknown:02}"
+        );
+    }
+
+    #[test]
+    fn render_episode_template_fills_output_template() {
+        let episode = EpisodeURL {
+            series_title: Some("Re:Zero <Season 2>".to_string()),
+            ..EpisodeURL::fixture(3)
+        };
+
+        assert_eq!(
+            render_episode_template(
+                "{title} - S01E{episode:02} [{quality}p {lang}].mkv",
+                &episode
+            ),
+            "Re Zero Season 2 - S01E03 [1080p jp].mkv"
+        );
+    }
+
+    #[test]
+    fn variants_picked_for_one_episode_get_distinct_names() {
+        let picked = |language: &str| EpisodeURL {
+            language: language.to_string(),
+            ..EpisodeURL::fixture(3)
+        };
+        let urls = [picked("jp"), picked("en")];
+        let download_args =
+            |flags: &[&str]| Cli::parse_from(["pahe"].iter().chain(flags)).download_args;
+
+        let mut args = download_args(&[]);
+        name_picked_variants(&mut args, &urls).expect("default names should be made distinct");
+        assert_eq!(args.name_template.as_deref(), Some(VARIANT_NAME_TEMPLATE));
+
+        let mut args = download_args(&["--name-template", "{series} - {episode:02} {lang}"]);
+        assert!(name_picked_variants(&mut args, &urls).is_ok());
+
+        let mut args = download_args(&["--name-template", "{series} - {episode:02}"]);
+        assert!(name_picked_variants(&mut args, &urls).is_err());
+
+        let mut args = download_args(&["--output", "episode.mp4"]);
+        assert!(name_picked_variants(&mut args, &urls).is_err());
+
+        let mut args = download_args(&[]);
+        name_picked_variants(&mut args, &urls[..1]).expect("a single variant needs no template");
+        assert_eq!(args.name_template, None);
+    }
+
+    #[tokio::test]
+    async fn first_interrupt_cancels_download_and_second_forces_quit() {
+        let cancel = CancellationToken::new();
+        let forced = Arc::new(AtomicBool::new(false));
+        let (signal_tx, signal_rx) = futures::channel::mpsc::unbounded::<()>();
+        let watcher = tokio::spawn(watch_interrupts(signal_rx, cancel.clone(), {
+            let forced = Arc::clone(&forced);
+            move || forced.store(true, Ordering::SeqCst)
+        }));
+
+        // stands in for a download that only stops once its token fires
+        let download = {
+            let cancel = cancel.clone();
+            tokio::spawn(async move {
+                cancel.cancelled().await;
+                Err::<(), _>(DownloaderError::Cancelled)
+            })
+        };
+
+        signal_tx.unbounded_send(()).unwrap();
+        let result = download.await.unwrap();
+        assert!(matches!(result, Err(DownloaderError::Cancelled)));
+        assert!(!forced.load(Ordering::SeqCst));
+
+        signal_tx.unbounded_send(()).unwrap();
+        watcher.await.unwrap();
+        assert!(forced.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn write_completions_covers_subcommands() {
+        let mut script = Vec::new();
+        write_completions(clap_complete::Shell::Bash, &mut script);
+        let script = String::from_utf8(script).expect("completions should be utf-8");
+
+        assert!(!script.is_empty());
+        assert!(script.contains("download"));
+    }
+
+    #[tokio::test]
+    async fn dry_run_plans_templated_files_without_writing() {
+        use wiremock::matchers::{header, method, path};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        for (episode, size) in [(1, 1_048_576u64), (2, 2_097_152)] {
+            Mock::given(method("HEAD"))
+                .and(path(format!("/ep{episode}.mp4")))
+                .respond_with(ResponseTemplate::new(200).insert_header(
+                    "content-disposition",
+                    format!(r#"attachment; filename="AnimePahe_Frieren_-_0{episode}.mp4""#),
+                ))
+                .mount(&server)
+                .await;
+            Mock::given(method("GET"))
+                .and(path(format!("/ep{episode}.mp4")))
+                .and(header("range", "bytes=0-0"))
+                .respond_with(
+                    ResponseTemplate::new(206)
+                        .insert_header("content-range", format!("bytes 0-0/{size}"))
+                        .set_body_bytes(vec![0u8]),
+                )
+                .expect(1)
+                .mount(&server)
+                .await;
+        }
+
+        let dir = std::env::temp_dir().join(format!("pahe-dry-run-{}", std::process::id()));
+        let dir_arg = dir.to_string_lossy().into_owned();
+        let args = Cli::parse_from([
+            "pahe",
+            "--dry-run",
+            "--dir",
+            &dir_arg,
+            "--name-template",
+            "{series} - {episode:02}",
+        ])
+        .download_args;
+        let urls: Vec<EpisodeURL> = [1, 2]
+            .into_iter()
+            .map(|index| EpisodeURL {
+                url: format!("{}/ep{index}.mp4", server.uri()),
+                ..EpisodeURL::fixture(index)
+            })
+            .collect();
+
+        let plan = plan_downloads(&reqwest::Client::new(), &args, &urls)
+            .await
+            .expect("plan should resolve");
+
+        assert!(args.dry_run);
+        assert_eq!(
+            plan,
+            [
+                PlannedFile {
+                    episode: 1,
+                    path: dir.join("Frieren - 01.mp4"),
+                    size: Some(1_048_576),
+                },
+                PlannedFile {
+                    episode: 2,
+                    path: dir.join("Frieren - 02.mp4"),
+                    size: Some(2_097_152),
+                },
+            ]
+        );
+        assert!(!dir.exists(), "a dry run must not create anything");
+        assert!(
+            format_plan(&plan).ends_with("dry run: would write 2 file(s), ~3.00 MB"),
+            "{}",
+            format_plan(&plan)
+        );
+    }
+
+    #[test]
+    fn on_complete_args_fill_placeholders_after_splitting() {
+        let episode_url = EpisodeURL {
+            series_title: Some("Sousou no Frieren".to_string()),
+            ..EpisodeURL::fixture(7)
+        };
+
+        assert_eq!(
+            on_complete_args(
+                r#"notify-send "done: {title}" 'episode {episode}' {file}"#,
+                Path::new("/media/anime/Frieren - 07.mkv"),
+                &episode_url,
+            ),
+            [
+                "notify-send",
+                "done: Sousou no Frieren",
+                "episode 7",
+                "/media/anime/Frieren - 07.mkv",
+            ]
+        );
+        assert_eq!(
+            on_complete_args(
+                r"mkvpropedit {file} --edit\ info",
+                Path::new("a b.mkv"),
+                &episode_url
+            ),
+            ["mkvpropedit", "a b.mkv", "--edit info"]
+        );
+        assert_eq!(
+            crate::args::split_command_line(r#"sh -c "echo \"hi\"""#).unwrap(),
+            ["sh", "-c", r#"echo "hi""#]
+        );
+        assert_eq!(
+            crate::args::split_command_line("a '' b").unwrap(),
+            ["a", "", "b"]
+        );
+        assert!(crate::args::split_command_line("echo 'oops").is_err());
+    }
+
+    #[test]
+    fn player_args_pass_referer_and_user_agent() {
+        let link = DirectLink {
+            referer: "https://kwik.si/f/abc".to_string(),
+            direct_link: "https://cdn.example/ep5.mp4".to_string(),
+        };
+
+        assert_eq!(
+            player_args("mpv", &link, "pahe/1.0").unwrap(),
+            [
+                "--referrer=https://kwik.si/f/abc",
+                "--user-agent=pahe/1.0",
+                "--http-header-fields=Referer: https://kwik.si/f/abc",
+                "https://cdn.example/ep5.mp4",
+            ]
+        );
+        assert_eq!(
+            player_args("vlc", &link, "pahe/1.0").unwrap(),
+            [
+                "--http-referrer=https://kwik.si/f/abc",
+                "--http-user-agent=pahe/1.0",
+                "https://cdn.example/ep5.mp4",
+            ]
+        );
+        assert!(player_args("winamp", &link, "pahe/1.0").is_err());
+    }
+
+    #[test]
+    fn normalize_series_input_accepts_other_animepahe_domains() {
+        let id = "123e4567-e89b-12d3-a456-426614174000";
+        let domains = AnimepaheDomains::new("animepahe.si", &[]);
+
+        for domain in ["animepahe.ru", "animepahe.com"] {
+            let normalized = domains
+                .normalize_series_input(&format!("https://{domain}/anime/{id}"))
+                .expect("known domain should be accepted");
+            assert_eq!(normalized.anime_id, id);
+        }
+
+        let play = format!("https://www.animepahe.com/play/{id}/3cf1e5860ff5e9f766b36241c4dd6d48");
+        let normalized = domains.normalize_series_input(&play).expect("play link");
+        assert_eq!(normalized.anime_id, id);
+        assert!(normalized.session_id.is_some());
+
+        let mirror = format!("https://animepahe.mirror/anime/{id}?ref=1");
+        assert!(domains.normalize_series_input(&mirror).is_err());
+        for domains in [
+            AnimepaheDomains::new("animepahe.mirror", &[]),
+            AnimepaheDomains::new("animepahe.si", &["animepahe.mirror".to_string()]),
+        ] {
+            let normalized = domains
+                .normalize_series_input(&mirror)
+                .expect("the active and accepted domains should be accepted");
+            assert_eq!(normalized.anime_id, id);
+        }
+    }
+
+    #[test]
+    fn episode_play_links_are_checked_against_the_configured_domains() {
+        let id = "123e4567-e89b-12d3-a456-426614174000";
+        let session = "3cf1e5860ff5e9f766b36241c4dd6d48";
+        let selection = |host: &str| {
+            format!("https://{host}/play/{id}/{session}")
+                .parse::<EpisodeSelection>()
+                .expect("play links should parse")
+        };
+        let domains = AnimepaheDomains::new("animepahe.mirror", &[]);
+
+        for host in ["animepahe.mirror", "animepahe.ru"] {
+            assert_eq!(
+                domains.check_selection(selection(host)).unwrap(),
+                EpisodeSelection::Session {
+                    anime_id: Some(id.to_string()),
+                    session_id: session.to_string(),
+                }
+            );
+        }
+        assert!(domains.check_selection(selection("example.com")).is_err());
         assert_eq!(
-            normalized,
-            format!("https://{ANIMEPAHE_DOMAIN}/anime/123e4567-e89b-12d3-a456-426614174000")
+            domains.check_selection(EpisodeSelection::All).unwrap(),
+            EpisodeSelection::All
         );
     }
 
     #[test]
-    fn normalize_series_link_rejects_non_animepahe_links() {
+    fn normalize_series_id_rejects_non_animepahe_links() {
         let err =
-            normalize_series_link("https://example.com/anime/123e4567-e89b-12d3-a456-426614174000")
+            normalize_series_id("https://example.com/anime/123e4567-e89b-12d3-a456-426614174000")
                 .expect_err("non animepahe links should be rejected");
         assert!(
             err.to_string()
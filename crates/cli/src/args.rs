@@ -1,19 +1,64 @@
+use std::collections::BTreeSet;
 use std::path::PathBuf;
 use std::str::FromStr;
 
-use clap::{ArgGroup, Args};
+use clap::{ArgGroup, Args, ValueEnum};
 
 use crate::constants::*;
 
 #[derive(Debug, Clone, Args)]
 pub struct AppArgs {
+    /// Config file with defaults [default: $XDG_CONFIG_HOME/pahe/config.toml]
+    #[arg(long, env = "PAHE_CONFIG", value_name = "PATH")]
+    pub config: Option<PathBuf>,
+
     /// Logging verbosity (error, warn, info, debug)
     #[arg(long, default_value = "info")]
     pub log_level: String,
 
+    /// Also append every pahe/pahe_core trace event to this file, regardless of --log-level
+    #[arg(long, value_name = "PATH")]
+    pub log_file: Option<PathBuf>,
+
+    /// Format of the --log-file output
+    #[arg(long, value_enum, default_value_t = LogFormat::Plain)]
+    pub log_format: LogFormat,
+
     /// Use interactive prompts to edit arguments before execution
     #[arg(short, long)]
     pub interactive: bool,
+
+    /// Disable colors and spinners; also implied by NO_COLOR or a non-terminal output
+    #[arg(long)]
+    pub no_color: bool,
+
+    /// Extra PEM/DER root certificate to trust (e.g. a corporate proxy CA)
+    #[arg(long, value_name = "PATH")]
+    pub ca_cert: Option<PathBuf>,
+
+    /// Disable TLS certificate verification (dangerous)
+    #[arg(long)]
+    pub insecure: bool,
+
+    /// Extra kwik mirror host label to accept (repeatable, e.g. --kwik-host kwikie)
+    #[arg(long = "kwik-host", value_name = "LABEL")]
+    pub kwik_hosts: Vec<String>,
+
+    /// Proxy for every request (e.g. socks5://127.0.0.1:9050)
+    #[arg(long, env = "PAHE_PROXY", value_name = "URL")]
+    pub proxy: Option<String>,
+
+    /// User-Agent sent with every request
+    #[arg(long, env = "PAHE_USER_AGENT", value_name = "UA")]
+    pub user_agent: Option<String>,
+
+    /// Animepahe domain to send requests to; links on any known animepahe domain are accepted
+    #[arg(long, env = "PAHE_DOMAIN", value_name = "HOST", default_value = ANIMEPAHE_DOMAIN)]
+    pub domain: String,
+
+    /// Extra animepahe domain to accept links on (repeatable, e.g. --accept-domain animepahe.pw)
+    #[arg(long = "accept-domain", value_name = "HOST")]
+    pub accept_domains: Vec<String>,
 }
 
 #[derive(Debug, Clone, Args)]
@@ -22,48 +67,296 @@ pub struct ResolveArgs {
     #[arg(short, long)]
     pub series: Option<String>,
 
-    /// Cookies used to authenticate pahe requests
-    #[arg(short, long, env = "PAHE_COOKIES")]
-    pub cookies: Option<String>,
+    #[command(flatten)]
+    pub connection: ConnectionArgs,
 
-    /// Episode range (1-indexed) or a session id/play URL
-    #[arg(short, long, default_value = "1")]
-    pub episodes: EpisodeRange,
+    /// Episodes (1-indexed) as numbers and ranges, e.g. `1,3,5-8`, `all`, `latest`, `-3..-1` counting from the end, or a session id, anime/session id pair or play URL
+    #[arg(short, long, default_value = "1", allow_hyphen_values = true)]
+    pub episodes: EpisodeSelection,
 
-    /// Quality to select (e.g. 1080p, 720p, highest, lowest)
+    /// Qualities to try in order (e.g. 1080p,720p,highest); fails if none is offered
     #[arg(short, long, default_value = "highest")]
     pub quality: String,
 
-    /// Audio language code to select (e.g. jp, en)
+    /// Audio languages to try in order (e.g. jp, or jp,en to fall back to the dub)
     #[arg(short, long, default_value = "jp")]
     pub lang: String,
 
+    /// Break resolution ties by file size [default: larger, or smaller with `lowest`]
+    #[arg(long, value_enum)]
+    pub prefer: Option<SizePreference>,
+
     /// Resolve episode to streaming source instead of direct download URL
     #[arg(long)]
     pub stream: bool,
 
+    /// Check each resolved link with a one-byte request instead of just printing it
+    #[arg(long)]
+    pub probe: bool,
+
+    /// Continue past per-episode failures and report them at the end
+    #[arg(long)]
+    pub keep_going: bool,
+
+    /// Episodes to fetch variants and resolve links for at once (non-interactive runs only)
+    #[arg(long, value_name = "N", default_value_t = 1, value_parser = clap::value_parser!(u8).range(1..=8))]
+    pub resolve_concurrency: u8,
+
     #[command(flatten)]
     pub app_args: AppArgs,
 }
 
+/// how requests to animepahe are authenticated and timed, shared by every command that makes them.
+#[derive(Debug, Clone, Args)]
+pub struct ConnectionArgs {
+    /// Cookies used to authenticate pahe requests
+    #[arg(short, long, env = "PAHE_COOKIES")]
+    pub cookies: Option<String>,
+
+    /// Netscape `cookies.txt` export to read cookies from; --cookies takes precedence
+    #[arg(long, env = "PAHE_COOKIES_FILE", value_name = "PATH")]
+    pub cookies_file: Option<PathBuf>,
+
+    /// A single cookie as `name=value`; can be repeated and is merged with --cookies
+    #[arg(long = "cookie", value_name = "NAME=VALUE", value_parser = parse_cookie_pair)]
+    pub cookie: Vec<(String, String)>,
+
+    /// Fail instead of warning when the cookies look malformed or lack DDoS-Guard clearance
+    #[arg(long)]
+    pub strict_cookies: bool,
+
+    /// Seconds to wait for a connection or a response before giving up
+    #[arg(long, value_name = "SECS", default_value_t = 30)]
+    pub timeout: u64,
+}
+
+impl ResolveArgs {
+    /// the series and connection settings, without the resolution options.
+    pub fn series_args(&self) -> SeriesArgs {
+        SeriesArgs {
+            series: self.series.clone(),
+            connection: self.connection.clone(),
+            app_args: self.app_args.clone(),
+        }
+    }
+
+    /// `--series` unset and every resolution option at its default, as if none was given.
+    pub fn new(connection: ConnectionArgs, app_args: AppArgs) -> Self {
+        Self {
+            series: None,
+            connection,
+            episodes: EpisodeSelection::Episodes(BTreeSet::from([1])),
+            quality: "highest".to_string(),
+            lang: "jp".to_string(),
+            prefer: None,
+            stream: false,
+            probe: false,
+            keep_going: false,
+            resolve_concurrency: 1,
+            app_args,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Args)]
 pub struct DownloadArgs {
-    /// Output path for downloaded file
+    /// Output path for downloaded file, or `-` to write it to stdout
     #[arg(short, long)]
     pub output: Option<String>,
 
+    /// File name template, e.g. "{series} - {episode:02} - {episode_title}"; the extension is kept from the server
+    #[arg(long, value_name = "TEMPLATE", conflicts_with = "output")]
+    pub name_template: Option<String>,
+
+    /// Full file name template, e.g. "{title} - S01E{episode:02} [{quality}p {lang}].mkv"
+    #[arg(
+        long,
+        value_name = "TEMPLATE",
+        conflicts_with_all = ["output", "name_template"]
+    )]
+    pub output_template: Option<String>,
+
     /// Output directory for downloaded files
     #[arg(short, long)]
     pub dir: Option<PathBuf>,
 
-    /// Number of parallel connections
-    #[arg(short = 'n', long, default_value_t = 1)]
+    /// File of series urls to download one after another, each optionally followed by episodes
+    #[arg(
+        long,
+        value_name = "FILE",
+        conflicts_with_all = ["series", "output", "sha256", "interactive"]
+    )]
+    pub batch: Option<PathBuf>,
+
+    /// Number of parallel connections, or `auto` to pick one from the file size
+    #[arg(short = 'n', long, default_value = "1", value_parser = parse_connections)]
     pub connections: usize,
 
+    /// Number of episodes to download at once, each with its own progress bar
+    #[arg(long, value_name = "N", default_value_t = 1, value_parser = clap::value_parser!(u8).range(1..))]
+    pub parallel_episodes: u8,
+
+    /// Expected SHA-256 of the downloaded file (single-episode downloads only)
+    #[arg(long, value_name = "HEX")]
+    pub sha256: Option<String>,
+
+    /// Cap the download speed in bytes per second (e.g. 500K, 2M)
+    #[arg(long, value_name = "RATE", value_parser = parse_size)]
+    pub limit_rate: Option<u64>,
+
+    /// Seconds a connection may receive no data before it is failed
+    #[arg(long, value_name = "SECS", default_value_t = 60)]
+    pub stall_timeout: u64,
+
+    /// Ask for (or require --yes) confirmation when the batch is larger than this (e.g. 10GB)
+    #[arg(long, value_name = "SIZE", value_parser = parse_size)]
+    pub confirm_above: Option<u64>,
+
+    /// Proceed without confirmation prompts
+    #[arg(short, long)]
+    pub yes: bool,
+
+    /// Download episodes even when the output file already matches the remote size
+    #[arg(long)]
+    pub force: bool,
+
+    /// Resolve everything and print the files that would be written with their sizes, then stop
+    #[arg(long, conflicts_with = "sha256")]
+    pub dry_run: bool,
+
+    /// Download only the episodes with no matching file in --dir yet, per the naming template
+    #[arg(long, conflicts_with_all = ["episodes", "output", "sha256"])]
+    pub only_new: bool,
+
+    /// What to do when the output file already exists
+    #[arg(long, value_enum, default_value_t = CollisionPolicy::Rename)]
+    pub on_collision: CollisionPolicy,
+
+    /// JSON-lines file every completed download is appended to [default: ~/.local/share/pahe/history.jsonl]
+    #[arg(long, value_name = "PATH")]
+    pub history: Option<PathBuf>,
+
+    /// Command run after each episode finishes; {file}, {title} and {episode} are filled in
+    #[arg(long, value_name = "CMD", value_parser = parse_command)]
+    pub on_complete: Option<String>,
+
+    /// Wait for --on-complete to exit before moving on to the next episode
+    #[arg(long, requires = "on_complete")]
+    pub on_complete_wait: bool,
+
+    /// Send a desktop notification when the batch finishes, or after every episode
+    #[arg(
+        long,
+        value_enum,
+        value_name = "WHEN",
+        num_args = 0..=1,
+        default_missing_value = "batch"
+    )]
+    pub notify: Option<NotifyWhen>,
+
     #[command(flatten)]
     pub resolve: ResolveArgs,
 }
 
+#[derive(Debug, Clone, Args)]
+pub struct ResolveCommandArgs {
+    /// Also write the resolved links to an aria2c input file (`aria2c -i FILE`)
+    #[arg(long, value_name = "FILE")]
+    pub output_links: Option<PathBuf>,
+
+    /// File name template for the `out=` lines of --output-links, e.g. "{title} - {episode:02}.mp4"
+    #[arg(long, value_name = "TEMPLATE", requires = "output_links")]
+    pub output_template: Option<String>,
+
+    /// Also write the resolved links to an m3u playlist for mpv or VLC
+    #[arg(long, value_name = "FILE")]
+    pub playlist: Option<PathBuf>,
+
+    /// Stream the resolved episodes in a player instead of printing them
+    #[arg(long)]
+    pub play: bool,
+
+    /// Player used by --play (mpv or vlc)
+    #[arg(long, default_value = "mpv", requires = "play")]
+    pub player: String,
+
+    /// How the resolved links are printed; `json` writes an array to stdout and logs to stderr
+    #[arg(long, value_enum, default_value = "text")]
+    pub format: OutputFormat,
+
+    #[command(flatten)]
+    pub resolve: ResolveArgs,
+}
+
+/// the series to read and how to reach animepahe, for commands that resolve no episodes.
+#[derive(Debug, Clone, Args)]
+pub struct SeriesArgs {
+    /// AnimePahe anime/play url or uuid
+    #[arg(short, long)]
+    pub series: Option<String>,
+
+    #[command(flatten)]
+    pub connection: ConnectionArgs,
+
+    #[command(flatten)]
+    pub app_args: AppArgs,
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct ListArgs {
+    /// Episodes (1-indexed) as numbers and ranges, e.g. `1,3,5-8`, `latest` or `-3..-1` counting from the end
+    #[arg(short, long, default_value = "all", allow_hyphen_values = true)]
+    pub episodes: EpisodeSelection,
+
+    /// How the episodes are printed; `json` writes an array to stdout and logs to stderr
+    #[arg(long, value_enum, default_value = "text")]
+    pub format: OutputFormat,
+
+    #[command(flatten)]
+    pub series_args: SeriesArgs,
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct CompletionsArgs {
+    /// Shell to generate the completion script for
+    #[arg(value_enum)]
+    pub shell: clap_complete::Shell,
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct MetadataArgs {
+    /// How the metadata is printed; `json` writes an object to stdout and logs to stderr
+    #[arg(long, value_enum, default_value = "text")]
+    pub format: OutputFormat,
+
+    #[command(flatten)]
+    pub series_args: SeriesArgs,
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct SearchArgs {
+    /// Anime name to search for
+    pub query: String,
+
+    #[command(flatten)]
+    pub connection: ConnectionArgs,
+
+    /// How the picked series' links are printed; `json` writes an array to stdout and logs to stderr
+    #[arg(long, value_enum, default_value = "text")]
+    pub format: OutputFormat,
+
+    #[command(flatten)]
+    pub app_args: AppArgs,
+}
+
+impl SearchArgs {
+    /// arguments for resolving the picked series: these connection settings, and every
+    /// other option at its default (`-i` prompts for them instead).
+    pub fn resolve_args(&self) -> ResolveArgs {
+        ResolveArgs::new(self.connection.clone(), self.app_args.clone())
+    }
+}
+
 #[derive(Debug, Clone, Args)]
 #[command(
     group(
@@ -90,20 +383,61 @@ pub struct PlayArgs {
     pub resolve: ResolveArgs,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    /// Colored, human-readable lines
+    Text,
+    /// A json array of resolved or listed episodes
+    Json,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum LogFormat {
+    /// Human-readable lines
+    Plain,
+    /// One json object per event
+    Json,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum CollisionPolicy {
+    /// Append ` (1)`, ` (2)`, ... to the file name
+    Rename,
+    /// Leave the existing file alone and skip the episode
+    Skip,
+    /// Replace the existing file
+    Overwrite,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum NotifyWhen {
+    /// Once, after the last episode of the series
+    Batch,
+    /// After every downloaded episode
+    Episode,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum SizePreference {
+    Larger,
+    Smaller,
+}
+
 #[derive(Debug, Clone)]
 pub struct RuntimeArgs {
     pub series: String,
     pub cookies: String,
-    pub episodes: EpisodeRange,
+    pub episodes: EpisodeSelection,
     pub quality: String,
     pub lang: String,
+    pub prefer: Option<SizePreference>,
 }
 
 impl RuntimeArgs {
     pub fn new(
         series: String,
         cookies: String,
-        episodes: EpisodeRange,
+        episodes: EpisodeSelection,
         quality: String,
         lang: String,
     ) -> Self {
@@ -113,128 +447,550 @@ impl RuntimeArgs {
             episodes,
             quality,
             lang,
+            prefer: None,
         }
     }
 }
 
-#[derive(Debug, Clone)]
-pub enum EpisodeRange {
-    Range {
-        start: i32,
-        end: i32,
-    },
+/// `auto` maps to `0`, which the downloader resolves from the file size.
+pub fn parse_connections(raw: &str) -> std::result::Result<usize, String> {
+    if raw.eq_ignore_ascii_case("auto") {
+        return Ok(0);
+    }
+
+    match raw.parse::<usize>() {
+        Ok(0) | Err(_) => Err(format!(
+            "invalid connection count `{raw}`: expected a positive number or `auto`"
+        )),
+        Ok(connections) => Ok(connections),
+    }
+}
+
+fn parse_size(raw: &str) -> std::result::Result<u64, String> {
+    let input = raw.trim().to_ascii_uppercase();
+    let split = input
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(input.len());
+    let (value, unit) = input.split_at(split);
+    let value: f64 = value
+        .parse()
+        .map_err(|_| format!("invalid size `{raw}`: expected e.g. 500MB or 10GB"))?;
+    let multiplier = match unit.trim().trim_end_matches("IB").trim_end_matches('B') {
+        "" => 1u64,
+        "K" => 1 << 10,
+        "M" => 1 << 20,
+        "G" => 1 << 30,
+        "T" => 1 << 40,
+        _ => return Err(format!("invalid size unit in `{raw}`")),
+    };
+
+    Ok((value * multiplier as f64) as u64)
+}
+
+/// splits a command line into arguments the way a posix shell would, minus expansions:
+/// whitespace separates arguments, quotes group them and `\` escapes the next character
+/// (only `\` and `"` inside double quotes).
+pub fn split_command_line(raw: &str) -> std::result::Result<Vec<String>, String> {
+    let mut args = Vec::new();
+    let mut current: Option<String> = None;
+    let mut chars = raw.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => args.extend(current.take()),
+            '\'' => {
+                let arg = current.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => arg.push(c),
+                        None => return Err(format!("unterminated single quote in `{raw}`")),
+                    }
+                }
+            }
+            '"' => {
+                let arg = current.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(c @ ('\\' | '"')) => arg.push(c),
+                            Some(c) => {
+                                arg.push('\\');
+                                arg.push(c);
+                            }
+                            None => return Err(format!("unterminated double quote in `{raw}`")),
+                        },
+                        Some(c) => arg.push(c),
+                        None => return Err(format!("unterminated double quote in `{raw}`")),
+                    }
+                }
+            }
+            '\\' => match chars.next() {
+                Some(c) => current.get_or_insert_with(String::new).push(c),
+                None => return Err(format!("trailing backslash in `{raw}`")),
+            },
+            c => current.get_or_insert_with(String::new).push(c),
+        }
+    }
+    args.extend(current);
+    Ok(args)
+}
+
+fn parse_command(raw: &str) -> std::result::Result<String, String> {
+    if split_command_line(raw)?.is_empty() {
+        return Err("the command is empty".to_string());
+    }
+    Ok(raw.to_string())
+}
+
+fn parse_cookie_pair(raw: &str) -> std::result::Result<(String, String), String> {
+    let (name, value) = raw
+        .split_once('=')
+        .ok_or_else(|| format!("invalid cookie `{raw}`: expected name=value"))?;
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(format!("invalid cookie `{raw}`: missing cookie name"));
+    }
+
+    Ok((name.to_string(), value.trim().to_string()))
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EpisodeSelection {
+    All,
+    /// deduplicated, sorted episode numbers from a list such as `1,3,5-8,12`.
+    Episodes(BTreeSet<i32>),
     Session {
         anime_id: Option<String>,
         session_id: String,
     },
+    /// a play link, turned into a [`EpisodeSelection::Session`] once the accepted domains
+    /// are known.
+    PlayLink(String),
 }
 
-impl FromStr for EpisodeRange {
+impl FromStr for EpisodeSelection {
     type Err = String;
 
     fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
         let input = s.trim();
 
-        if let Some(caps) = PLAY_LINK_RE.captures(input) {
-            let anime_id = caps.get(1).map(|m| m.as_str().to_string());
-            let session_id = caps
-                .get(2)
-                .map(|m| m.as_str().to_string())
-                .ok_or("invalid play url")?;
-            return Ok(EpisodeRange::Session {
-                anime_id,
-                session_id,
-            });
+        if input.eq_ignore_ascii_case("all") {
+            return Ok(EpisodeSelection::All);
+        }
+
+        if input.contains("/play/") {
+            return Ok(EpisodeSelection::PlayLink(input.to_string()));
         }
 
         if SESSION_ID_RE.is_match(input) {
-            return Ok(EpisodeRange::Session {
+            return Ok(EpisodeSelection::Session {
                 anime_id: None,
                 session_id: input.to_string(),
             });
         }
 
-        if let Some((start, end)) = input.split_once('-') {
-            let start: i32 = start.parse().map_err(|_| "invalid start")?;
-            let end: i32 = end.parse().map_err(|_| "invalid end")?;
+        if let Some((anime_id, session_id)) = input.split_once('/')
+            && UUID_RE.is_match(anime_id)
+            && SESSION_ID_RE.is_match(session_id)
+        {
+            return Ok(EpisodeSelection::Session {
+                anime_id: Some(anime_id.to_string()),
+                session_id: session_id.to_string(),
+            });
+        }
 
-            if start > end {
-                return Err("start cannot be greater than end".into());
+        let mut episodes = BTreeSet::new();
+        for part in input.split(',').map(str::trim) {
+            if part.eq_ignore_ascii_case("latest") {
+                episodes.insert(-1);
+                continue;
             }
 
-            Ok(EpisodeRange::Range { start, end })
-        } else {
-            let value: i32 = input.parse().map_err(|_| "invalid number/session id/url")?;
-            Ok(EpisodeRange::Range {
-                start: value,
-                end: value,
-            })
+            // `..` also separates ranges, so ones counting from the end (`-3..-1`) can be written
+            let range = part.split_once("..").or_else(|| {
+                part.char_indices()
+                    .skip(1)
+                    .find(|&(_, c)| c == '-')
+                    .map(|(at, _)| (&part[..at], &part[at + 1..]))
+            });
+            if let Some((start, end)) = range {
+                let start: i32 = start.trim().parse().map_err(|_| "invalid start")?;
+                let end: i32 = end.trim().parse().map_err(|_| "invalid end")?;
+
+                if (start < 0) != (end < 0) {
+                    return Err(format!(
+                        "invalid range {part}: cannot mix counting from the start and the end"
+                    ));
+                }
+                if start > end {
+                    return Err(format!(
+                        "invalid range {part}: start cannot be greater than end"
+                    ));
+                }
+
+                // only episodes not already selected count towards the cap
+                let span = i64::from(end) - i64::from(start) + 1;
+                let overlap = episodes.range(start..=end).count() as i64;
+                if (episodes.len() as i64) + span - overlap > MAX_SELECTED_EPISODES as i64 {
+                    return Err(format!(
+                        "invalid range {part}: selections are limited to {MAX_SELECTED_EPISODES} episodes; use `all` instead"
+                    ));
+                }
+                episodes.extend(start..=end);
+            } else {
+                let value: i32 = part.parse().map_err(|_| "invalid number/session id/url")?;
+                episodes.insert(value);
+            }
         }
+
+        Ok(EpisodeSelection::Episodes(episodes))
+    }
+}
+
+impl EpisodeSelection {
+    /// whether the selection holds positions counted from the end, such as `latest` or `-3..-1`.
+    pub fn counts_from_end(&self) -> bool {
+        matches!(self, EpisodeSelection::Episodes(episodes) if episodes.first().is_some_and(|&first| first < 0))
     }
 }
 
-impl std::fmt::Display for EpisodeRange {
+/// turns positions counted from the end into 1-indexed ones, given `total` episodes.
+///
+/// positions before the first episode are dropped.
+pub fn positions_from_end(episodes: &BTreeSet<i32>, total: i32) -> BTreeSet<i32> {
+    episodes
+        .iter()
+        .map(|&episode| {
+            if episode < 0 {
+                total + 1 + episode
+            } else {
+                episode
+            }
+        })
+        .filter(|&episode| episode > 0)
+        .collect()
+}
+
+impl std::fmt::Display for EpisodeSelection {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            EpisodeRange::Range { start, end } => {
-                if start == end {
-                    write!(f, "{start}")
-                } else {
-                    write!(f, "{start}-{end}")
+            EpisodeSelection::All => write!(f, "all"),
+            EpisodeSelection::Episodes(episodes) => {
+                let mut runs: Vec<(i32, i32)> = Vec::new();
+                for &episode in episodes {
+                    match runs.last_mut() {
+                        Some((_, end)) if *end + 1 == episode && episode != 0 => *end = episode,
+                        _ => runs.push((episode, episode)),
+                    }
                 }
+                let parts: Vec<String> = runs
+                    .into_iter()
+                    .map(|(start, end)| {
+                        if start == end {
+                            start.to_string()
+                        } else if start < 0 {
+                            format!("{start}..{end}")
+                        } else {
+                            format!("{start}-{end}")
+                        }
+                    })
+                    .collect();
+                write!(f, "{}", parts.join(","))
             }
-            EpisodeRange::Session {
+            EpisodeSelection::Session {
                 anime_id: Some(anime_id),
                 session_id,
-            } => write!(f, "https://{ANIMEPAHE_DOMAIN}/play/{anime_id}/{session_id}"),
-            EpisodeRange::Session {
+            } => write!(f, "{anime_id}/{session_id}"),
+            EpisodeSelection::Session {
                 anime_id: None,
                 session_id,
             } => write!(f, "{session_id}"),
+            EpisodeSelection::PlayLink(link) => write!(f, "{link}"),
         }
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use clap::Parser;
+
     use super::*;
 
+    fn episodes(selection: EpisodeSelection) -> Vec<i32> {
+        match selection {
+            EpisodeSelection::Episodes(episodes) => episodes.into_iter().collect(),
+            other => panic!("expected episode numbers, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn parse_episode_selection_number() {
+        let parsed = "12".parse::<EpisodeSelection>().expect("must parse number");
+        assert_eq!(episodes(parsed), vec![12]);
+    }
+
+    #[test]
+    fn parse_episode_selection_span() {
+        let parsed = "2-5".parse::<EpisodeSelection>().expect("must parse range");
+        assert_eq!(episodes(parsed), vec![2, 3, 4, 5]);
+    }
+
+    #[test]
+    fn parse_episode_selection_list() {
+        let parsed = "1,3,5-8,12"
+            .parse::<EpisodeSelection>()
+            .expect("must parse list");
+        assert_eq!(parsed.to_string(), "1,3,5-8,12");
+        assert_eq!(episodes(parsed), vec![1, 3, 5, 6, 7, 8, 12]);
+    }
+
+    fn from_end(input: &str, total: i32) -> Vec<i32> {
+        let parsed = input.parse::<EpisodeSelection>().expect("must parse");
+        assert!(
+            parsed.counts_from_end(),
+            "{input} should count from the end"
+        );
+        positions_from_end(&episodes(parsed).into_iter().collect(), total)
+            .into_iter()
+            .collect()
+    }
+
+    #[test]
+    fn parse_episode_selection_keywords() {
+        assert_eq!("all".parse(), Ok(EpisodeSelection::All));
+        assert_eq!("ALL".parse(), Ok(EpisodeSelection::All));
+        assert_eq!(from_end("latest", 28), vec![28]);
+    }
+
+    #[test]
+    fn parse_episode_selection_from_end() {
+        assert_eq!("-1".parse::<EpisodeSelection>().unwrap().to_string(), "-1");
+        assert_eq!(from_end("-1", 12), vec![12]);
+
+        assert_eq!(
+            "-3..-1".parse::<EpisodeSelection>().unwrap().to_string(),
+            "-3..-1"
+        );
+        assert_eq!(from_end("-3..-1", 12), vec![10, 11, 12]);
+        assert_eq!(from_end("1,-2..-1", 2), vec![1, 2]);
+        assert_eq!(from_end("-5..-1", 2), vec![1, 2]);
+
+        assert!("1..-1".parse::<EpisodeSelection>().is_err());
+        assert!("-1..-3".parse::<EpisodeSelection>().is_err());
+        assert!(!"3-5".parse::<EpisodeSelection>().unwrap().counts_from_end());
+    }
+
+    #[test]
+    fn parse_episode_selection_merges_overlaps() {
+        let parsed = "5-8, 7-9".parse::<EpisodeSelection>().expect("must parse");
+        assert_eq!(parsed.to_string(), "5-9");
+        assert_eq!(episodes(parsed), vec![5, 6, 7, 8, 9]);
+    }
+
     #[test]
-    fn parse_episode_range_number() {
-        let parsed = "12".parse::<EpisodeRange>().expect("must parse number");
-        assert!(matches!(parsed, EpisodeRange::Range { start: 12, end: 12 }));
+    fn parse_episode_selection_rejects_huge_ranges() {
+        assert!("1-2000000000".parse::<EpisodeSelection>().is_err());
+        assert!("1-6000,6001-12000".parse::<EpisodeSelection>().is_err());
+        assert!("-2000000000..-1".parse::<EpisodeSelection>().is_err());
+        assert!("latest,-10001..-2".parse::<EpisodeSelection>().is_err());
+        assert!("latest,-10000..-2".parse::<EpisodeSelection>().is_ok());
+        assert!("1-6000,1-6000".parse::<EpisodeSelection>().is_ok());
+        assert!("1-6000,3000-10000".parse::<EpisodeSelection>().is_ok());
+        assert!("1-6000,3000-10001".parse::<EpisodeSelection>().is_err());
+        assert_eq!(
+            episodes("1-10000".parse::<EpisodeSelection>().unwrap()).len(),
+            10_000
+        );
     }
 
     #[test]
-    fn parse_episode_range_span() {
-        let parsed = "2-5".parse::<EpisodeRange>().expect("must parse range");
-        assert!(matches!(parsed, EpisodeRange::Range { start: 2, end: 5 }));
+    fn parse_episode_selection_rejects_reversed_range() {
+        assert!("5-3".parse::<EpisodeSelection>().is_err());
+        assert!("1,5-3".parse::<EpisodeSelection>().is_err());
+        assert!("1,,2".parse::<EpisodeSelection>().is_err());
+    }
+
+    #[test]
+    fn search_takes_only_connection_options() {
+        #[derive(Parser)]
+        struct SearchCli {
+            #[command(flatten)]
+            search: SearchArgs,
+        }
+
+        assert!(SearchCli::try_parse_from(["pahe", "frieren", "-e", "1-4"]).is_err());
+        assert!(SearchCli::try_parse_from(["pahe", "frieren", "--quality", "720p"]).is_err());
+
+        let search =
+            SearchCli::try_parse_from(["pahe", "frieren", "--timeout", "5", "--cookie", "a=b"])
+                .expect("search should parse")
+                .search;
+        let resolve = search.resolve_args();
+        assert_eq!(resolve.connection.timeout, 5);
+        assert_eq!(
+            resolve.connection.cookie,
+            [("a".to_string(), "b".to_string())]
+        );
+        assert_eq!(resolve.series, None);
+    }
+
+    #[test]
+    fn resolve_args_new_matches_the_flag_defaults() {
+        #[derive(Parser)]
+        struct ResolveCli {
+            #[command(flatten)]
+            resolve: ResolveArgs,
+        }
+
+        let parsed = ResolveCli::parse_from(["pahe"]).resolve;
+        let built = ResolveArgs::new(parsed.connection.clone(), parsed.app_args.clone());
+        assert_eq!(format!("{built:?}"), format!("{parsed:?}"));
+    }
+
+    #[test]
+    fn format_is_only_accepted_by_commands_that_print_results() {
+        #[derive(Parser)]
+        struct DownloadCli {
+            #[command(flatten)]
+            download: DownloadArgs,
+        }
+        #[derive(Parser)]
+        struct ListCli {
+            #[command(flatten)]
+            list: ListArgs,
+        }
+
+        assert!(DownloadCli::try_parse_from(["pahe", "--format", "json"]).is_err());
+        let list = ListCli::try_parse_from(["pahe", "--format", "json"])
+            .expect("list should parse")
+            .list;
+        assert!(matches!(list.format, OutputFormat::Json));
+        assert_eq!(list.episodes, EpisodeSelection::All);
+    }
+
+    #[test]
+    fn metadata_takes_only_the_series_and_connection_options() {
+        #[derive(Parser)]
+        struct MetadataCli {
+            #[command(flatten)]
+            metadata: MetadataArgs,
+        }
+
+        for flag in [
+            &["--episodes", "1-4"][..],
+            &["--quality", "720p"],
+            &["--lang", "en"],
+            &["--stream"],
+            &["--keep-going"],
+        ] {
+            let argv = ["pahe", "--series", "x"].iter().chain(flag);
+            assert!(
+                MetadataCli::try_parse_from(argv).is_err(),
+                "metadata should reject {flag:?}"
+            );
+        }
+
+        let metadata = MetadataCli::try_parse_from(["pahe", "--series", "x", "--timeout", "5"])
+            .expect("metadata should parse")
+            .metadata;
+        assert_eq!(metadata.series_args.connection.timeout, 5);
+    }
+
+    #[test]
+    fn list_takes_no_resolution_options() {
+        #[derive(Parser)]
+        struct ListCli {
+            #[command(flatten)]
+            list: ListArgs,
+        }
+
+        for flag in [
+            &["--quality", "720p"][..],
+            &["--lang", "en"],
+            &["--prefer", "smaller"],
+            &["--stream"],
+            &["--probe"],
+            &["--keep-going"],
+            &["--resolve-concurrency", "2"],
+        ] {
+            let argv = ["pahe", "--series", "x"].iter().chain(flag);
+            assert!(
+                ListCli::try_parse_from(argv).is_err(),
+                "list should reject {flag:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn parse_connections_accepts_auto() {
+        assert_eq!(parse_connections("auto"), Ok(0));
+        assert_eq!(parse_connections("4"), Ok(4));
+        assert!(parse_connections("0").is_err());
+    }
+
+    #[test]
+    fn parse_size_accepts_units() {
+        assert_eq!(parse_size("512"), Ok(512));
+        assert_eq!(parse_size("500MB"), Ok(500 * 1024 * 1024));
+        assert_eq!(parse_size("1.5gb"), Ok(1536 * 1024 * 1024));
+        assert_eq!(parse_size("2 GiB"), Ok(2 * 1024 * 1024 * 1024));
+        assert_eq!(parse_size("2M"), Ok(2 * 1024 * 1024));
+        assert_eq!(parse_size("500k"), Ok(500 * 1024));
+        assert!(parse_size("lots").is_err());
+    }
+
+    #[test]
+    fn parse_cookie_pair_splits_on_first_equals() {
+        let parsed = parse_cookie_pair("__ddg2_=abc=def").expect("must parse cookie");
+        assert_eq!(parsed, ("__ddg2_".to_string(), "abc=def".to_string()));
+        assert!(parse_cookie_pair("__ddg2_").is_err());
+        assert!(parse_cookie_pair("=abc").is_err());
+    }
+
+    #[test]
+    fn parse_episode_all() {
+        let parsed = "all".parse::<EpisodeSelection>().expect("must parse all");
+        assert!(matches!(parsed, EpisodeSelection::All));
     }
 
     #[test]
     fn parse_episode_session_id() {
         let parsed = "3cf1e5860ff5e9f766b36241c4dd6d48de3ef45d41183ecd079e1772aeb27c3c"
-            .parse::<EpisodeRange>()
+            .parse::<EpisodeSelection>()
             .expect("must parse session id");
         assert!(matches!(
             parsed,
-            EpisodeRange::Session { anime_id: None, .. }
+            EpisodeSelection::Session { anime_id: None, .. }
         ));
     }
 
     #[test]
-    fn parse_episode_play_url() {
-        let parsed = format!(
-            "https://{ANIMEPAHE_DOMAIN}/play/123e4567-e89b-12d3-a456-426614174000/3cf1e5860ff5e9f766b36241c4dd6d48de3ef45d41183ecd079e1772aeb27c3c"
-        )
-        .parse::<EpisodeRange>()
-        .expect("must parse play url");
+    fn episode_session_displays_without_a_domain() {
+        let pair = "123e4567-e89b-12d3-a456-426614174000/3cf1e5860ff5e9f766b36241c4dd6d48de3ef45d41183ecd079e1772aeb27c3c";
+        let parsed = pair
+            .parse::<EpisodeSelection>()
+            .expect("must parse anime/session id pair");
+
         assert!(matches!(
             parsed,
-            EpisodeRange::Session {
+            EpisodeSelection::Session {
                 anime_id: Some(_),
                 ..
             }
         ));
+        assert_eq!(parsed.to_string(), pair);
+    }
+
+    #[test]
+    fn parse_episode_play_url() {
+        let link = format!(
+            "https://{ANIMEPAHE_DOMAIN}/play/123e4567-e89b-12d3-a456-426614174000/3cf1e5860ff5e9f766b36241c4dd6d48de3ef45d41183ecd079e1772aeb27c3c"
+        );
+        let parsed = link
+            .parse::<EpisodeSelection>()
+            .expect("must parse play url");
+        assert_eq!(parsed, EpisodeSelection::PlayLink(link));
     }
 }
@@ -0,0 +1,181 @@
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+use pahe::errors::*;
+
+use crate::args::*;
+
+/// defaults read from `config.toml`.
+///
+/// values only fill in what wasn't given on the command line or through the environment:
+/// flag > env > file > built-in default.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub cookies: Option<String>,
+    pub quality: Option<String>,
+    pub lang: Option<String>,
+    /// a connection count, or `"auto"`.
+    pub connections: Option<ConfigConnections>,
+    pub dir: Option<PathBuf>,
+    pub log_level: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum ConfigConnections {
+    Count(usize),
+    Named(String),
+}
+
+impl Config {
+    /// reads `path`, or `$XDG_CONFIG_HOME/pahe/config.toml` when none is given.
+    ///
+    /// a missing default file is not an error; a missing explicit one is.
+    pub fn load(path: Option<&Path>) -> Result<Self> {
+        let (path, explicit) = match path {
+            Some(path) => (path.to_path_buf(), true),
+            None => match default_config_path() {
+                Some(path) => (path, false),
+                None => return Ok(Self::default()),
+            },
+        };
+
+        let text = match std::fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(err) if !explicit && err.kind() == std::io::ErrorKind::NotFound => {
+                return Ok(Self::default());
+            }
+            Err(err) => {
+                return Err(PaheError::Message(format!(
+                    "failed to read config {}: {err}",
+                    path.display()
+                )));
+            }
+        };
+
+        Self::parse(&text)
+            .map_err(|err| PaheError::Message(format!("invalid config {}: {err}", path.display())))
+    }
+
+    pub fn parse(text: &str) -> std::result::Result<Self, String> {
+        let config: Self = toml::from_str(text).map_err(|err| err.message().to_string())?;
+        if let Some(ConfigConnections::Named(raw)) = &config.connections {
+            parse_connections(raw)?;
+        }
+        Ok(config)
+    }
+
+    /// fills `args` from the file where `explicit(id)` says the value wasn't given.
+    pub fn apply_resolve(&self, args: &mut ResolveArgs, explicit: impl Fn(&str) -> bool) {
+        self.apply_connection(&mut args.connection, &mut args.app_args, &explicit);
+        if let Some(quality) = self.quality.as_ref().filter(|_| !explicit("quality")) {
+            args.quality = quality.clone();
+        }
+        if let Some(lang) = self.lang.as_ref().filter(|_| !explicit("lang")) {
+            args.lang = lang.clone();
+        }
+    }
+
+    /// the cookies and log level, which every command talking to animepahe takes.
+    pub fn apply_connection(
+        &self,
+        connection: &mut ConnectionArgs,
+        app_args: &mut AppArgs,
+        explicit: impl Fn(&str) -> bool,
+    ) {
+        // any other cookie source on the command line replaces the configured cookies
+        if connection.cookies.is_none()
+            && connection.cookies_file.is_none()
+            && connection.cookie.is_empty()
+        {
+            connection.cookies = self.cookies.clone();
+        }
+        if let Some(level) = self.log_level.as_ref().filter(|_| !explicit("log_level")) {
+            app_args.log_level = level.clone();
+        }
+    }
+
+    /// [`Config::apply_resolve`] plus the download-only settings.
+    pub fn apply_download(&self, args: &mut DownloadArgs, explicit: impl Fn(&str) -> bool) {
+        if let Some(connections) = self
+            .connections
+            .as_ref()
+            .filter(|_| !explicit("connections"))
+        {
+            args.connections = match connections {
+                ConfigConnections::Count(count) => *count,
+                ConfigConnections::Named(raw) => parse_connections(raw).unwrap_or(args.connections),
+            };
+        }
+        if args.dir.is_none() {
+            args.dir = self.dir.clone();
+        }
+        self.apply_resolve(&mut args.resolve, explicit);
+    }
+}
+
+fn default_config_path() -> Option<PathBuf> {
+    let base = std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+    Some(base.join("pahe").join("config.toml"))
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+
+    use super::*;
+
+    #[derive(Debug, Parser)]
+    struct TestCli {
+        #[command(flatten)]
+        download: DownloadArgs,
+    }
+
+    #[test]
+    fn config_fills_only_values_not_given_explicitly() {
+        let config = Config::parse(
+            r#"
+            cookies = "__ddg2_=abc"
+            quality = "1080p,720p"
+            lang = "jp,en"
+            connections = "auto"
+            dir = "/media/anime"
+            log_level = "debug"
+            "#,
+        )
+        .expect("config should parse");
+
+        let mut args = TestCli::parse_from(["pahe", "--quality", "480p", "-n", "2"]).download;
+        config.apply_download(&mut args, |id| matches!(id, "quality" | "connections"));
+
+        assert_eq!(args.resolve.quality, "480p");
+        assert_eq!(args.connections, 2);
+        assert_eq!(args.resolve.lang, "jp,en");
+        assert_eq!(
+            args.resolve.connection.cookies.as_deref(),
+            Some("__ddg2_=abc")
+        );
+        assert_eq!(args.dir, Some(PathBuf::from("/media/anime")));
+        assert_eq!(args.resolve.app_args.log_level, "debug");
+    }
+
+    #[test]
+    fn config_cookies_yield_to_any_cookie_flag() {
+        let config = Config {
+            cookies: Some("__ddg2_=from-file".to_string()),
+            ..Default::default()
+        };
+
+        let mut args = TestCli::parse_from(["pahe", "--cookie", "__ddg2_=from-flag"]).download;
+        config.apply_download(&mut args, |_| false);
+
+        assert_eq!(args.resolve.connection.cookies, None);
+        assert!(Config::parse("connections = \"lots\"").is_err());
+        assert!(Config::parse("colour = true").is_err());
+    }
+}
@@ -1,35 +1,64 @@
 use std::sync::LazyLock;
+use std::time::Duration;
 
 use regex::Regex;
 
 pub const VERSION: &str = env!("CARGO_PKG_VERSION");
 
+/// domain requests go to unless `--domain` says otherwise.
 pub const ANIMEPAHE_DOMAIN: &str = "animepahe.si";
 
+/// domains animepahe has been served from; links on any of them are accepted, along with
+/// `--domain` and every `--accept-domain`.
+pub const KNOWN_ANIMEPAHE_DOMAINS: &[&str] = &[
+    "animepahe.si",
+    "animepahe.ru",
+    "animepahe.com",
+    "animepahe.org",
+];
+
+/// `--output` value that pipes the download to stdout.
+pub const STDOUT_OUTPUT: &str = "-";
+
+/// most episodes an `--episodes` list may name; every number is kept in memory, so
+/// `1-2000000000` is refused instead of allocated. longer series can use `all`.
+pub const MAX_SELECTED_EPISODES: usize = 10_000;
+
+/// file name template for an episode downloaded in several hand-picked variants when no
+/// naming option was given; animepahe's own file names don't carry the audio language.
+pub const VARIANT_NAME_TEMPLATE: &str = "{title} - {episode:02} [{quality}p {lang}]";
+
+/// direct links probed at once when sizing a batch, to stay under kwik's rate limits.
+pub const PROBE_CONCURRENCY: usize = 8;
+
+/// exit code after ctrl-c, as shells report for SIGINT.
+pub const INTERRUPTED_EXIT_CODE: u8 = 130;
+
+/// how long a cancelled command may take to stop its downloads and keep their partial files;
+/// a second ctrl-c quits right away.
+pub const INTERRUPT_GRACE: Duration = Duration::from_secs(10);
+
 pub static UUID_RE: LazyLock<Regex> =
     LazyLock::new(|| Regex::new(r"^[a-f0-9-]{36}$").expect("uuid regex must compile"));
 
 pub static SESSION_ID_RE: LazyLock<Regex> =
     LazyLock::new(|| Regex::new(r"^[a-f0-9]{32,}$").expect("session id regex must compile"));
 
-pub static ANIME_LINK_RE: LazyLock<Regex> = LazyLock::new(|| {
-    Regex::new(
-        format!(
-            r"^https?://(?:www\.)?{}/anime/([a-f0-9-]{{36}})(?:[/?#].*)?$",
-            regex::escape(ANIMEPAHE_DOMAIN)
-        )
-        .as_str(),
-    )
-    .expect("anime link regex must compile")
+pub static HTML_TAG_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"<[^>]*>").expect("html tag regex must compile"));
+
+/// a CSI escape sequence, as emitted by `owo_colors` styles and cursor movement.
+pub static ANSI_ESCAPE_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"\x1b\[[0-9;?]*[A-Za-z]").expect("ansi escape regex must compile")
+});
+
+/// a `{name}` or zero-padded `{name:0N}` file name placeholder, with the separator before it.
+pub static TEMPLATE_PLACEHOLDER_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?P<separator>\s*[-_.]?\s*)\{(?P<name>\w+)(?::0(?P<width>\d+))?\}")
+        .expect("template placeholder regex must compile")
 });
 
-pub static PLAY_LINK_RE: LazyLock<Regex> = LazyLock::new(|| {
-    Regex::new(
-        format!(
-            r"^https?://(?:www\.)?{}/play/([a-f0-9-]{{36}})/([a-f0-9]{{32,}})(?:[/?#].*)?$",
-            regex::escape(ANIMEPAHE_DOMAIN)
-        )
-        .as_str(),
-    )
-    .expect("play link regex must compile")
+/// the episode number in animepahe's own file names, e.g. `AnimePahe_Title_-_05_1080p_Group.mp4`.
+pub static SERVER_EPISODE_FILE_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"_-_0*(?P<episode>\d+)(?:[_.]|$)").expect("server file name regex must compile")
 });
@@ -1,218 +1,988 @@
+use std::time::Duration;
+
 use owo_colors::OwoColorize;
+use serde::Serialize;
 
-use pahe::client::EpisodeVariant;
+use pahe::client::{
+    Anime, EpisodeInfo, EpisodeVariant, SearchResult, SeriesEpisodes, resolve_in_order,
+};
 use pahe::errors::*;
-use pahe::prelude::PaheBuilder;
+use pahe::prelude::{ClientOptions, CookieStatus, PaheBuilder, PaheClient, QualityPreference};
 
 use crate::args::*;
-use crate::constants::*;
 use crate::logger::*;
 use crate::prompt::*;
 use crate::utils::*;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct EpisodeURL {
+    #[serde(rename = "episode")]
+    pub index: u32,
+    /// audio language of the selected variant.
+    pub language: String,
+    /// resolution of the selected variant, e.g. `1080`.
+    pub quality: i32,
+    pub bluray: bool,
     pub referer: String,
     pub url: String,
+    /// series title from the series page, used for output file naming.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub series_title: Option<String>,
+    /// episode title from the release api, used for output file naming.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub episode_title: Option<String>,
+    /// the variant `url` was resolved from, kept to resolve it again once kwik rotates it.
+    #[serde(skip)]
+    pub variant: Option<EpisodeVariant>,
+}
+
+/// an episode that could not be resolved or downloaded.
+#[derive(Debug)]
+pub struct EpisodeFailure {
     pub index: u32,
+    pub error: PaheError,
+}
+
+/// outcome of resolving a batch of episodes.
+#[derive(Default)]
+pub struct ResolvedEpisodes {
+    pub urls: Vec<EpisodeURL>,
+    pub failures: Vec<EpisodeFailure>,
+    /// the client the urls were resolved with, for resolving expired links again.
+    pub pahe: Option<PaheClient>,
 }
 
-enum QualityPreference {
-    Highest,
-    Lowest,
-    Exact(i32),
+/// prints a summary of failed episodes and turns them into an error.
+pub fn report_failures(failures: &[EpisodeFailure], logger: &CliLogger) -> Result<()> {
+    if failures.is_empty() {
+        return Ok(());
+    }
+
+    let lines = failures
+        .iter()
+        .map(|failure| {
+            format!(
+                "{} {}: {}",
+                "episode".dimmed(),
+                failure.index.bold(),
+                failure.error
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n  ");
+    logger.failed(format!("{} episode(s) failed:\n  {lines}", failures.len()));
+
+    Err(PaheError::Message(format!(
+        "{} episode(s) failed",
+        failures.len()
+    )))
 }
 
 pub async fn resolve_episode_urls(
     args: ResolveArgs,
+    options: &ClientOptions,
     logger: &CliLogger,
-) -> Result<Vec<EpisodeURL>> {
+) -> Result<ResolvedEpisodes> {
     let resolve_stream = args.stream;
+    let keep_going = args.keep_going;
+    let concurrency = usize::from(args.resolve_concurrency);
+    let interactive = args.app_args.interactive;
+    let connection = args.connection.clone();
+    let prefer = args.prefer;
+    let domain = args.app_args.domain.clone();
+    let domains = AnimepaheDomains::from_args(&args.app_args);
     let mut runtime = match args {
-        args if args.app_args.interactive => prompt_for_args(args)?,
+        args if args.app_args.interactive => prompt_for_args(args, &domains)?,
         ResolveArgs {
             series: Some(series),
-            cookies: Some(cookies),
+            connection: ConnectionArgs { cookies, .. },
             episodes,
             quality,
             lang,
             ..
-        } => RuntimeArgs::new(series, cookies, episodes, quality, lang),
-        args => prompt_for_args(args)?,
+        } if cookies.is_some()
+            || connection.cookies_file.is_some()
+            || !connection.cookie.is_empty() =>
+        {
+            RuntimeArgs::new(series, cookies.unwrap_or_default(), episodes, quality, lang)
+        }
+        args => prompt_for_args(args, &domains)?,
     };
-    let normalized_series = normalize_series_input(&runtime.series)?;
-    runtime.series = normalized_series.anime_link.clone();
+    runtime.prefer = prefer;
+    runtime.episodes = domains.check_selection(runtime.episodes)?;
+    let normalized_series = domains.normalize_series_input(&runtime.series)?;
     if let Some(session_id) = normalized_series.session_id {
-        runtime.episodes = EpisodeRange::Session {
-            anime_id: Some(normalized_series.anime_id),
+        runtime.episodes = EpisodeSelection::Session {
+            anime_id: Some(normalized_series.anime_id.clone()),
             session_id,
         };
     }
 
     logger.loading("initializing");
-    let pahe = PaheBuilder::new().cookies_str(&runtime.cookies).build()?;
+    let mut pahe = build_client(&runtime.cookies, &connection, options, &domain, logger)?;
+
+    match logger
+        .while_loading("checking cookies", pahe.check_cookies())
+        .await?
+    {
+        CookieStatus::Ok => {}
+        status if interactive => {
+            runtime.cookies = prompt_for_fresh_cookies(status)?;
+            // the pasted cookies replace every configured source
+            let fresh = ConnectionArgs {
+                cookie: Vec::new(),
+                cookies_file: None,
+                ..connection.clone()
+            };
+            pahe = build_client(&runtime.cookies, &fresh, options, &domain, logger)?;
+        }
+        CookieStatus::Missing => {
+            return Err(PaheError::DdosGuard {
+                context: "animepahe".to_string(),
+                hint: "pass clearance cookies via --cookies, --cookies-file or PAHE_COOKIES"
+                    .to_string(),
+            });
+        }
+        CookieStatus::Stale => {
+            return Err(PaheError::DdosGuard {
+                context: "animepahe".to_string(),
+                hint: "your cookies are stale; refresh them from a real browser session"
+                    .to_string(),
+            });
+        }
+    }
 
+    let series_link = pahe.anime_link(&normalized_series.anime_id);
     let info = logger
         .while_loading(
-            format!("getting info from: {}", runtime.series.yellow()),
-            pahe.get_series_metadata(&runtime.series),
+            format!("getting info from: {}", series_link.yellow()),
+            pahe.get_series_metadata(&series_link),
         )
         .await?;
 
-    let links = match &runtime.episodes {
-        EpisodeRange::Range { start, end } => {
-            logger
-                .while_loading(
-                    format!("retrieving {} episodes", (end - start).yellow()),
-                    pahe.fetch_series_episode_links(&info.id, *start, *end),
-                )
-                .await?
+    let links = fetch_selected_episodes(&pahe, &info.id, &runtime.episodes, logger).await?;
+
+    let links = if interactive {
+        prompt_for_episodes(links)?
+    } else {
+        links
+    };
+
+    let mut results = ResolvedEpisodes::default();
+    let mut record = |episode: &EpisodeInfo,
+                      resolved: Result<Vec<(EpisodeURL, EpisodeVariant)>>| {
+        let n = &episode.episode;
+        let resolved = match resolved {
+            Ok(resolved) => resolved,
+            Err(error) if keep_going => {
+                logger.failed(format!("episode {}: {error}", n.yellow()));
+                results.failures.push(EpisodeFailure { index: *n, error });
+                return Ok(());
+            }
+            Err(error) => return Err(error),
+        };
+
+        for (mut resolved, selected) in resolved {
+            resolved.series_title = info.title.as_deref().map(str::trim).map(String::from);
+            results.urls.push(resolved);
+
+            let info = vec![
+                (
+                    "title".dimmed(),
+                    info.title
+                        .clone()
+                        .unwrap_or_else(|| "unknown".to_string())
+                        .trim()
+                        .to_string(),
+                ),
+                ("episode".dimmed(), n.to_string()),
+                ("language".dimmed(), selected.lang.to_string()),
+                ("quality".dimmed(), format!("{}p", selected.resolution)),
+                ("bluray".dimmed(), selected.bluray.to_string()),
+            ];
+
+            logger.success(
+                info.into_iter()
+                    .map(|(k, v)| format!("{}: {}", k, v))
+                    .collect::<Vec<_>>()
+                    .join("\n  "),
+            );
+        }
+        Ok(())
+    };
+
+    if concurrency > 1 && !interactive {
+        logger.loading(format!(
+            "resolving {} episodes, {} at a time",
+            links.len().yellow(),
+            concurrency
+        ));
+        let resolved = resolve_in_order(&links, concurrency, |episode| {
+            resolve_episode(
+                &pahe,
+                episode,
+                &runtime,
+                resolve_stream,
+                false,
+                true,
+                logger,
+            )
+        })
+        .await;
+        for (episode, result) in links.iter().zip(resolved) {
+            record(episode, result)?;
+        }
+    } else {
+        for episode in links.iter() {
+            logger.loading(format!("processing episode {}", episode.episode.yellow()));
+            logger.debug("episode", format!("link: {}", episode.play_link.yellow()));
+
+            let resolved = resolve_episode(
+                &pahe,
+                episode,
+                &runtime,
+                resolve_stream,
+                interactive,
+                false,
+                logger,
+            )
+            .await;
+            record(episode, resolved)?;
+        }
+    }
+
+    results.pahe = Some(pahe);
+    Ok(results)
+}
+
+/// fetches the release metadata of the episodes in `selection`, without resolving them.
+pub async fn fetch_selected_episodes(
+    pahe: &PaheClient,
+    anime_id: &str,
+    selection: &EpisodeSelection,
+    logger: &CliLogger,
+) -> Result<Vec<EpisodeInfo>> {
+    let links = match selection {
+        EpisodeSelection::All => {
+            let mut found = 0;
+            let episodes = pahe
+                .fetch_all_episodes_with(anime_id, |page| {
+                    found += page.len();
+                    logger.loading(format!("retrieving all episodes: found {}", found.yellow()));
+                })
+                .await?;
+            if !episodes.is_consistent() {
+                logger.debug(
+                    "episode",
+                    format!(
+                        "animepahe reported {} episodes but {} were found",
+                        episodes.reported_total,
+                        episodes.observed_count()
+                    ),
+                );
+            }
+            if episodes.episodes.is_empty() {
+                return Err(PaheError::NoEpisodesAvailable {
+                    anime_id: anime_id.to_string(),
+                });
+            }
+            episodes.episodes
+        }
+        EpisodeSelection::Episodes(wanted) => {
+            let wanted = if selection.counts_from_end() {
+                let total = pahe.get_series_episode_count(anime_id).await?;
+                positions_from_end(wanted, total)
+            } else {
+                wanted.clone()
+            };
+            let (Some(&start), Some(&end)) = (wanted.first(), wanted.last()) else {
+                return Err(PaheError::Message("no episodes selected".to_string()));
+            };
+            let mut found = 0;
+            logger.loading(format!("retrieving {} episodes", wanted.len().yellow()));
+            let SeriesEpisodes {
+                reported_total,
+                episodes: fetched,
+            } = pahe
+                .fetch_series_range_with(anime_id, start, end, |page| {
+                    found += page.len();
+                    logger.loading(format!("retrieving episodes: found {}", found.yellow()));
+                })
+                .await?;
+
+            // selections are 1-indexed positions in the release list, which starts at `start`.
+            let fetched_count = fetched.len();
+            let episodes: Vec<EpisodeInfo> = (start..)
+                .zip(fetched)
+                .filter(|(position, _)| wanted.contains(position))
+                .map(|(_, episode)| episode)
+                .collect();
+
+            let missing: Vec<String> = wanted
+                .iter()
+                .filter(|&&position| position - start >= fetched_count as i32)
+                .map(i32::to_string)
+                .collect();
+            if episodes.is_empty() {
+                return Err(PaheError::EpisodeNotFound {
+                    episode: start,
+                    total: reported_total,
+                });
+            }
+            if !missing.is_empty() {
+                logger.warn(format!("episode(s) not found: {}", missing.join(", ")));
+            }
+            episodes
         }
-        EpisodeRange::Session {
-            anime_id,
+        EpisodeSelection::Session {
+            anime_id: session_anime_id,
             session_id,
         } => {
-            let anime_id = anime_id.as_deref().unwrap_or(&info.id);
-            let link = format!("https://{ANIMEPAHE_DOMAIN}/play/{anime_id}/{session_id}");
+            let anime_id = session_anime_id.as_deref().unwrap_or(anime_id);
+            let link = pahe.play_link(anime_id, session_id);
             let episode = pahe.fetch_episode_index(&link).await?;
-            vec![(episode, link)]
+            vec![EpisodeInfo {
+                episode,
+                session: session_id.to_string(),
+                play_link: link,
+                ..Default::default()
+            }]
+        }
+        EpisodeSelection::PlayLink(link) => {
+            return Err(PaheError::Message(format!(
+                "play link {link} must be checked against the animepahe domains first"
+            )));
         }
     };
 
-    if links.is_empty() {
-        return match runtime.episodes {
-            EpisodeRange::Range { start, .. } => Err(PaheError::EpisodeNotFound(start)),
-            EpisodeRange::Session { .. } => Err(PaheError::Message(
-                "episode not found for given session input".to_string(),
-            )),
-        };
+    Ok(links)
+}
+
+/// one row of `list` output.
+#[derive(Debug, Serialize)]
+pub struct ListedEpisode {
+    pub episode: u32,
+    pub title: Option<String>,
+    pub aired_at: Option<String>,
+    pub duration: Option<String>,
+    pub filler: bool,
+}
+
+#[cfg(test)]
+impl EpisodeURL {
+    /// episode `index` of Frieren in 1080p jp, served from `https://cdn.example/ep{index}.mp4`.
+    pub fn fixture(index: u32) -> Self {
+        Self {
+            index,
+            language: "jp".to_string(),
+            quality: 1080,
+            bluray: false,
+            referer: "https://kwik.si/f/abc".to_string(),
+            url: format!("https://cdn.example/ep{index}.mp4"),
+            series_title: Some("Frieren".to_string()),
+            episode_title: None,
+            variant: None,
+        }
+    }
+}
+
+impl From<EpisodeInfo> for ListedEpisode {
+    fn from(episode: EpisodeInfo) -> Self {
+        Self {
+            episode: episode.episode,
+            title: episode.title,
+            aired_at: episode.aired_at,
+            duration: episode.duration,
+            filler: episode.filler,
+        }
+    }
+}
+
+/// series metadata as printed by `metadata`.
+#[derive(Debug, Serialize)]
+pub struct SeriesMetadata {
+    pub id: String,
+    pub title: Option<String>,
+    pub synopsis: Option<String>,
+    pub year: Option<u32>,
+    pub season: Option<String>,
+    pub status: Option<String>,
+    #[serde(rename = "type")]
+    pub kind: Option<String>,
+    pub genres: Vec<String>,
+    pub poster: Option<String>,
+    pub episodes: Option<u32>,
+}
+
+impl From<Anime> for SeriesMetadata {
+    fn from(anime: Anime) -> Self {
+        Self {
+            id: anime.id,
+            title: anime.title.map(|title| title.trim().to_string()),
+            synopsis: anime.synopsis,
+            year: anime.year,
+            season: anime.season,
+            status: anime.status,
+            kind: anime.kind,
+            genres: anime.genres,
+            poster: anime.poster,
+            episodes: anime.episodes,
+        }
     }
+}
+
+/// builds a client from the cookies and timeout in `args`, without prompting.
+pub fn connect_series(
+    args: &SeriesArgs,
+    options: &ClientOptions,
+    logger: &CliLogger,
+) -> Result<PaheClient> {
+    connect(&args.connection, &args.app_args.domain, options, logger)
+}
+
+/// loads the metadata of `--series` with `pahe`.
+async fn load_series(
+    pahe: &PaheClient,
+    args: &SeriesArgs,
+    domains: &AnimepaheDomains,
+    logger: &CliLogger,
+) -> Result<(NormalizedSeriesInput, Anime)> {
+    let series = args
+        .series
+        .as_deref()
+        .ok_or_else(|| PaheError::Message("--series is required".to_string()))?;
+    let normalized = domains.normalize_series_input(series)?;
+
+    let series_link = pahe.anime_link(&normalized.anime_id);
+    let info = logger
+        .while_loading(
+            format!("getting info from: {}", series_link.yellow()),
+            pahe.get_series_metadata(&series_link),
+        )
+        .await?;
+
+    Ok((normalized, info))
+}
+
+/// loads the series metadata only, touching neither episodes nor kwik.
+pub async fn series_metadata(
+    args: &SeriesArgs,
+    options: &ClientOptions,
+    logger: &CliLogger,
+) -> Result<SeriesMetadata> {
+    let pahe = connect_series(args, options, logger)?;
+    let domains = AnimepaheDomains::from_args(&args.app_args);
+    let (_, info) = load_series(&pahe, args, &domains, logger).await?;
+    Ok(info.into())
+}
+
+/// loads the series metadata and the selected episodes with `pahe`, without resolving any
+/// links.
+pub async fn list_episodes(
+    pahe: &PaheClient,
+    args: &SeriesArgs,
+    episodes: &EpisodeSelection,
+    logger: &CliLogger,
+) -> Result<(Anime, Vec<ListedEpisode>)> {
+    let domains = AnimepaheDomains::from_args(&args.app_args);
+    let (normalized, info) = load_series(pahe, args, &domains, logger).await?;
+    let selection = match normalized.session_id {
+        Some(session_id) => EpisodeSelection::Session {
+            anime_id: Some(normalized.anime_id),
+            session_id,
+        },
+        None => domains.check_selection(episodes.clone())?,
+    };
+    let episodes = fetch_selected_episodes(pahe, &info.id, &selection, logger).await?;
+
+    Ok((
+        info,
+        episodes.into_iter().map(ListedEpisode::from).collect(),
+    ))
+}
+
+/// runs an animepahe search with the cookies configured in `connection`.
+pub async fn search_series(
+    query: &str,
+    connection: &ConnectionArgs,
+    domain: &str,
+    options: &ClientOptions,
+    logger: &CliLogger,
+) -> Result<Vec<SearchResult>> {
+    let pahe = connect(connection, domain, options, logger)?;
 
-    let mut results = Vec::new();
+    logger
+        .while_loading(format!("searching for {query}"), pahe.search_series(query))
+        .await
+}
 
-    for (n, link) in links.iter() {
-        logger.loading(format!("processing episode {}", n.yellow()));
-        logger.debug("episode", format!("link: {}", link.yellow()));
+/// builds a client from the cookies and timeout in `connection`, without prompting.
+fn connect(
+    connection: &ConnectionArgs,
+    domain: &str,
+    options: &ClientOptions,
+    logger: &CliLogger,
+) -> Result<PaheClient> {
+    let cookies = connection.cookies.clone().unwrap_or_default();
+    build_client(&cookies, connection, options, domain, logger)
+}
 
-        let variants = logger
+/// builds a client sending `cookies` along with the `--cookie` pairs and `--cookies-file`
+/// entries in `connection`; the file is read and parsed by [`PaheBuilder::cookies_file`].
+fn build_client(
+    cookies: &str,
+    connection: &ConnectionArgs,
+    options: &ClientOptions,
+    domain: &str,
+    logger: &CliLogger,
+) -> Result<PaheClient> {
+    let strict_cookies = connection.strict_cookies;
+    let mut builder = PaheBuilder::new()
+        .base_domain(domain)
+        .cookies_str(cookies)
+        .client_options(options.clone())
+        .timeout(Duration::from_secs(connection.timeout))
+        .strict_cookies(strict_cookies);
+    for (name, value) in &connection.cookie {
+        builder = builder.cookie(name, value);
+    }
+    if let Some(path) = &connection.cookies_file {
+        builder = builder.cookies_file(path);
+    }
+    if !strict_cookies && let Some(issue) = builder.validate_cookies() {
+        logger.warn(format!("{issue} (use --strict-cookies to fail instead)"));
+    }
+    builder.build()
+}
+
+/// resolves the variants picked for `episode`: the one chosen by `--quality`/`--lang`, or
+/// every variant hand-picked in interactive mode.
+///
+/// `quiet` drops the spinners, for resolving several episodes at once.
+async fn resolve_episode(
+    pahe: &PaheClient,
+    episode: &EpisodeInfo,
+    runtime: &RuntimeArgs,
+    resolve_stream: bool,
+    interactive: bool,
+    quiet: bool,
+    logger: &CliLogger,
+) -> Result<Vec<(EpisodeURL, EpisodeVariant)>> {
+    let n = episode.episode;
+    let variants = pahe.fetch_episode_variants(&episode.play_link);
+    let variants = if quiet {
+        variants.await?
+    } else {
+        logger
             .while_loading(
                 format!("fetching variants for episode {}", n.yellow()),
-                pahe.fetch_episode_variants(link),
+                variants,
             )
-            .await?;
-        let selected = select_quality(variants, &runtime.quality, &runtime.lang, logger)?;
-        let quality = format!("{}p", selected.resolution);
-        let resolved = if resolve_stream {
-            let stream = logger
-                .while_loading(
-                    format!("resolving stream link for episode {}", n.yellow()),
-                    pahe.resolve_stream(&selected),
-                )
-                .await?;
+            .await?
+    };
+    let picked = if interactive {
+        prompt_for_variants(n, variants)?
+    } else {
+        vec![select_quality(
+            pahe,
+            variants,
+            &runtime.quality,
+            &runtime.lang,
+            runtime.prefer,
+        )?]
+    };
 
-            EpisodeURL {
-                referer: stream.referer,
-                url: stream.source,
-                index: *n,
-            }
+    let mut resolved = Vec::with_capacity(picked.len());
+    for selected in picked {
+        let variant = resolve_selected(pahe, episode, selected, resolve_stream);
+        resolved.push(if quiet {
+            variant.await?
         } else {
-            let direct = logger
-                .while_loading(
-                    format!("resolving direct link for episode {}", n.yellow()),
-                    pahe.resolve_download(&selected),
-                )
-                .await?;
-
-            EpisodeURL {
-                referer: direct.referer,
-                url: direct.direct_link,
-                index: *n,
-            }
-        };
+            resolve_variant(variant, n, resolve_stream, logger).await?
+        });
+    }
+    Ok(resolved)
+}
 
-        results.push(resolved);
+/// awaits `resolving` behind a spinner naming episode `n`.
+async fn resolve_variant(
+    resolving: impl Future<Output = Result<(EpisodeURL, EpisodeVariant)>>,
+    n: u32,
+    resolve_stream: bool,
+    logger: &CliLogger,
+) -> Result<(EpisodeURL, EpisodeVariant)> {
+    let kind = if resolve_stream { "stream" } else { "direct" };
+    logger
+        .while_loading(
+            format!("resolving {kind} link for episode {}", n.yellow()),
+            resolving,
+        )
+        .await
+}
 
-        let info = vec![
-            (
-                "title".dimmed(),
-                info.title
-                    .clone()
-                    .unwrap_or_else(|| "unknown".to_string())
-                    .trim()
-                    .to_string(),
-            ),
-            ("episode".dimmed(), n.to_string()),
-            ("language".dimmed(), selected.lang.to_string()),
-            ("quality".dimmed(), quality.to_string()),
-            ("bluray".dimmed(), selected.bluray.to_string()),
-        ];
+async fn resolve_selected(
+    pahe: &PaheClient,
+    episode: &EpisodeInfo,
+    selected: EpisodeVariant,
+    resolve_stream: bool,
+) -> Result<(EpisodeURL, EpisodeVariant)> {
+    let n = episode.episode;
+    let (referer, url) = if resolve_stream {
+        let stream = pahe.resolve_stream(&selected).await?;
+        (stream.referer, stream.source)
+    } else {
+        let direct = pahe.resolve_download(&selected).await?;
+        (direct.referer, direct.direct_link)
+    };
 
-        logger.success(
-            info.into_iter()
-                .map(|(k, v)| format!("{}: {}", k, v))
-                .collect::<Vec<_>>()
-                .join("\n  "),
-        );
-    }
+    let resolved = EpisodeURL {
+        index: n,
+        language: selected.lang.clone(),
+        quality: selected.resolution,
+        bluray: selected.bluray,
+        referer,
+        url,
+        series_title: None,
+        episode_title: episode.title.clone(),
+        variant: (!resolve_stream).then(|| selected.clone()),
+    };
 
-    Ok(results)
+    Ok((resolved, selected))
 }
 
+/// applies `--quality`, `--lang` and `--prefer` through [`PaheClient::select_variant_from`].
+///
+/// `quality` is a fallback list tried in order; an exact resolution that isn't offered only
+/// falls through to `highest` when the list says so. `audio_lang` is a fallback list as well
+/// (`jp,en`), and the language is settled before the quality.
 fn select_quality(
+    pahe: &PaheClient,
     variants: Vec<EpisodeVariant>,
     quality: &str,
     audio_lang: &str,
-    logger: &CliLogger,
+    prefer: Option<SizePreference>,
 ) -> Result<EpisodeVariant> {
-    let pool: Vec<EpisodeVariant> = variants
-        .iter()
-        .filter(|variant| match audio_lang {
-            "en" => variant.lang == "en",
-            "jp" => variant.lang == "jp",
-            "zh" => variant.lang == "zh",
-            "any" => true,
-            _ => false,
-        })
-        .cloned()
+    let preferences = parse_quality(quality)?;
+    let prefer = prefer.unwrap_or(match preferences.first() {
+        Some(QualityPreference::Lowest) => SizePreference::Smaller,
+        _ => SizePreference::Larger,
+    });
+
+    let langs: Vec<&str> = audio_lang
+        .split(',')
+        .map(str::trim)
+        .filter(|lang| !lang.is_empty())
         .collect();
 
-    if pool.is_empty() {
-        return Err(PaheError::NoSelectableVariant);
+    pahe.select_variant_from(
+        variants,
+        &preferences,
+        &langs,
+        prefer == SizePreference::Smaller,
+    )
+}
+
+/// parses `--quality`, e.g. `1080p,720p,highest`.
+fn parse_quality(raw: &str) -> Result<Vec<QualityPreference>> {
+    QualityPreference::parse_list(raw)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pick(quality: &str, prefer: Option<SizePreference>) -> EpisodeVariant {
+        let variants = vec![
+            EpisodeVariant::fixture(720, "jp", "200MB"),
+            EpisodeVariant::fixture(1080, "jp", "1.2GB"),
+            EpisodeVariant::fixture(1080, "jp", "800MB"),
+            EpisodeVariant::fixture(720, "jp", "150MB"),
+        ];
+        let pahe = PaheBuilder::new().build().expect("client should build");
+        select_quality(&pahe, variants, quality, "jp", prefer).expect("variant should be picked")
     }
 
-    logger.debug(
-        "episode",
-        format!(
-            "selecting quality from {} variant(s) with quality={} and lang={}",
-            pool.len(),
-            quality,
-            audio_lang
-        ),
-    );
-
-    let preference = parse_quality(quality).ok_or(PaheError::NoSelectableVariant)?;
-
-    let selected = match preference {
-        QualityPreference::Highest => pool.into_iter().max_by_key(|variant| variant.resolution),
-        QualityPreference::Lowest => pool.into_iter().min_by_key(|variant| variant.resolution),
-        QualityPreference::Exact(target) => pool
-            .iter()
-            .find(|variant| variant.resolution == target)
-            .cloned()
-            .or_else(|| pool.into_iter().max_by_key(|variant| variant.resolution)),
-    };
+    #[test]
+    fn select_quality_prefers_larger_for_highest() {
+        assert_eq!(
+            pick("highest", None).dpahe_link,
+            "https://pahe.win/1080-jp-1.2GB"
+        );
+    }
 
-    selected.ok_or(PaheError::NoSelectableVariant)
-}
+    #[test]
+    fn select_quality_prefers_smaller_for_lowest() {
+        assert_eq!(
+            pick("lowest", None).dpahe_link,
+            "https://pahe.win/720-jp-150MB"
+        );
+    }
 
-fn parse_quality(raw_quality: &str) -> Option<QualityPreference> {
-    let normalized = raw_quality.trim().to_ascii_lowercase();
-    match normalized.as_str() {
-        "highest" => Some(QualityPreference::Highest),
-        "lowest" => Some(QualityPreference::Lowest),
-        _ => {
-            let digits = normalized.trim_end_matches('p');
-            digits.parse::<i32>().ok().map(QualityPreference::Exact)
-        }
+    #[test]
+    fn select_quality_honors_explicit_size_preference() {
+        assert_eq!(
+            pick("1080p", Some(SizePreference::Smaller)).dpahe_link,
+            "https://pahe.win/1080-jp-800MB"
+        );
+    }
+
+    #[test]
+    fn select_quality_falls_back_in_listed_order() {
+        assert_eq!(
+            pick("480p,720p,highest", None).dpahe_link,
+            "https://pahe.win/720-jp-200MB"
+        );
+        assert_eq!(
+            pick("480p,highest", None).dpahe_link,
+            "https://pahe.win/1080-jp-1.2GB"
+        );
+    }
+
+    #[test]
+    fn select_quality_fails_when_nothing_listed_is_offered() {
+        let pahe = PaheBuilder::new().build().expect("client should build");
+        let variants = vec![
+            EpisodeVariant::fixture(720, "jp", "200MB"),
+            EpisodeVariant::fixture(1080, "jp", "1.2GB"),
+        ];
+
+        let err = select_quality(&pahe, variants, "480p", "jp", None).unwrap_err();
+        assert!(matches!(err, PaheError::QualityUnavailable { .. }));
+        assert!(matches!(
+            parse_quality("1080p,best"),
+            Err(PaheError::InvalidQuality(raw)) if raw == "best"
+        ));
+    }
+
+    #[tokio::test]
+    async fn fetch_selected_episodes_lists_requested_episodes() {
+        use wiremock::matchers::{method, path, query_param};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        let data = (13..=24)
+            .map(|ep| format!(r#"{{"episode":{ep},"session":"s{ep}","title":"Episode {ep}"}}"#))
+            .collect::<Vec<_>>()
+            .join(",");
+        Mock::given(method("GET"))
+            .and(path("/api"))
+            .and(query_param("m", "release"))
+            .and(query_param("page", "1"))
+            .respond_with(ResponseTemplate::new(200).set_body_raw(
+                format!(
+                    r#"{{"total":12,"per_page":30,"current_page":1,"last_page":1,"data":[{data}]}}"#
+                ),
+                "application/json",
+            ))
+            .mount(&server)
+            .await;
+
+        let pahe = PaheBuilder::new()
+            .base_url(&server.uri())
+            .and_then(|builder| builder.build())
+            .expect("client should build");
+        let selection = "2,4-5".parse::<EpisodeSelection>().unwrap();
+        let episodes =
+            fetch_selected_episodes(&pahe, "anime", &selection, &CliLogger::new("error"))
+                .await
+                .expect("episodes should be listed");
+
+        let listed: Vec<ListedEpisode> = episodes.into_iter().map(ListedEpisode::from).collect();
+        assert_eq!(
+            listed
+                .iter()
+                .map(|episode| episode.episode)
+                .collect::<Vec<_>>(),
+            vec![14, 16, 17]
+        );
+        assert_eq!(listed[0].title.as_deref(), Some("Episode 14"));
+    }
+
+    #[test]
+    fn cookies_file_is_read_by_the_client_builder() {
+        use clap::Parser;
+
+        use crate::app::{Cli, Commands};
+
+        let missing = std::env::temp_dir().join("pahe-cli-missing-cookies.txt");
+        let cli = Cli::try_parse_from([
+            "pahe",
+            "metadata",
+            "--series",
+            "x",
+            "--cookies-file",
+            missing.to_str().unwrap(),
+        ])
+        .expect("metadata flags should parse");
+        let Some(Commands::Metadata(args)) = cli.command else {
+            panic!("expected the metadata command");
+        };
+
+        let result = connect_series(
+            &args.series_args,
+            &ClientOptions::default(),
+            &CliLogger::new("error"),
+        );
+        assert!(matches!(
+            result,
+            Err(PaheError::CookiesFile { action: "read", .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn selection_past_the_last_episode_reports_the_total() {
+        use wiremock::matchers::{method, path, query_param};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/api"))
+            .and(query_param("m", "release"))
+            .and(query_param("page", "1"))
+            .respond_with(ResponseTemplate::new(200).set_body_raw(
+                r#"{"total":12,"per_page":30,"current_page":1,"last_page":1,"data":[{"episode":1,"session":"s1"}]}"#,
+                "application/json",
+            ))
+            .expect(1)
+            .mount(&server)
+            .await;
+        let pahe = PaheBuilder::new()
+            .base_url(&server.uri())
+            .and_then(|builder| builder.build())
+            .expect("client should build");
+
+        let err = fetch_selected_episodes(
+            &pahe,
+            "anime",
+            &"20-21".parse().unwrap(),
+            &CliLogger::new("error"),
+        )
+        .await
+        .expect_err("episodes past the end should fail");
+
+        assert!(matches!(
+            err,
+            PaheError::EpisodeNotFound {
+                episode: 20,
+                total: 12
+            }
+        ));
+    }
+
+    #[tokio::test]
+    async fn list_command_lists_episodes_counted_from_the_end() {
+        use clap::Parser;
+        use wiremock::matchers::{method, path, query_param};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        use crate::app::{Cli, Commands};
+
+        const ANIME_ID: &str = "123e4567-e89b-12d3-a456-426614174000";
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path(format!("/anime/{ANIME_ID}")))
+            .respond_with(ResponseTemplate::new(200).set_body_string(
+                r#"<div class="title-wrapper"><h1><span>Frieren</span></h1></div>"#,
+            ))
+            .expect(1)
+            .mount(&server)
+            .await;
+        let data = (13..=24)
+            .map(|ep| format!(r#"{{"episode":{ep},"session":"s{ep}","title":"Episode {ep}"}}"#))
+            .collect::<Vec<_>>()
+            .join(",");
+        Mock::given(method("GET"))
+            .and(path("/api"))
+            .and(query_param("m", "release"))
+            .and(query_param("page", "1"))
+            .respond_with(ResponseTemplate::new(200).set_body_raw(
+                format!(
+                    r#"{{"total":12,"per_page":30,"current_page":1,"last_page":1,"data":[{data}]}}"#
+                ),
+                "application/json",
+            ))
+            .mount(&server)
+            .await;
+
+        let cli = Cli::try_parse_from(["pahe", "list", "--series", ANIME_ID, "-e", "-2..-1"])
+            .expect("list flags should parse");
+        let Some(Commands::List(args)) = cli.command else {
+            panic!("expected the list command");
+        };
+        let pahe = PaheBuilder::new()
+            .base_url(&server.uri())
+            .and_then(|builder| builder.build())
+            .expect("client should build");
+
+        let (info, listed) = list_episodes(
+            &pahe,
+            &args.series_args,
+            &args.episodes,
+            &CliLogger::new("error"),
+        )
+        .await
+        .expect("episodes should be listed");
+
+        assert_eq!(info.title.as_deref(), Some("Frieren"));
+        assert_eq!(
+            listed
+                .iter()
+                .map(|episode| episode.episode)
+                .collect::<Vec<_>>(),
+            vec![23, 24]
+        );
+    }
+
+    #[test]
+    fn resolved_episode_serializes_to_json() {
+        let episode = EpisodeURL {
+            bluray: true,
+            ..EpisodeURL::fixture(3)
+        };
+
+        assert_eq!(
+            serde_json::to_value(&episode).unwrap(),
+            serde_json::json!({
+                "episode": 3,
+                "language": "jp",
+                "quality": 1080,
+                "bluray": true,
+                "referer": "https://kwik.si/f/abc",
+                "url": "https://cdn.example/ep3.mp4",
+                "series_title": "Frieren",
+            })
+        );
+    }
+
+    #[test]
+    fn series_metadata_serializes_as_library_entry() {
+        let anime = Anime {
+            id: "4a9abc55-0a54-c544-3e14-736c79ddafe7".to_string(),
+            title: Some(" Sousou no Frieren ".to_string()),
+            synopsis: Some("During their decade-long quest".to_string()),
+            poster: Some("https://i.animepahe.si/posters/frieren.jpg".to_string()),
+            status: Some("Finished Airing".to_string()),
+            kind: Some("TV".to_string()),
+            season: Some("Fall 2023".to_string()),
+            year: Some(2023),
+            genres: vec!["Adventure".to_string(), "Fantasy".to_string()],
+            episodes: None,
+        };
+
+        assert_eq!(
+            serde_json::to_value(SeriesMetadata::from(anime)).unwrap(),
+            serde_json::json!({
+                "id": "4a9abc55-0a54-c544-3e14-736c79ddafe7",
+                "title": "Sousou no Frieren",
+                "synopsis": "During their decade-long quest",
+                "year": 2023,
+                "season": "Fall 2023",
+                "status": "Finished Airing",
+                "type": "TV",
+                "genres": ["Adventure", "Fantasy"],
+                "poster": "https://i.animepahe.si/posters/frieren.jpg",
+                "episodes": null,
+            })
+        );
     }
 }
@@ -0,0 +1,153 @@
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::Serialize;
+
+use pahe::errors::*;
+use pahe_downloader::DownloadStats;
+
+use crate::episode::EpisodeURL;
+
+/// one completed download, written as a line of `history.jsonl`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct HistoryEntry {
+    /// unix timestamp, in seconds, of when the download finished.
+    pub timestamp: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub series: Option<String>,
+    pub episode: u32,
+    /// resolution of the downloaded variant, e.g. `1080`.
+    pub quality: i32,
+    pub lang: String,
+    /// lossily converted, since JSON strings can't hold paths that aren't valid UTF-8.
+    pub output: String,
+    pub bytes: u64,
+    pub duration_secs: f64,
+}
+
+impl HistoryEntry {
+    pub fn new(episode_url: &EpisodeURL, output: &Path, stats: &DownloadStats) -> Self {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |since| since.as_secs());
+        Self {
+            timestamp,
+            series: episode_url.series_title.clone(),
+            episode: episode_url.index,
+            quality: episode_url.quality,
+            lang: episode_url.language.clone(),
+            output: output.to_string_lossy().into_owned(),
+            bytes: stats.bytes,
+            duration_secs: stats.elapsed.as_secs_f64(),
+        }
+    }
+
+    /// the entry as a single JSON line, newline included.
+    pub fn to_json_line(&self) -> Result<String> {
+        let mut line = serde_json::to_string(self)
+            .map_err(|err| PaheError::Message(format!("failed to serialize history: {err}")))?;
+        line.push('\n');
+        Ok(line)
+    }
+}
+
+/// appends `entry` to the history file at `path`, creating it and its directory if needed.
+pub fn append_history(path: &Path, entry: &HistoryEntry) -> Result<()> {
+    let failed = |err: std::io::Error| {
+        PaheError::Message(format!("failed to write history {}: {err}", path.display()))
+    };
+    let line = entry.to_json_line()?;
+    if let Some(parent) = path
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+    {
+        std::fs::create_dir_all(parent).map_err(failed)?;
+    }
+    std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .and_then(|mut file| file.write_all(line.as_bytes()))
+        .map_err(failed)
+}
+
+/// `$XDG_DATA_HOME/pahe/history.jsonl`, falling back to `~/.local/share`.
+pub fn default_history_path() -> Option<PathBuf> {
+    let base = std::env::var_os("XDG_DATA_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| {
+            std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".local").join("share"))
+        })?;
+    Some(base.join("pahe").join("history.jsonl"))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn completed_download_serializes_to_one_json_line() {
+        let entry = HistoryEntry {
+            timestamp: 1_760_000_000,
+            series: Some("Frieren".to_string()),
+            episode: 3,
+            quality: 1080,
+            lang: "jp".to_string(),
+            output: "/media/anime/Frieren - 03.mp4".to_string(),
+            bytes: 1_048_576,
+            duration_secs: 12.5,
+        };
+
+        assert_eq!(
+            entry.to_json_line().unwrap(),
+            concat!(
+                r#"{"timestamp":1760000000,"series":"Frieren","episode":3,"quality":1080,"#,
+                r#""lang":"jp","output":"/media/anime/Frieren - 03.mp4","bytes":1048576,"#,
+                r#""duration_secs":12.5}"#,
+                "\n"
+            )
+        );
+    }
+
+    #[test]
+    fn history_entries_are_appended() {
+        let dir = std::env::temp_dir().join(format!("pahe-history-{}", std::process::id()));
+        let path = dir.join("nested").join("history.jsonl");
+        let stats = DownloadStats::fixture(10, Duration::from_secs(2));
+        let episode_url = EpisodeURL {
+            language: "en".to_string(),
+            quality: 720,
+            series_title: None,
+            ..EpisodeURL::fixture(1)
+        };
+        let entry = HistoryEntry::new(&episode_url, Path::new("a.mp4"), &stats);
+
+        append_history(&path, &entry).expect("first append");
+        append_history(&path, &entry).expect("second append");
+
+        let written = std::fs::read_to_string(&path).expect("history should exist");
+        let _ = std::fs::remove_dir_all(&dir);
+        assert_eq!(written.lines().count(), 2);
+        assert!(!written.contains("series"));
+        assert!(written.contains(r#""duration_secs":2.0"#));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn non_utf8_output_paths_are_written_lossily() {
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt;
+
+        let stats = DownloadStats::fixture(1, Duration::from_secs(1));
+        let episode_url = EpisodeURL::fixture(1);
+        let output = Path::new(OsStr::from_bytes(b"ep\xff.mp4"));
+        let entry = HistoryEntry::new(&episode_url, output, &stats);
+
+        let line = entry.to_json_line().expect("entry should serialize");
+        assert!(line.contains("\"output\":\"ep\u{fffd}.mp4\""), "{line}");
+    }
+}
@@ -9,12 +9,16 @@ use std::{
 };
 use tracing::{Event, Subscriber};
 use tracing_subscriber::field::Visit;
+use tracing_subscriber::filter::filter_fn;
 use tracing_subscriber::layer::{Context, Layer};
 use tracing_subscriber::prelude::*;
 use tracing_subscriber::registry::Registry;
 
 use pahe::errors::*;
 
+use crate::args::LogFormat;
+use crate::utils::strip_ansi;
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 pub enum LogLevel {
     Error,
@@ -41,11 +45,16 @@ pub struct CliLogger {
     pub spinner_step: AtomicUsize,
     pub loading_active: AtomicBool,
     pub loading_padded: AtomicBool,
+    /// writes everything to stderr, keeping stdout free for piped downloads.
+    pub use_stderr: bool,
+    /// styles output and animates the spinner; plain incremental lines otherwise.
+    pub color: bool,
 }
 
 #[derive(Debug, Clone, Copy)]
 enum LogState {
     Success,
+    Warn,
     Failed,
     Debug,
 }
@@ -57,6 +66,8 @@ impl CliLogger {
             spinner_step: AtomicUsize::new(0),
             loading_active: AtomicBool::new(false),
             loading_padded: AtomicBool::new(false),
+            use_stderr: false,
+            color: true,
         })
     }
 
@@ -70,15 +81,50 @@ impl CliLogger {
             spinner_step: AtomicUsize::new(0),
             loading_active: AtomicBool::new(false),
             loading_padded: AtomicBool::new(false),
+            use_stderr: false,
+            color: true,
         })
     }
 
+    /// sends all output to stderr instead of stdout.
+    pub fn with_stderr(mut self, use_stderr: bool) -> Self {
+        self.use_stderr = use_stderr;
+        self
+    }
+
+    /// drops colors, the spinner and cursor movement when `color` is false.
+    pub fn with_color(mut self, color: bool) -> Self {
+        self.color = color;
+        self
+    }
+
+    fn output(&self) -> Box<dyn Write> {
+        if self.use_stderr {
+            Box::new(std::io::stderr())
+        } else {
+            Box::new(std::io::stdout())
+        }
+    }
+
+    /// prints a plain line to the logger's stream.
+    pub fn println(&self, line: impl AsRef<str>) {
+        let _ = writeln!(self.output(), "{}", self.render(line.as_ref()));
+    }
+
+    /// `line` as it will be written, styling stripped unless color is enabled.
+    fn render(&self, line: &str) -> String {
+        if self.color {
+            line.to_string()
+        } else {
+            strip_ansi(line)
+        }
+    }
+
     fn log(&self, level: LogLevel, state: LogState, message: impl AsRef<str>) {
         self.clear_loading_line_if_needed();
-        let icon = self.icon(state);
 
         if level <= self.level {
-            println!("{} {}", icon, message.as_ref());
+            self.println(format!("{} {}", self.icon(state), message.as_ref()));
         }
     }
 
@@ -87,13 +133,34 @@ impl CliLogger {
             return;
         }
 
-        self.draw_loading_frame(message.as_ref());
+        if self.color {
+            self.draw_loading_frame(message.as_ref());
+        } else {
+            self.println(Self::plain_loading_line(message.as_ref()));
+        }
+    }
+
+    fn plain_loading_line(message: &str) -> String {
+        format!("… {message}")
     }
 
     pub fn success(&self, message: impl AsRef<str>) {
         self.log(LogLevel::Info, LogState::Success, message);
     }
 
+    /// a dimmed line without an icon, for running totals that aren't news of their own.
+    pub fn note(&self, message: impl AsRef<str>) {
+        self.clear_loading_line_if_needed();
+
+        if LogLevel::Info <= self.level {
+            self.println(message.as_ref().dimmed().to_string());
+        }
+    }
+
+    pub fn warn(&self, message: impl AsRef<str>) {
+        self.log(LogLevel::Warn, LogState::Warn, message);
+    }
+
     pub fn failed(&self, message: impl AsRef<str>) {
         self.log(LogLevel::Error, LogState::Failed, message);
     }
@@ -113,6 +180,7 @@ impl CliLogger {
     fn icon(&self, state: LogState) -> Box<dyn std::fmt::Display> {
         match state {
             LogState::Success => Box::new("✓".green()),
+            LogState::Warn => Box::new("!".yellow()),
             LogState::Failed => Box::new("✗".red()),
             LogState::Debug => Box::new("λ".cyan()),
         }
@@ -127,14 +195,20 @@ impl CliLogger {
         }
 
         let message = message.into();
+        if !self.color {
+            self.println(Self::plain_loading_line(&message));
+            return future.await;
+        }
+
         let mut ticker = tokio::time::interval(Duration::from_millis(120));
         let mut future = Box::pin(future);
         self.loading_active.store(true, Ordering::Relaxed);
+        // clears the spinner line on completion as well as when this future is dropped mid-flight
+        let _guard = LoadingGuard { logger: self };
 
         loop {
             tokio::select! {
                 result = &mut future => {
-                    self.clear_loading_line_if_needed();
                     return result;
                 }
                 _ = ticker.tick() => {
@@ -150,7 +224,7 @@ impl CliLogger {
         let frame = FRAMES[idx % FRAMES.len()].to_string();
         let frame = frame.yellow();
 
-        let mut stdout = std::io::stdout();
+        let mut stdout = self.output();
 
         if !self.loading_padded.swap(true, Ordering::Relaxed) {
             let _ = writeln!(stdout);
@@ -168,7 +242,7 @@ impl CliLogger {
 
     fn clear_loading_line_if_needed(&self) {
         if self.loading_active.swap(false, Ordering::Relaxed) {
-            let mut stdout = std::io::stdout();
+            let mut stdout = self.output();
             let _ = execute!(
                 stdout,
                 cursor::MoveToColumn(0),
@@ -188,6 +262,16 @@ impl CliLogger {
     }
 }
 
+struct LoadingGuard<'a> {
+    logger: &'a CliLogger,
+}
+
+impl Drop for LoadingGuard<'_> {
+    fn drop(&mut self) {
+        self.logger.clear_loading_line_if_needed();
+    }
+}
+
 #[derive(Default)]
 struct EventFieldVisitor {
     message: Option<String>,
@@ -216,7 +300,7 @@ where
     fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
         let metadata = event.metadata();
         let target = metadata.target();
-        if !(target.starts_with("pahe::") || target.starts_with("pahe_core::")) {
+        if !is_pahe_target(target) {
             return;
         }
 
@@ -239,13 +323,35 @@ where
     }
 }
 
-pub fn init_tracing(logger: Arc<CliLogger>) {
+fn is_pahe_target(target: &str) -> bool {
+    target.starts_with("pahe::") || target.starts_with("pahe_core::")
+}
+
+/// builds the optional file layer that records every pahe event, independent of the cli level.
+fn file_layer<S>(file: std::fs::File, format: LogFormat) -> Box<dyn Layer<S> + Send + Sync>
+where
+    S: Subscriber + for<'span> tracing_subscriber::registry::LookupSpan<'span>,
+{
+    let layer = tracing_subscriber::fmt::layer()
+        .with_ansi(false)
+        .with_writer(std::sync::Mutex::new(file));
+    let filter = filter_fn(|metadata| is_pahe_target(metadata.target()));
+
+    match format {
+        LogFormat::Plain => layer.with_filter(filter).boxed(),
+        LogFormat::Json => layer.json().with_filter(filter).boxed(),
+    }
+}
+
+pub fn init_tracing(logger: Arc<CliLogger>, log_file: Option<(std::fs::File, LogFormat)>) {
     static INIT: Once = Once::new();
 
     INIT.call_once(|| {
-        let subscriber = Registry::default().with(CliTracingLayer {
-            logger: Arc::clone(&logger),
-        });
+        let subscriber = Registry::default()
+            .with(CliTracingLayer {
+                logger: Arc::clone(&logger),
+            })
+            .with(log_file.map(|(file, format)| file_layer(file, format)));
 
         if let Err(err) = tracing::subscriber::set_global_default(subscriber) {
             logger.debug(
@@ -255,3 +361,37 @@ pub fn init_tracing(logger: Arc<CliLogger>) {
         }
     });
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn while_loading_resets_spinner_state_when_cancelled() {
+        let logger = CliLogger::new("info");
+        let pending = logger.while_loading("waiting", std::future::pending::<()>());
+        let _ = tokio::time::timeout(Duration::from_millis(50), pending).await;
+
+        assert!(!logger.loading_active.load(Ordering::Relaxed));
+        assert!(!logger.loading_padded.load(Ordering::Relaxed));
+    }
+
+    #[tokio::test]
+    async fn plain_logger_writes_no_escape_sequences() {
+        let logger = CliLogger::new("debug").with_color(false);
+        let line = format!(
+            "{} {:>15} {}",
+            logger.icon(LogState::Debug),
+            "episode".bold().bright_purple(),
+            "3".yellow()
+        );
+
+        let rendered = logger.render(&line);
+        assert!(!rendered.contains('\x1b'), "{rendered:?}");
+        assert!(rendered.ends_with("episode 3"));
+
+        logger.while_loading("fetching", async {}).await;
+        assert!(!logger.loading_active.load(Ordering::Relaxed));
+        assert!(!logger.loading_padded.load(Ordering::Relaxed));
+    }
+}
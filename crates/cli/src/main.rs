@@ -1,15 +1,20 @@
 mod app;
 mod args;
+mod config;
 mod constants;
 mod episode;
+mod history;
 mod logger;
+mod notify;
 mod progress;
 mod prompt;
 mod utils;
 
+use std::process::ExitCode;
+
 use app::*;
 
 #[tokio::main]
-async fn main() {
-    App::new().run().await;
+async fn main() -> ExitCode {
+    App::new().run().await
 }
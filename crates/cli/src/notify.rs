@@ -0,0 +1,99 @@
+//! desktop notifications for `--notify`; a no-op unless built with the `notify` feature.
+
+use crate::logger::CliLogger;
+use crate::progress::BatchProgress;
+use crate::utils::format_bytes;
+
+/// the title and text of one desktop notification.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Notification {
+    pub summary: String,
+    pub body: String,
+}
+
+/// announces a finished series, e.g. `Frieren finished` / `12 of 12 episodes downloaded · 4.20 GB`.
+pub fn batch_notification(
+    series: Option<&str>,
+    batch: &BatchProgress,
+    failed: usize,
+) -> Notification {
+    let mut body = format!(
+        "{} of {} episodes downloaded · {}",
+        batch.downloaded(),
+        batch.total(),
+        format_bytes(batch.bytes())
+    );
+    if failed > 0 {
+        body.push_str(&format!(" · {failed} failed"));
+    }
+
+    Notification {
+        summary: format!("{} finished", series.unwrap_or("download")),
+        body,
+    }
+}
+
+/// announces one downloaded episode.
+pub fn episode_notification(series: Option<&str>, episode: u32, file: &str) -> Notification {
+    Notification {
+        summary: match series {
+            Some(series) => format!("{series} episode {episode} downloaded"),
+            None => format!("episode {episode} downloaded"),
+        },
+        body: file.to_string(),
+    }
+}
+
+/// shows `notification`; failures, e.g. no notification daemon, are only logged at debug level.
+pub fn send(notification: &Notification, logger: &CliLogger) {
+    #[cfg(feature = "notify")]
+    {
+        let shown = notify_rust::Notification::new()
+            .appname("pahe")
+            .summary(&notification.summary)
+            .body(&notification.body)
+            .show();
+        if let Err(err) = shown {
+            logger.debug("notify", format!("couldn't show notification: {err}"));
+        }
+    }
+
+    #[cfg(not(feature = "notify"))]
+    logger.debug(
+        "notify",
+        format!(
+            "built without the `notify` feature; not showing \"{}\"",
+            notification.summary
+        ),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use pahe_downloader::DownloadStats;
+
+    use super::*;
+
+    #[test]
+    fn batch_notification_names_the_series_and_counts() {
+        let stats = DownloadStats::fixture(100_000_000, Duration::from_secs(10));
+        let mut batch = BatchProgress::new(3);
+        batch.finish(Some(&stats));
+        batch.finish(Some(&stats));
+        batch.finish(None);
+
+        assert_eq!(
+            batch_notification(Some("Frieren"), &batch, 1),
+            Notification {
+                summary: "Frieren finished".to_string(),
+                body: "2 of 3 episodes downloaded · 190.73 MB · 1 failed".to_string(),
+            }
+        );
+        assert_eq!(
+            batch_notification(None, &batch, 0).summary,
+            "download finished"
+        );
+    }
+}
@@ -1,14 +1,66 @@
-use std::io::Write;
+use std::collections::VecDeque;
+use std::io::{IsTerminal, Write};
 use std::time::{Duration, Instant};
 
 use crossterm::{cursor::*, execute, style::*, terminal::*};
 use owo_colors::OwoColorize;
-use pahe_downloader::DownloadEvent;
+use pahe_downloader::{DownloadEvent, DownloadStats};
 
 use crate::utils::*;
 
+/// how often the append-only fallback prints a progress line.
+const PLAIN_PROGRESS_INTERVAL: Duration = Duration::from_secs(5);
+
+/// how far back the displayed speed looks, so a stall shows up within a few seconds.
+const SPEED_WINDOW: Duration = Duration::from_secs(3);
+
+/// recent `(elapsed, downloaded)` samples, for a transfer rate over the last [`SPEED_WINDOW`].
+#[derive(Debug, Default)]
+struct SpeedWindow {
+    samples: VecDeque<(Duration, u64)>,
+}
+
+impl SpeedWindow {
+    fn record(&mut self, at: Duration, downloaded: u64) {
+        self.samples.push_back((at, downloaded));
+        // keep the newest sample from before the window as the baseline
+        let window_start = at.saturating_sub(SPEED_WINDOW);
+        while self
+            .samples
+            .get(1)
+            .is_some_and(|(sample_at, _)| *sample_at <= window_start)
+        {
+            self.samples.pop_front();
+        }
+    }
+
+    /// bytes per second between the window's baseline and `now`; `None` until there is a
+    /// baseline to measure from.
+    fn rate(&self, now: Duration) -> Option<f64> {
+        let (first_at, first_bytes) = *self.samples.front()?;
+        let (_, last_bytes) = *self.samples.back()?;
+        let span = now.saturating_sub(first_at).as_secs_f64();
+        (span > 0.0).then(|| last_bytes.saturating_sub(first_bytes) as f64 / span)
+    }
+
+    fn clear(&mut self) {
+        self.samples.clear();
+    }
+}
+
+/// bytes per second over the whole transfer.
+fn average_speed(downloaded: u64, elapsed: Duration) -> f64 {
+    if elapsed.as_secs_f64() > 0.0 {
+        downloaded as f64 / elapsed.as_secs_f64()
+    } else {
+        0.0
+    }
+}
+
 pub struct DownloadProgressRenderer {
     enabled: bool,
+    cursor_control: bool,
+    last_plain_line: Option<Instant>,
     initialized: bool,
     spinner_step: usize,
     started_at: Option<Instant>,
@@ -16,6 +68,7 @@ pub struct DownloadProgressRenderer {
     finished: bool,
     total: Option<u64>,
     status: DownloadStatus,
+    window: SpeedWindow,
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -29,6 +82,11 @@ impl DownloadProgressRenderer {
     pub fn new(enabled: bool) -> Self {
         Self {
             enabled,
+            cursor_control: supports_cursor_control(
+                std::io::stdout().is_terminal(),
+                std::env::var("TERM").ok().as_deref(),
+            ),
+            last_plain_line: None,
             initialized: false,
             spinner_step: 0,
             started_at: None,
@@ -36,14 +94,27 @@ impl DownloadProgressRenderer {
             finished: false,
             total: None,
             status: DownloadStatus::Waiting,
+            window: SpeedWindow::default(),
         }
     }
 
+    /// without color, progress falls back to the plain append-only lines.
+    pub fn with_color(mut self, color: bool) -> Self {
+        self.cursor_control &= color;
+        self
+    }
+
     pub fn handle(&mut self, event: DownloadEvent) {
         if !self.enabled {
             return;
         }
 
+        self.update(event);
+        self.draw_current();
+    }
+
+    /// applies an event to the tracked state without drawing anything.
+    fn update(&mut self, event: DownloadEvent) {
         match event {
             DownloadEvent::Started { total_bytes, .. } => {
                 self.total = total_bytes;
@@ -51,7 +122,7 @@ impl DownloadProgressRenderer {
                 self.finished = false;
                 self.started_at = Some(Instant::now());
                 self.status = DownloadStatus::Waiting;
-                self.draw_current();
+                self.window.clear();
             }
             DownloadEvent::Progress {
                 downloaded_bytes,
@@ -63,7 +134,7 @@ impl DownloadProgressRenderer {
                 self.started_at = Some(Instant::now() - elapsed);
                 self.finished = false;
                 self.status = DownloadStatus::Downloading;
-                self.draw_current();
+                self.window.record(elapsed, downloaded_bytes);
             }
             DownloadEvent::Finished {
                 downloaded_bytes,
@@ -73,7 +144,6 @@ impl DownloadProgressRenderer {
                 self.started_at = Some(Instant::now() - elapsed);
                 self.finished = true;
                 self.status = DownloadStatus::Done;
-                self.draw_current();
             }
         }
     }
@@ -85,12 +155,29 @@ impl DownloadProgressRenderer {
         self.draw_current();
     }
 
-    fn draw_current(&mut self) {
-        let elapsed = self
-            .started_at
+    fn elapsed(&self) -> Duration {
+        self.started_at
             .map(|started| started.elapsed())
-            .unwrap_or(Duration::ZERO);
-        self.draw_frame(self.downloaded, self.total, elapsed, self.finished);
+            .unwrap_or(Duration::ZERO)
+    }
+
+    /// the recent transfer rate while downloading, and the overall average once done or
+    /// before the window has a baseline.
+    fn speed(&self, downloaded: u64, elapsed: Duration, done: bool) -> f64 {
+        let average = average_speed(downloaded, elapsed);
+        if done {
+            return average;
+        }
+        self.window.rate(elapsed).unwrap_or(average)
+    }
+
+    fn draw_current(&mut self) {
+        self.draw_frame(self.downloaded, self.total, self.elapsed(), self.finished);
+    }
+
+    /// the bar and status lines for the current state, as drawn by [`Self::draw_frame`].
+    fn current_lines(&mut self) -> [String; 2] {
+        self.frame_lines(self.downloaded, self.total, self.elapsed(), self.finished)
     }
 
     pub fn draw_frame(
@@ -100,6 +187,11 @@ impl DownloadProgressRenderer {
         elapsed: Duration,
         done: bool,
     ) {
+        if !self.cursor_control {
+            self.draw_plain_line(downloaded, total, elapsed, done);
+            return;
+        }
+
         let mut stdout = std::io::stdout();
 
         if !self.initialized {
@@ -108,6 +200,21 @@ impl DownloadProgressRenderer {
             self.initialized = true;
         }
 
+        let [bar_line, status_line] = self.frame_lines(downloaded, total, elapsed, done);
+        let _ = execute!(stdout, MoveUp(3), Clear(ClearType::FromCursorDown));
+        let _ = writeln!(stdout);
+        let _ = writeln!(stdout, "{bar_line}");
+        let _ = writeln!(stdout, "{status_line}");
+        let _ = stdout.flush();
+    }
+
+    fn frame_lines(
+        &mut self,
+        downloaded: u64,
+        total: Option<u64>,
+        elapsed: Duration,
+        done: bool,
+    ) -> [String; 2] {
         let spinner = if done {
             "✓"
         } else {
@@ -117,31 +224,9 @@ impl DownloadProgressRenderer {
             frame
         };
 
-        let ratio = total
-            .map(|total_bytes| {
-                if total_bytes == 0 {
-                    1.0
-                } else {
-                    downloaded as f64 / total_bytes as f64
-                }
-            })
-            .unwrap_or(0.0)
-            .clamp(0.0, 1.0);
-
-        let bar_width = 43.0;
-        let filled = (ratio * bar_width).round();
-        let empty = bar_width - filled;
-        let bar = format!(
-            "[{}{}]",
-            "█".repeat(filled as usize),
-            " ".repeat(empty as usize)
-        );
+        let bar = progress_bar(downloaded, total);
 
-        let speed_bps = if elapsed.as_secs_f64() > 0.0 {
-            downloaded as f64 / elapsed.as_secs_f64()
-        } else {
-            0.0
-        };
+        let speed_bps = self.speed(downloaded, elapsed, done);
         let speed_text = format!("{}/s", format_bytes_f64(speed_bps));
 
         let eta = total.and_then(|total_bytes| estimate_eta(downloaded, total_bytes, elapsed));
@@ -171,17 +256,340 @@ impl DownloadProgressRenderer {
         let speed_cell = speed_cell.cyan();
         let eta_text = eta_text.magenta();
 
-        let _ = execute!(stdout, MoveUp(3), Clear(ClearType::FromCursorDown));
-        let _ = writeln!(stdout);
-        let _ = writeln!(stdout, "{spinner} {bar}  eta {eta_text}");
+        [
+            format!("{spinner} {bar}  eta {eta_text}"),
+            format!("{status_cell} {downloaded_cell} / {total_cell} {speed_cell}"),
+        ]
+    }
+
+    /// append-only fallback for dumb terminals and redirected output: no escapes, throttled.
+    fn draw_plain_line(
+        &mut self,
+        downloaded: u64,
+        total: Option<u64>,
+        elapsed: Duration,
+        done: bool,
+    ) {
+        let due = self
+            .last_plain_line
+            .is_none_or(|last| last.elapsed() >= PLAIN_PROGRESS_INTERVAL);
+        if !(done || due) {
+            return;
+        }
+        self.last_plain_line = Some(Instant::now());
+
+        let speed_bps = self.speed(downloaded, elapsed, done);
+        let mut stdout = std::io::stdout();
         let _ = writeln!(
             stdout,
-            "{status_cell} {downloaded_cell} / {total_cell} {speed_cell}"
+            "{}",
+            plain_progress_line(downloaded, total, speed_bps, done)
         );
         let _ = stdout.flush();
     }
 }
 
+/// running totals of a multi-episode download, for the batch summary line.
+#[derive(Debug)]
+pub struct BatchProgress {
+    total: usize,
+    finished: usize,
+    /// episodes that were actually transferred, as opposed to skipped or failed.
+    measured: usize,
+    bytes: u64,
+    /// wall clock start, so episodes downloaded side by side aren't counted twice.
+    started_at: Instant,
+}
+
+impl BatchProgress {
+    pub fn new(total: usize) -> Self {
+        Self {
+            total,
+            finished: 0,
+            measured: 0,
+            bytes: 0,
+            started_at: Instant::now(),
+        }
+    }
+
+    /// counts one finished episode; `stats` is `None` when it was skipped or failed.
+    pub fn finish(&mut self, stats: Option<&DownloadStats>) {
+        self.finished += 1;
+        if let Some(stats) = stats {
+            self.measured += 1;
+            self.bytes += stats.bytes;
+        }
+    }
+
+    pub fn remaining(&self) -> usize {
+        self.total.saturating_sub(self.finished)
+    }
+
+    pub fn total(&self) -> usize {
+        self.total
+    }
+
+    /// episodes that were transferred, leaving out skipped and failed ones.
+    pub fn downloaded(&self) -> usize {
+        self.measured
+    }
+
+    pub fn bytes(&self) -> u64 {
+        self.bytes
+    }
+
+    /// time left for the remaining episodes `elapsed` into the batch, assuming they are as
+    /// large as the average transferred one and arrive at the throughput observed so far.
+    fn eta_after(&self, elapsed: Duration) -> Option<Duration> {
+        if self.measured == 0 || self.bytes == 0 || elapsed.is_zero() {
+            return None;
+        }
+        let bytes_per_episode = self.bytes as f64 / self.measured as f64;
+        let throughput = self.bytes as f64 / elapsed.as_secs_f64();
+        Some(Duration::from_secs_f64(
+            bytes_per_episode * self.remaining() as f64 / throughput,
+        ))
+    }
+
+    /// e.g. `batch 2/12 episodes · 1.40 GB · eta 25:00`.
+    pub fn summary(&self) -> String {
+        self.summary_after(self.started_at.elapsed())
+    }
+
+    fn summary_after(&self, elapsed: Duration) -> String {
+        let eta = match self.eta_after(elapsed) {
+            Some(eta) if self.remaining() > 0 => format_duration(eta),
+            Some(_) => "00:00".to_string(),
+            None => "--:--".to_string(),
+        };
+        format!(
+            "batch {}/{} episodes · {} · eta {eta}",
+            self.finished,
+            self.total,
+            format_bytes(self.bytes)
+        )
+    }
+}
+
+/// one episode's place in a [`MultiProgressRenderer`].
+enum Slot {
+    /// not started yet; takes no lines.
+    Pending,
+    /// a label line followed by the two progress lines.
+    Active(Box<DownloadProgressRenderer>),
+    /// collapsed to a single summary line that is still part of the redrawn frame.
+    Done(String),
+    /// summary line already scrolled out of the redrawn frame.
+    Flushed,
+}
+
+/// lines to write for one redraw, after moving the cursor up over the previous frame.
+#[derive(Debug, PartialEq, Eq)]
+struct Frame {
+    move_up: usize,
+    lines: Vec<String>,
+}
+
+/// progress for several concurrent downloads, one block of lines per episode.
+///
+/// finished episodes collapse to a single line; once every episode above them has finished
+/// too, those lines are left in place and no longer redrawn.
+pub struct MultiProgressRenderer {
+    enabled: bool,
+    cursor_control: bool,
+    color: bool,
+    labels: Vec<String>,
+    slots: Vec<Slot>,
+    /// redrawn below the episodes, e.g. the [`BatchProgress`] summary.
+    footer: Option<String>,
+    drawn_lines: usize,
+}
+
+impl MultiProgressRenderer {
+    pub fn new(enabled: bool, labels: Vec<String>) -> Self {
+        let slots = labels.iter().map(|_| Slot::Pending).collect();
+        Self {
+            enabled,
+            cursor_control: supports_cursor_control(
+                std::io::stdout().is_terminal(),
+                std::env::var("TERM").ok().as_deref(),
+            ),
+            color: true,
+            labels,
+            slots,
+            footer: None,
+            drawn_lines: 0,
+        }
+    }
+
+    /// without color, each episode only prints plain start and summary lines.
+    pub fn with_color(mut self, color: bool) -> Self {
+        self.color = color;
+        self.cursor_control &= color;
+        self
+    }
+
+    pub fn handle(&mut self, slot: usize, event: DownloadEvent) {
+        if !self.enabled {
+            return;
+        }
+
+        if matches!(self.slots[slot], Slot::Pending) {
+            if !self.cursor_control {
+                self.print_plain(format!("{} {}", self.labels[slot], "downloading".dimmed()));
+            }
+            self.slots[slot] = Slot::Active(Box::new(DownloadProgressRenderer::new(true)));
+        }
+        if let Slot::Active(renderer) = &mut self.slots[slot] {
+            renderer.update(event);
+        }
+        self.draw();
+    }
+
+    /// collapses `slot` to `summary`, e.g. `✓ episode 3: done Frieren - 03.mp4`.
+    pub fn finish(&mut self, slot: usize, summary: String) {
+        if !self.enabled {
+            return;
+        }
+
+        if !self.cursor_control {
+            self.print_plain(summary);
+            self.slots[slot] = Slot::Flushed;
+            return;
+        }
+        self.slots[slot] = Slot::Done(summary);
+        self.draw();
+    }
+
+    /// sets the line shown below every episode; printed once without cursor control.
+    pub fn set_footer(&mut self, footer: String) {
+        if !self.enabled {
+            return;
+        }
+
+        if !self.cursor_control {
+            self.print_plain(footer);
+            return;
+        }
+        self.footer = Some(footer);
+        self.draw();
+    }
+
+    pub fn tick(&mut self) {
+        if self.enabled
+            && self
+                .slots
+                .iter()
+                .any(|slot| matches!(slot, Slot::Active(_)))
+        {
+            self.draw();
+        }
+    }
+
+    fn draw(&mut self) {
+        if !self.cursor_control {
+            return;
+        }
+
+        let frame = self.frame();
+        let mut stdout = std::io::stdout();
+        // `MoveUp(0)` still moves one line on some terminals.
+        if frame.move_up > 0 {
+            let _ = execute!(stdout, MoveUp(frame.move_up as u16));
+        }
+        let _ = execute!(stdout, MoveToColumn(0), Clear(ClearType::FromCursorDown));
+        for line in &frame.lines {
+            let _ = writeln!(stdout, "{line}");
+        }
+        let _ = stdout.flush();
+    }
+
+    /// builds the next redraw and records how many of its lines the one after must replace.
+    fn frame(&mut self) -> Frame {
+        let mut lines = Vec::new();
+        let mut settled_lines = 0;
+        let mut settled = true;
+
+        for (slot, label) in self.slots.iter_mut().zip(&self.labels) {
+            match slot {
+                Slot::Flushed => {}
+                Slot::Pending => settled = false,
+                Slot::Done(summary) => {
+                    lines.push(summary.clone());
+                    if settled {
+                        settled_lines += 1;
+                        *slot = Slot::Flushed;
+                    }
+                }
+                Slot::Active(renderer) => {
+                    settled = false;
+                    lines.push(label.bold().to_string());
+                    lines.extend(renderer.current_lines());
+                }
+            }
+        }
+
+        lines.extend(self.footer.iter().map(|footer| footer.dimmed().to_string()));
+
+        let move_up = self.drawn_lines;
+        self.drawn_lines = lines.len() - settled_lines;
+        Frame { move_up, lines }
+    }
+
+    fn print_plain(&self, line: String) {
+        let line = if self.color { line } else { strip_ansi(&line) };
+        let mut stdout = std::io::stdout();
+        let _ = writeln!(stdout, "{line}");
+        let _ = stdout.flush();
+    }
+}
+
+/// cursor movement is only safe on an interactive terminal that isn't `TERM=dumb`.
+fn supports_cursor_control(is_terminal: bool, term: Option<&str>) -> bool {
+    is_terminal && term != Some("dumb")
+}
+
+fn plain_progress_line(downloaded: u64, total: Option<u64>, speed_bps: f64, done: bool) -> String {
+    let status = if done { "done" } else { "downloading" };
+
+    match total {
+        Some(total_bytes) if total_bytes > 0 => {
+            let percent = (downloaded as f64 / total_bytes as f64 * 100.0).clamp(0.0, 100.0);
+            format!(
+                "{status} {percent:.0}% {} / {} {}/s",
+                format_bytes(downloaded),
+                format_bytes(total_bytes),
+                format_bytes_f64(speed_bps)
+            )
+        }
+        _ => format!(
+            "{status} {} {}/s",
+            format_bytes(downloaded),
+            format_bytes_f64(speed_bps)
+        ),
+    }
+}
+
+/// cells inside the brackets of the progress bar.
+const BAR_WIDTH: usize = 43;
+
+/// `[███   ]` filled in proportion to `downloaded / total`; empty while the size is unknown.
+fn progress_bar(downloaded: u64, total: Option<u64>) -> String {
+    let ratio = total
+        .map(|total_bytes| {
+            if total_bytes == 0 {
+                1.0
+            } else {
+                downloaded as f64 / total_bytes as f64
+            }
+        })
+        .unwrap_or(0.0)
+        .clamp(0.0, 1.0);
+
+    let filled = (ratio * BAR_WIDTH as f64).round() as usize;
+    format!("[{}{}]", "█".repeat(filled), " ".repeat(BAR_WIDTH - filled))
+}
+
 fn fit_cell(text: &str, width: usize, align_right: bool) -> String {
     let clipped = if text.len() > width {
         text[..width].to_string()
@@ -195,3 +603,153 @@ fn fit_cell(text: &str, width: usize, align_right: bool) -> String {
         format!("{clipped:<width$}")
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cursor_control_requires_a_capable_terminal() {
+        assert!(supports_cursor_control(true, Some("xterm-256color")));
+        assert!(supports_cursor_control(true, None));
+        assert!(!supports_cursor_control(true, Some("dumb")));
+        assert!(!supports_cursor_control(false, Some("xterm-256color")));
+    }
+
+    fn multi(slots: usize) -> MultiProgressRenderer {
+        let mut renderer =
+            MultiProgressRenderer::new(true, (1..=slots).map(|n| format!("episode {n}")).collect());
+        renderer.cursor_control = true;
+        renderer
+    }
+
+    #[test]
+    fn multi_renderer_frames_three_lines_per_active_slot() {
+        let mut renderer = multi(3);
+        renderer.slots[0] = Slot::Active(Box::new(DownloadProgressRenderer::new(true)));
+        renderer.slots[1] = Slot::Active(Box::new(DownloadProgressRenderer::new(true)));
+
+        let first = renderer.frame();
+        assert_eq!(first.move_up, 0);
+        assert_eq!(first.lines.len(), 6);
+        assert!(first.lines[0].contains("episode 1"));
+        assert!(first.lines[3].contains("episode 2"));
+
+        let second = renderer.frame();
+        assert_eq!(second.move_up, 6);
+        assert_eq!(second.lines.len(), 6);
+    }
+
+    #[test]
+    fn multi_renderer_collapses_and_settles_finished_slots() {
+        let mut renderer = multi(3);
+        renderer.slots[0] = Slot::Active(Box::new(DownloadProgressRenderer::new(true)));
+        renderer.slots[1] = Slot::Active(Box::new(DownloadProgressRenderer::new(true)));
+        assert_eq!(renderer.frame().lines.len(), 6);
+
+        // a slot below an active one stays in the redrawn frame as a single line.
+        renderer.slots[1] = Slot::Done("done 2".to_string());
+        let frame = renderer.frame();
+        assert_eq!(frame.move_up, 6);
+        assert_eq!(frame.lines.len(), 4);
+        assert_eq!(frame.lines[3], "done 2");
+
+        // once the first slot finishes, both summary lines settle and the next slot starts fresh.
+        renderer.slots[0] = Slot::Done("done 1".to_string());
+        let frame = renderer.frame();
+        assert_eq!(frame.move_up, 4);
+        assert_eq!(frame.lines, vec!["done 1", "done 2"]);
+
+        renderer.slots[2] = Slot::Active(Box::new(DownloadProgressRenderer::new(true)));
+        let frame = renderer.frame();
+        assert_eq!(frame.move_up, 0);
+        assert_eq!(frame.lines.len(), 3);
+    }
+
+    #[test]
+    fn plain_progress_line_has_no_escape_sequences() {
+        let line = plain_progress_line(512, Some(1024), 512.0, false);
+
+        assert!(line.starts_with("downloading 50% "));
+        assert!(!line.contains('\x1b'));
+        assert!(plain_progress_line(10, None, 0.0, true).starts_with("done "));
+    }
+
+    #[test]
+    fn batch_eta_scales_observed_throughput_to_remaining_episodes() {
+        let stats = |megabytes: u64, secs: u64| {
+            DownloadStats::fixture(megabytes * 1024 * 1024, Duration::from_secs(secs))
+        };
+
+        let mut batch = BatchProgress::new(6);
+        assert_eq!(batch.eta_after(Duration::from_secs(1)), None);
+
+        // 300 MB and 100 MB downloaded side by side, 100s into the batch:
+        // 200 MB per episode at 4 MB/s, however long each one took on its own
+        batch.finish(Some(&stats(300, 90)));
+        batch.finish(Some(&stats(100, 80)));
+        // a skipped episode counts as finished but doesn't skew the averages
+        batch.finish(None);
+
+        let elapsed = Duration::from_secs(100);
+        assert_eq!(batch.remaining(), 3);
+        assert_eq!(batch.eta_after(elapsed), Some(Duration::from_secs(150)));
+        assert_eq!(
+            batch.summary_after(elapsed),
+            "batch 3/6 episodes · 400.00 MB · eta 02:30"
+        );
+    }
+
+    #[test]
+    fn multi_renderer_keeps_the_footer_below_the_slots() {
+        let mut renderer = multi(2);
+        renderer.slots[0] = Slot::Active(Box::new(DownloadProgressRenderer::new(true)));
+        renderer.footer = Some("batch 0/2 episodes".to_string());
+
+        let frame = renderer.frame();
+        assert_eq!(frame.lines.len(), 4);
+        assert!(frame.lines[3].contains("batch 0/2 episodes"));
+        assert_eq!(renderer.frame().move_up, 4);
+    }
+
+    #[test]
+    fn progress_bar_fills_in_proportion() {
+        let cells = |bar: &str| bar.chars().filter(|c| *c == '█').count();
+
+        assert_eq!(progress_bar(0, Some(100)).chars().count(), BAR_WIDTH + 2);
+        assert_eq!(cells(&progress_bar(0, Some(100))), 0);
+        assert_eq!(cells(&progress_bar(50, Some(100))), 22);
+        assert_eq!(cells(&progress_bar(100, Some(100))), BAR_WIDTH);
+        assert_eq!(cells(&progress_bar(200, Some(100))), BAR_WIDTH);
+        assert_eq!(cells(&progress_bar(0, Some(0))), BAR_WIDTH);
+        assert_eq!(cells(&progress_bar(10, None)), 0);
+    }
+
+    #[test]
+    fn fit_cell_clips_and_pads() {
+        assert_eq!(fit_cell("12.00 MB", 10, true), "  12.00 MB");
+        assert_eq!(fit_cell("waiting", 10, false), "waiting   ");
+        assert_eq!(fit_cell("downloading", 4, false), "down");
+    }
+
+    #[test]
+    fn speed_window_reflects_only_recent_samples() {
+        let secs = Duration::from_secs;
+        let mut window = SpeedWindow::default();
+        assert_eq!(window.rate(secs(1)), None);
+
+        // 1000 B/s for ten seconds, then a slowdown to 100 B/s
+        for second in 0..=10 {
+            window.record(secs(second), second * 1000);
+        }
+        assert_eq!(window.rate(secs(10)), Some(1000.0));
+        for second in 11..=14 {
+            window.record(secs(second), 10_000 + (second - 10) * 100);
+        }
+        assert_eq!(window.rate(secs(14)), Some(100.0));
+        assert!(average_speed(10_400, secs(14)) > 700.0);
+
+        // no new samples: the rate decays instead of holding the last value
+        assert!(window.rate(secs(18)).is_some_and(|rate| rate < 50.0));
+    }
+}
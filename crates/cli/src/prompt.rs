@@ -1,10 +1,11 @@
 use inquire::*;
+use pahe::client::{CookieStatus, EpisodeInfo, EpisodeVariant, SearchResult};
 use pahe::errors::*;
 
 use crate::args::*;
 use crate::utils::*;
 
-pub fn prompt_for_args(args: ResolveArgs) -> Result<RuntimeArgs> {
+pub fn prompt_for_args(args: ResolveArgs, domains: &AnimepaheDomains) -> Result<RuntimeArgs> {
     let series_default = args.series.unwrap_or_default();
 
     let series = Text::new("series:")
@@ -12,10 +13,12 @@ pub fn prompt_for_args(args: ResolveArgs) -> Result<RuntimeArgs> {
         .with_initial_value(&series_default)
         .prompt()
         .map_err(|err| PaheError::Message(format!("failed to read series URL: {err}")))?;
-    let normalized_series = normalize_series_input(&series)?;
+    let normalized_series = domains.normalize_series_input(&series)?;
 
-    let cookies = if let Some(cookies) = args.cookies {
+    let cookies = if let Some(cookies) = args.connection.cookies {
         cookies
+    } else if !args.connection.cookie.is_empty() || args.connection.cookies_file.is_some() {
+        String::new()
     } else {
         Text::new("cookies:")
             .with_help_message("you can also set this via PAHE_COOKIES environment variable")
@@ -24,24 +27,40 @@ pub fn prompt_for_args(args: ResolveArgs) -> Result<RuntimeArgs> {
     };
 
     let episodes = if let Some(session_id) = normalized_series.session_id {
-        EpisodeRange::Session {
+        EpisodeSelection::Session {
             anime_id: Some(normalized_series.anime_id),
             session_id,
         }
     } else {
         let episode_input = Text::new("episodes:")
             .with_help_message(
-                r#"a number (e.g. 12), range (e.g. 1-12), session id, or episode play url"#,
+                r#"numbers and ranges (e.g. 12, 1-12 or 1,3,5-8), all, session id, or episode play url"#,
             )
             .with_initial_value(&args.episodes.to_string())
             .prompt()
             .map_err(|err| PaheError::Message(format!("failed to read episode: {err}")))?;
 
-        episode_input.trim().parse::<EpisodeRange>().map_err(|_| {
-            PaheError::Message("episode must be a valid number/range/session id/url".to_string())
-        })?
+        episode_input
+            .trim()
+            .parse::<EpisodeSelection>()
+            .map_err(|_| {
+                PaheError::Message(
+                    "episode must be a valid number/range/all/session id/url".to_string(),
+                )
+            })?
     };
 
+    // interactive runs pick concrete variants per episode later on
+    if args.app_args.interactive {
+        return Ok(RuntimeArgs::new(
+            series,
+            cookies,
+            episodes,
+            args.quality,
+            args.lang,
+        ));
+    }
+
     let quality_choices = vec!["highest", "1080p", "720p", "480p", "lowest", "custom"];
     let quality_choice = Select::new("preferred quality:", quality_choices)
         .with_starting_cursor(0)
@@ -68,3 +87,85 @@ pub fn prompt_for_args(args: ResolveArgs) -> Result<RuntimeArgs> {
 
     Ok(RuntimeArgs::new(series, cookies, episodes, quality, lang))
 }
+
+pub fn prompt_for_episodes(links: Vec<EpisodeInfo>) -> Result<Vec<EpisodeInfo>> {
+    if links.len() <= 1 {
+        return Ok(links);
+    }
+
+    let options: Vec<String> = links.iter().map(describe_episode).collect();
+    let defaults: Vec<usize> = (0..options.len()).collect();
+    let picked = MultiSelect::new("episodes:", options)
+        .with_default(&defaults)
+        .with_help_message("space to toggle, enter to confirm")
+        .raw_prompt()
+        .map_err(|err| PaheError::Message(format!("failed to read episodes: {err}")))?;
+
+    let picked: Vec<EpisodeInfo> = picked
+        .into_iter()
+        .map(|option| links[option.index].clone())
+        .collect();
+
+    if picked.is_empty() {
+        return Err(PaheError::Message("no episodes selected".to_string()));
+    }
+
+    Ok(picked)
+}
+
+/// lets the user hand-pick one or more variants (mirror and quality) of an episode.
+pub fn prompt_for_variants(
+    episode: u32,
+    variants: Vec<EpisodeVariant>,
+) -> Result<Vec<EpisodeVariant>> {
+    let options: Vec<String> = variants.iter().map(describe_variant).collect();
+    let picked = MultiSelect::new(&format!("variants for episode {episode}:"), options)
+        .with_help_message("space to toggle, enter to confirm")
+        .raw_prompt()
+        .map_err(|err| PaheError::Message(format!("failed to read variants: {err}")))?;
+
+    if picked.is_empty() {
+        return Err(PaheError::NoSelectableVariant);
+    }
+
+    Ok(picked
+        .into_iter()
+        .map(|option| variants[option.index].clone())
+        .collect())
+}
+
+/// picks a search match, skipping the prompt when there is only one.
+pub fn prompt_for_search_result(results: Vec<SearchResult>) -> Result<SearchResult> {
+    if results.len() == 1 {
+        return Ok(results.into_iter().next().expect("one result"));
+    }
+
+    let options: Vec<String> = results.iter().map(describe_search_result).collect();
+    let picked = Select::new("series:", options)
+        .raw_prompt()
+        .map_err(|err| PaheError::Message(format!("failed to read series: {err}")))?;
+
+    results
+        .into_iter()
+        .nth(picked.index)
+        .ok_or_else(|| PaheError::Message("no series selected".to_string()))
+}
+
+pub fn prompt_for_fresh_cookies(status: CookieStatus) -> Result<String> {
+    let message = match status {
+        CookieStatus::Stale => "cookies are stale, paste fresh cookies:",
+        _ => "ddos-guard challenge hit, paste cookies:",
+    };
+
+    Text::new(message)
+        .with_help_message("copy them from a browser session that passed the challenge")
+        .prompt()
+        .map_err(|err| PaheError::Message(format!("failed to read cookies: {err}")))
+}
+
+pub fn confirm(message: &str) -> Result<bool> {
+    Confirm::new(message)
+        .with_default(false)
+        .prompt()
+        .map_err(|err| PaheError::Message(format!("failed to read confirmation: {err}")))
+}
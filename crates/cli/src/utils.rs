@@ -1,16 +1,33 @@
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
 use std::time::Duration;
 
+use pahe::ClientOptions;
+use pahe::client::{EpisodeInfo, EpisodeVariant, SearchResult};
 use pahe::errors::*;
+use regex::Regex;
 
+use crate::args::{AppArgs, CollisionPolicy, EpisodeSelection};
 use crate::constants::*;
+use crate::episode::{EpisodeURL, ListedEpisode};
 
 #[derive(Debug, Clone)]
 pub struct NormalizedSeriesInput {
     pub anime_id: String,
-    pub anime_link: String,
     pub session_id: Option<String>,
 }
 
+/// whether styled output is wanted: not with `--no-color`, a non-empty `NO_COLOR`, or when
+/// the output isn't a terminal.
+pub fn color_enabled(no_color_flag: bool, no_color_env: Option<&str>, is_terminal: bool) -> bool {
+    !no_color_flag && no_color_env.is_none_or(str::is_empty) && is_terminal
+}
+
+/// drops ansi styling and cursor escapes from `text`.
+pub fn strip_ansi(text: &str) -> String {
+    ANSI_ESCAPE_RE.replace_all(text, "").into_owned()
+}
+
 pub fn estimate_eta(downloaded: u64, total: u64, elapsed: Duration) -> Option<Duration> {
     if downloaded == 0 || total <= downloaded || elapsed.is_zero() {
         return None;
@@ -66,86 +83,665 @@ pub fn format_bytes_f64(bytes: f64) -> String {
     }
 }
 
-#[cfg(test)]
-pub fn normalize_series_link(raw: &str) -> Result<String> {
-    Ok(normalize_series_input(raw)?.anime_link)
-}
-
-pub fn normalize_series_input(raw: &str) -> Result<NormalizedSeriesInput> {
-    let input = raw.trim();
-    let normalized = input
-        .strip_prefix("https://")
-        .or_else(|| input.strip_prefix("http://"))
-        .unwrap_or(input);
-    let normalized = normalized.strip_prefix("www.").unwrap_or(normalized);
-    let normalized = normalized
-        .strip_prefix(ANIMEPAHE_DOMAIN)
-        .unwrap_or(normalized);
-    let normalized = normalized.strip_prefix('/').unwrap_or(normalized);
-
-    if UUID_RE.is_match(input) {
-        return Ok(NormalizedSeriesInput {
-            anime_id: input.to_string(),
-            anime_link: format!("https://{ANIMEPAHE_DOMAIN}/anime/{input}"),
-            session_id: None,
-        });
+/// whether an existing output file already holds the whole remote file.
+///
+/// an unknown remote size never counts as complete; interrupted downloads live in a separate
+/// partial file, so a short output can only come from somewhere else.
+pub fn is_already_complete(exists: bool, local_size: u64, remote_size: Option<u64>) -> bool {
+    exists && remote_size == Some(local_size)
+}
+
+/// decides where to write `path` under `policy`; `None` means the download should be skipped.
+pub fn resolve_collision(
+    path: &Path,
+    policy: CollisionPolicy,
+    exists: impl Fn(&Path) -> bool,
+) -> Option<PathBuf> {
+    if !exists(path) {
+        return Some(path.to_path_buf());
     }
 
-    if let Some((anime_id, session_id)) = normalized.split_once('/')
-        && UUID_RE.is_match(anime_id)
-        && SESSION_ID_RE.is_match(session_id)
-    {
-        return Ok(NormalizedSeriesInput {
-            anime_id: anime_id.to_string(),
-            anime_link: format!("https://{ANIMEPAHE_DOMAIN}/anime/{anime_id}"),
-            session_id: Some(session_id.to_string()),
-        });
+    match policy {
+        CollisionPolicy::Overwrite => Some(path.to_path_buf()),
+        CollisionPolicy::Skip => None,
+        CollisionPolicy::Rename => {
+            let stem = path
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            let extension = path
+                .extension()
+                .map(|ext| format!(".{}", ext.to_string_lossy()))
+                .unwrap_or_default();
+
+            (1..)
+                .map(|n| path.with_file_name(format!("{stem} ({n}){extension}")))
+                .find(|candidate| !exists(candidate))
+        }
     }
+}
 
-    if let Some(play_path) = normalized.strip_prefix("play/")
-        && let Some((anime_id, session_id)) = play_path.split_once('/')
-        && UUID_RE.is_match(anime_id)
-        && SESSION_ID_RE.is_match(session_id)
-    {
-        return Ok(NormalizedSeriesInput {
-            anime_id: anime_id.to_string(),
-            anime_link: format!("https://{ANIMEPAHE_DOMAIN}/anime/{anime_id}"),
-            session_id: Some(session_id.to_string()),
-        });
+/// replaces characters that are unsafe in file names on common filesystems.
+pub fn sanitize_file_component(raw: &str) -> String {
+    let replaced: String = raw
+        .chars()
+        .map(|c| match c {
+            '<' | '>' | ':' | '"' | '/' | '\\' | '|' | '?' | '*' => ' ',
+            c if c.is_control() => ' ',
+            c => c,
+        })
+        .collect();
+
+    replaced
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .trim_end_matches('.')
+        .to_string()
+}
+
+/// fills a `--name-template` or `--output-template` with sanitized values.
+///
+/// `{name:0N}` left-pads the value with zeros to `N` characters, e.g. `{episode:02}` renders `7`
+/// as `07`. placeholders without a value are dropped together with the separator in front of
+/// them, so `{episode} - {episode_title}` renders as `12` when the title is missing. unknown
+/// placeholders are left as written.
+pub fn render_name_template(template: &str, values: &[(&str, Option<&str>)]) -> String {
+    let rendered = TEMPLATE_PLACEHOLDER_RE.replace_all(template, |caps: &regex::Captures| {
+        let name = &caps["name"];
+        let Some((_, value)) = values.iter().find(|(key, _)| *key == name) else {
+            return caps[0].to_string();
+        };
+
+        match value.map(sanitize_file_component).filter(|v| !v.is_empty()) {
+            Some(value) => {
+                let width = caps
+                    .name("width")
+                    .and_then(|width| width.as_str().parse().ok())
+                    .unwrap_or(0);
+                format!("{}{value:0>width$}", &caps["separator"])
+            }
+            None => String::new(),
+        }
+    });
+
+    rendered
+        .trim_start_matches(|c: char| c.is_whitespace() || matches!(c, '-' | '_' | '.'))
+        .trim_end()
+        .to_string()
+}
+
+/// matches file names written for an episode and captures its number as `episode`.
+///
+/// with a template, `{episode}` becomes the capture and every other placeholder matches
+/// anything, separator included since it may have been dropped; any extension is allowed after
+/// it. without one, animepahe's own file names are assumed.
+pub fn episode_file_pattern(template: Option<&str>) -> Result<Regex> {
+    let Some(template) = template else {
+        return Ok(SERVER_EPISODE_FILE_RE.clone());
+    };
+
+    let mut pattern = String::from("^");
+    let mut last = 0;
+    for caps in TEMPLATE_PLACEHOLDER_RE.captures_iter(template) {
+        let whole = caps.get(0).expect("capture 0 always exists");
+        pattern.push_str(&regex::escape(&template[last..whole.start()]));
+        let separator = regex::escape(&caps["separator"]);
+        if &caps["name"] == "episode" {
+            pattern.push_str(&format!("{separator}0*(?P<episode>\\d+)"));
+        } else {
+            pattern.push_str(&format!("(?:{separator}.*?)?"));
+        }
+        last = whole.end();
     }
+    pattern.push_str(&regex::escape(&template[last..]));
+    pattern.push_str(r"(?:\.[^.]+)?$");
 
-    if let Some(anime_id) = normalized.strip_prefix("anime/")
-        && UUID_RE.is_match(anime_id)
-    {
-        return Ok(NormalizedSeriesInput {
-            anime_id: anime_id.to_string(),
-            anime_link: format!("https://{ANIMEPAHE_DOMAIN}/anime/{anime_id}"),
-            session_id: None,
-        });
+    if !pattern.contains("(?P<episode>") {
+        return Err(PaheError::Message(
+            "--only-new needs a template with an {episode} placeholder".to_string(),
+        ));
     }
+    Ok(Regex::new(&pattern)?)
+}
 
-    if let Some(caps) = ANIME_LINK_RE.captures(input)
-        && let Some(anime_id) = caps.get(1).map(|m| m.as_str())
-    {
-        return Ok(NormalizedSeriesInput {
-            anime_id: anime_id.to_string(),
-            anime_link: format!("https://{ANIMEPAHE_DOMAIN}/anime/{anime_id}"),
-            session_id: None,
-        });
+/// episode numbers of the files in `dir` that match `pattern`, skipping unfinished downloads.
+pub fn present_episode_numbers(dir: &Path, pattern: &Regex) -> Result<BTreeSet<u32>> {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(BTreeSet::new()),
+        Err(err) => {
+            return Err(PaheError::Message(format!(
+                "failed to read {}: {err}",
+                dir.display()
+            )));
+        }
+    };
+
+    Ok(entries
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_ok_and(|kind| kind.is_file()))
+        .filter_map(|entry| entry.file_name().into_string().ok())
+        .filter(|name| !name.contains(".pahe-tmp"))
+        .filter_map(|name| pattern.captures(&name)?["episode"].parse().ok())
+        .collect())
+}
+
+/// 1-indexed positions in `listed` whose episode number isn't in `present`.
+pub fn missing_episodes(listed: &[u32], present: &BTreeSet<u32>) -> BTreeSet<i32> {
+    (1..)
+        .zip(listed)
+        .filter(|(_, episode)| !present.contains(episode))
+        .map(|(position, _)| position)
+        .collect()
+}
+
+/// builds the transport options shared by the pahe, kwik and download clients.
+pub fn load_client_options(args: &AppArgs) -> Result<ClientOptions> {
+    let mut options = ClientOptions {
+        accept_invalid_certs: args.insecure,
+        kwik_hosts: args.kwik_hosts.clone(),
+        user_agent: args.user_agent.clone(),
+        ..Default::default()
+    };
+
+    if let Some(path) = &args.ca_cert {
+        let bytes = std::fs::read(path).map_err(|err| {
+            PaheError::Message(format!("failed to read {}: {err}", path.display()))
+        })?;
+        let cert = reqwest::Certificate::from_pem(&bytes)
+            .or_else(|_| reqwest::Certificate::from_der(&bytes))
+            .map_err(|err| {
+                PaheError::Message(format!(
+                    "failed to parse certificate {}: {err}",
+                    path.display()
+                ))
+            })?;
+        options.root_certificates.push(cert);
     }
 
-    if let Some(caps) = PLAY_LINK_RE.captures(input)
-        && let Some(anime_id) = caps.get(1).map(|m| m.as_str())
-        && let Some(session_id) = caps.get(2).map(|m| m.as_str())
-    {
-        return Ok(NormalizedSeriesInput {
-            anime_id: anime_id.to_string(),
-            anime_link: format!("https://{ANIMEPAHE_DOMAIN}/anime/{anime_id}"),
-            session_id: Some(session_id.to_string()),
+    if let Some(url) = &args.proxy {
+        options.proxy = Some(pahe::builder::parse_proxy(url)?);
+    }
+
+    Ok(options)
+}
+
+/// builds the reqwest client used for direct-link requests (probing, downloading).
+pub fn http_client(
+    options: &ClientOptions,
+    connect_timeout: Option<Duration>,
+) -> Result<reqwest::Client> {
+    let builder = options.apply(reqwest::Client::builder());
+    let builder = match connect_timeout {
+        Some(timeout) => builder.connect_timeout(timeout),
+        None => builder,
+    };
+
+    builder.build().map_err(PaheError::BuildClient)
+}
+
+/// one line of a `--batch` file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BatchEntry {
+    pub series: String,
+    /// episodes written after the url; `--episodes` applies when missing.
+    pub episodes: Option<EpisodeSelection>,
+}
+
+/// parses a `--batch` file: one series url per line, optionally followed by an episode
+/// selection such as `1-12` or `all`. blank lines and `#` comments are skipped.
+pub fn parse_batch_file(text: &str) -> std::result::Result<Vec<BatchEntry>, String> {
+    let mut entries = Vec::new();
+
+    for (number, line) in text.lines().enumerate() {
+        let line = line
+            .split_once('#')
+            .map_or(line, |(before, _)| before)
+            .trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        let (series, episodes) = match line.split_once(char::is_whitespace) {
+            Some((series, episodes)) => (series, Some(episodes.trim())),
+            None => (line, None),
+        };
+        let episodes = episodes
+            .map(|raw| raw.parse::<EpisodeSelection>())
+            .transpose()
+            .map_err(|err| format!("line {}: {err}", number + 1))?;
+
+        entries.push(BatchEntry {
+            series: series.to_string(),
+            episodes,
         });
     }
 
-    Err(PaheError::Message(
-        "invalid --series value: expected anime id/url or anime+session id/url".to_string(),
-    ))
+    Ok(entries)
+}
+
+/// one entry of an aria2c input file: the url, then indented per-download options.
+pub fn format_aria2_entry(episode: &EpisodeURL, user_agent: &str, out: Option<&str>) -> String {
+    let mut entry = format!(
+        "{}\n  referer={}\n  user-agent={user_agent}\n",
+        episode.url, episode.referer
+    );
+    if let Some(out) = out {
+        entry.push_str(&format!("  out={out}\n"));
+    }
+    entry
+}
+
+/// an extended m3u playlist of `episodes`.
+///
+/// kwik links only play with their referer, which is passed through `#EXTVLCOPT`; players
+/// that ignore it (e.g. mpv) need `--referrer` instead.
+pub fn format_m3u_playlist(episodes: &[EpisodeURL]) -> String {
+    let mut playlist = String::from("#EXTM3U\n");
+    for episode in episodes {
+        let title = [
+            episode.series_title.clone(),
+            Some(format!("episode {}", episode.index)),
+            episode.episode_title.clone(),
+        ]
+        .into_iter()
+        .flatten()
+        .collect::<Vec<_>>()
+        .join(" - ");
+
+        playlist.push_str(&format!(
+            "#EXTINF:-1,{title}\n#EXTVLCOPT:http-referrer={}\n{}\n",
+            episode.referer, episode.url
+        ));
+    }
+    playlist
+}
+
+/// one-line, human readable summary of a variant for pickers, e.g.
+/// `1080p jp BD · 1.20 GB — SubsPlease · 1080p BD`.
+pub fn describe_variant(variant: &EpisodeVariant) -> String {
+    let label = HTML_TAG_RE.replace_all(&variant.source_text, " ");
+    let label = label
+        .replace("&middot;", "·")
+        .replace("&amp;", "&")
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ");
+    let bluray = if variant.bluray { " BD" } else { "" };
+    let size = variant
+        .size_bytes
+        .map(|bytes| format!(" · {}", format_bytes(bytes)))
+        .unwrap_or_default();
+
+    format!(
+        "{}p {}{bluray}{size} — {label}",
+        variant.resolution, variant.lang
+    )
+}
+
+/// one-line label for an episode picker, e.g. `episode 3 - Title · 2023-10-06 · filler`.
+pub fn describe_episode(episode: &EpisodeInfo) -> String {
+    let mut label = format!("episode {}", episode.episode);
+    if let Some(title) = &episode.title {
+        label.push_str(&format!(" - {title}"));
+    }
+    // the api sends `YYYY-MM-DD HH:MM:SS`; the day is enough to tell episodes apart.
+    if let Some(aired_at) = &episode.aired_at {
+        let day = aired_at.split_whitespace().next().unwrap_or(aired_at);
+        label.push_str(&format!(" · {day}"));
+    }
+    if episode.filler {
+        label.push_str(" · filler");
+    }
+
+    label
+}
+
+/// renders `list` rows as an aligned table of episode, title, air date and duration.
+pub fn format_episode_table(episodes: &[ListedEpisode]) -> String {
+    let rows: Vec<[String; 4]> = episodes
+        .iter()
+        .map(|episode| {
+            let mut title = episode.title.clone().unwrap_or_else(|| "-".to_string());
+            if episode.filler {
+                title.push_str(" (filler)");
+            }
+            let aired = episode
+                .aired_at
+                .as_deref()
+                .and_then(|aired_at| aired_at.split_whitespace().next())
+                .unwrap_or("-")
+                .to_string();
+            let duration = episode.duration.clone().unwrap_or_else(|| "-".to_string());
+            [episode.episode.to_string(), title, aired, duration]
+        })
+        .collect();
+
+    let header = ["#", "title", "aired", "duration"].map(String::from);
+    let widths: Vec<usize> = (0..4)
+        .map(|column| {
+            std::iter::once(&header)
+                .chain(&rows)
+                .map(|row| row[column].chars().count())
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+
+    std::iter::once(&header)
+        .chain(&rows)
+        .map(|row| {
+            row.iter()
+                .zip(&widths)
+                .map(|(cell, width)| format!("{cell:<width$}"))
+                .collect::<Vec<_>>()
+                .join("  ")
+                .trim_end()
+                .to_string()
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// one-line summary of a search match, e.g. `Naruto (TV, 2002, 220 eps, Finished Airing)`.
+pub fn describe_search_result(result: &SearchResult) -> String {
+    let details: Vec<String> = [
+        result.kind.clone(),
+        result.year.map(|year| year.to_string()),
+        result.episodes.map(|episodes| format!("{episodes} eps")),
+        result.status.clone(),
+    ]
+    .into_iter()
+    .flatten()
+    .collect();
+
+    if details.is_empty() {
+        result.title.clone()
+    } else {
+        format!("{} ({})", result.title, details.join(", "))
+    }
+}
+
+#[cfg(test)]
+pub fn normalize_series_id(raw: &str) -> Result<String> {
+    Ok(AnimepaheDomains::new(ANIMEPAHE_DOMAIN, &[])
+        .normalize_series_input(raw)?
+        .anime_id)
+}
+
+/// `domains` as a regex alternation, e.g. `(?:animepahe\.si|animepahe\.ru)`.
+fn domain_alternation(domains: &[String]) -> String {
+    let escaped: Vec<String> = domains.iter().map(|domain| regex::escape(domain)).collect();
+    format!("(?:{})", escaped.join("|"))
+}
+
+/// the domains animepahe links are accepted on, with their link patterns compiled once.
+#[derive(Debug, Clone)]
+pub struct AnimepaheDomains {
+    domains: Vec<String>,
+    /// `/anime/{id}` links, capturing the id.
+    anime_link_re: Regex,
+    /// `/play/{id}/{session}` links, capturing both.
+    play_link_re: Regex,
+}
+
+impl AnimepaheDomains {
+    /// the known animepahe domains, plus `domain` (the one requests go to) and `accepted`.
+    pub fn new(domain: &str, accepted: &[String]) -> Self {
+        let mut domains: Vec<String> = KNOWN_ANIMEPAHE_DOMAINS
+            .iter()
+            .map(|domain| domain.to_string())
+            .collect();
+        for domain in std::iter::once(domain).chain(accepted.iter().map(String::as_str)) {
+            if !domains.iter().any(|known| known == domain) {
+                domains.push(domain.to_string());
+            }
+        }
+
+        let alternation = domain_alternation(&domains);
+        let anime_link_re = Regex::new(&format!(
+            r"^https?://(?:www\.)?{alternation}/anime/([a-f0-9-]{{36}})(?:[/?#].*)?$"
+        ))
+        .expect("anime link regex must compile");
+        let play_link_re = Regex::new(&format!(
+            r"^https?://(?:www\.)?{alternation}/play/([a-f0-9-]{{36}})/([a-f0-9]{{32,}})(?:[/?#].*)?$"
+        ))
+        .expect("play link regex must compile");
+
+        Self {
+            domains,
+            anime_link_re,
+            play_link_re,
+        }
+    }
+
+    /// the domains from `--domain` and `--accept-domain`, alongside the known ones.
+    pub fn from_args(args: &AppArgs) -> Self {
+        Self::new(&args.domain, &args.accept_domains)
+    }
+
+    /// reads a `--series` value: an anime or play link on one of the domains, an anime id, or
+    /// an anime and session id pair.
+    pub fn normalize_series_input(&self, raw: &str) -> Result<NormalizedSeriesInput> {
+        let input = raw.trim();
+        let normalized = input
+            .strip_prefix("https://")
+            .or_else(|| input.strip_prefix("http://"))
+            .unwrap_or(input);
+        let normalized = normalized.strip_prefix("www.").unwrap_or(normalized);
+        let normalized = self
+            .domains
+            .iter()
+            .find_map(|domain| normalized.strip_prefix(domain.as_str()))
+            .unwrap_or(normalized);
+        let normalized = normalized.strip_prefix('/').unwrap_or(normalized);
+
+        if UUID_RE.is_match(input) {
+            return Ok(NormalizedSeriesInput {
+                anime_id: input.to_string(),
+                session_id: None,
+            });
+        }
+
+        if let Some((anime_id, session_id)) = normalized.split_once('/')
+            && UUID_RE.is_match(anime_id)
+            && SESSION_ID_RE.is_match(session_id)
+        {
+            return Ok(NormalizedSeriesInput {
+                anime_id: anime_id.to_string(),
+                session_id: Some(session_id.to_string()),
+            });
+        }
+
+        if let Some(play_path) = normalized.strip_prefix("play/")
+            && let Some((anime_id, session_id)) = play_path.split_once('/')
+            && UUID_RE.is_match(anime_id)
+            && SESSION_ID_RE.is_match(session_id)
+        {
+            return Ok(NormalizedSeriesInput {
+                anime_id: anime_id.to_string(),
+                session_id: Some(session_id.to_string()),
+            });
+        }
+
+        if let Some(anime_id) = normalized.strip_prefix("anime/")
+            && UUID_RE.is_match(anime_id)
+        {
+            return Ok(NormalizedSeriesInput {
+                anime_id: anime_id.to_string(),
+                session_id: None,
+            });
+        }
+
+        if let Some(caps) = self.anime_link_re.captures(input)
+            && let Some(anime_id) = caps.get(1).map(|m| m.as_str())
+        {
+            return Ok(NormalizedSeriesInput {
+                anime_id: anime_id.to_string(),
+                session_id: None,
+            });
+        }
+
+        if let Some(caps) = self.play_link_re.captures(input)
+            && let Some(anime_id) = caps.get(1).map(|m| m.as_str())
+            && let Some(session_id) = caps.get(2).map(|m| m.as_str())
+        {
+            return Ok(NormalizedSeriesInput {
+                anime_id: anime_id.to_string(),
+                session_id: Some(session_id.to_string()),
+            });
+        }
+
+        Err(PaheError::Message(
+            "invalid --series value: expected anime id/url or anime+session id/url".to_string(),
+        ))
+    }
+
+    /// turns a play link given to `--episodes` into the episode it names, provided it is on
+    /// one of the domains; any other selection is returned as is.
+    pub fn check_selection(&self, selection: EpisodeSelection) -> Result<EpisodeSelection> {
+        let EpisodeSelection::PlayLink(link) = selection else {
+            return Ok(selection);
+        };
+
+        let caps = self.play_link_re.captures(&link).ok_or_else(|| {
+            PaheError::Message(format!(
+                "invalid --episodes value {link}: not a play link on an animepahe domain"
+            ))
+        })?;
+        Ok(EpisodeSelection::Session {
+            anime_id: Some(caps[1].to_string()),
+            session_id: caps[2].to_string(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn format_aria2_entry_lists_headers_and_out() {
+        let episode = EpisodeURL {
+            url: "https://cdn.example/ep3.mp4?token=x".to_string(),
+            ..EpisodeURL::fixture(3)
+        };
+
+        assert_eq!(
+            format_aria2_entry(&episode, "pahe/1.0", Some("Frieren - 03.mp4")),
+            "https://cdn.example/ep3.mp4?token=x\n  referer=https://kwik.si/f/abc\n  user-agent=pahe/1.0\n  out=Frieren - 03.mp4\n"
+        );
+        assert!(!format_aria2_entry(&episode, "pahe/1.0", None).contains("out="));
+    }
+
+    #[test]
+    fn format_m3u_playlist_titles_each_episode() {
+        let episode = |index: u32, title: Option<&str>| EpisodeURL {
+            referer: format!("https://kwik.si/f/{index}"),
+            episode_title: title.map(String::from),
+            ..EpisodeURL::fixture(index)
+        };
+
+        assert_eq!(
+            format_m3u_playlist(&[episode(1, Some("The Journey's End")), episode(2, None)]),
+            "#EXTM3U\n\
+             #EXTINF:-1,Frieren - episode 1 - The Journey's End\n\
+             #EXTVLCOPT:http-referrer=https://kwik.si/f/1\n\
+             https://cdn.example/ep1.mp4\n\
+             #EXTINF:-1,Frieren - episode 2\n\
+             #EXTVLCOPT:http-referrer=https://kwik.si/f/2\n\
+             https://cdn.example/ep2.mp4\n"
+        );
+    }
+
+    #[test]
+    fn missing_episodes_fills_gaps_in_offset_numbering() {
+        let listed: Vec<u32> = (13..=24).collect();
+        let present = BTreeSet::from([13, 14, 16, 24, 30]);
+
+        assert_eq!(
+            missing_episodes(&listed, &present),
+            BTreeSet::from([3, 5, 6, 7, 8, 9, 10, 11])
+        );
+        assert!(missing_episodes(&[1, 2], &BTreeSet::from([1, 2])).is_empty());
+    }
+
+    #[test]
+    fn episode_file_pattern_reads_numbers_back_from_names() {
+        let episode = |pattern: &Regex, name: &str| {
+            pattern
+                .captures(name)
+                .map(|caps| caps["episode"].to_string())
+        };
+
+        let templated = episode_file_pattern(Some("{series} - {episode:02} - {episode_title}"))
+            .expect("template should compile");
+        assert_eq!(
+            episode(&templated, "Frieren - 07 - The Hero.mp4").as_deref(),
+            Some("7")
+        );
+        assert_eq!(
+            episode(&templated, "Frieren - 12.mkv").as_deref(),
+            Some("12")
+        );
+        assert_eq!(episode(&templated, "notes.txt"), None);
+
+        let server = episode_file_pattern(None).expect("default pattern");
+        assert_eq!(
+            episode(
+                &server,
+                "AnimePahe_Sousou_no_Frieren_-_05_1080p_SubsPlease.mp4"
+            )
+            .as_deref(),
+            Some("5")
+        );
+        assert!(episode_file_pattern(Some("{series}")).is_err());
+    }
+
+    #[test]
+    fn parse_batch_file_reads_urls_and_selections() {
+        let text = "\
+# weekly watch list
+https://animepahe.si/anime/4a9abc55-0a54-c544-3e14-736c79ddafe7 1-3,5
+
+  https://animepahe.si/anime/123e4567-e89b-12d3-a456-426614174000   # whole default
+123e4567-e89b-12d3-a456-426614174001 all
+";
+
+        let entries = parse_batch_file(text).expect("batch file should parse");
+        assert_eq!(entries.len(), 3);
+        assert_eq!(
+            entries[0].series,
+            "https://animepahe.si/anime/4a9abc55-0a54-c544-3e14-736c79ddafe7"
+        );
+        assert_eq!(
+            entries[0].episodes,
+            Some(EpisodeSelection::Episodes([1, 2, 3, 5].into()))
+        );
+        assert_eq!(entries[1].episodes, None);
+        assert_eq!(entries[2].episodes, Some(EpisodeSelection::All));
+
+        let err =
+            parse_batch_file("https://animepahe.si/anime/x\nhttps://animepahe.si/anime/y 3-x")
+                .unwrap_err();
+        assert!(err.starts_with("line 2:"), "{err}");
+    }
+
+    #[test]
+    fn color_enabled_honors_flag_env_and_terminal() {
+        assert!(color_enabled(false, None, true));
+        assert!(color_enabled(false, Some(""), true));
+        assert!(!color_enabled(true, None, true));
+        assert!(!color_enabled(false, Some("1"), true));
+        assert!(!color_enabled(false, None, false));
+    }
+
+    #[test]
+    fn is_already_complete_requires_matching_size() {
+        assert!(is_already_complete(true, 1024, Some(1024)));
+        assert!(!is_already_complete(true, 512, Some(1024)));
+        assert!(!is_already_complete(true, 1024, None));
+        assert!(!is_already_complete(false, 0, Some(0)));
+    }
 }
@@ -51,6 +51,9 @@ pub enum KwikError {
     #[error("invalid base")]
     InvalidBase,
 
+    #[error("packed chunk {chunk:?} is too large to decode")]
+    DecodeOverflow { chunk: String },
+
     #[error("kwik retry limit exceeded for {link}")]
     RetryLimitExceeded { link: String },
 
@@ -1,15 +1,28 @@
 use regex::Regex;
 use reqwest::cookie::Jar;
-use reqwest::header::{ACCEPT, CONTENT_TYPE, LOCATION, ORIGIN, REFERER, USER_AGENT};
+use reqwest::header::{
+    ACCEPT, CONTENT_LENGTH, CONTENT_TYPE, LOCATION, ORIGIN, REFERER, USER_AGENT,
+};
 use reqwest::redirect::Policy;
-use reqwest::{Client, Url};
-use std::sync::Arc;
+use reqwest::{Client, RequestBuilder, Url};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 use tracing::{debug, info};
 
 use crate::errors::{KwikError, ParserError, Result};
+use crate::options::ClientOptions;
 use crate::{parser, utils};
 
-const CLIENT_UA: &str = "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/138.0.0.0 Safari/537.36";
+/// attempts [`KwikClient::resolve_file_with_configured_retries`] makes before giving up, unless
+/// configured otherwise.
+pub const DEFAULT_KWIK_RETRIES: u8 = 5;
+
+/// host labels of the kwik mirror family that pahe.win is known to point at.
+pub const DEFAULT_KWIK_HOSTS: &[&str] = &["kwik", "kwikie"];
+
+/// digits of every base up to 64, in order; [`decode_base`] reads numbers with its prefix.
+pub const BASE_ALPHABET: &str = "0123456789abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ+/";
 
 #[derive(Debug, Clone)]
 pub struct PaheLink {
@@ -60,15 +73,185 @@ pub struct KwikClient {
     client: Client,
     no_redirect_client: Client,
     base_alphabet: String,
+    kwik_link_re: Regex,
+    /// `None` when an injected client brings its own.
+    user_agent: Option<String>,
+    retries: u8,
+    cache: Option<LinkCache>,
+}
+
+/// direct links resolved by [`KwikClient::resolve_direct_link`], keyed by pahe link.
+///
+/// kwik links expire, so entries are only reused for `ttl`.
+struct LinkCache {
+    ttl: Duration,
+    entries: Mutex<HashMap<String, (Instant, DirectLink)>>,
+}
+
+impl LinkCache {
+    fn get(&self, pahe_link: &str) -> Option<DirectLink> {
+        let entries = self.entries.lock().unwrap_or_else(|err| err.into_inner());
+        entries
+            .get(pahe_link)
+            .filter(|(stored_at, _)| stored_at.elapsed() < self.ttl)
+            .map(|(_, link)| link.clone())
+    }
+
+    fn insert(&self, pahe_link: &str, link: DirectLink) {
+        let mut entries = self.entries.lock().unwrap_or_else(|err| err.into_inner());
+        entries.retain(|_, (stored_at, _)| stored_at.elapsed() < self.ttl);
+        entries.insert(pahe_link.to_string(), (Instant::now(), link));
+    }
+}
+
+/// builds the regex matching quoted kwik file links on any of the given host labels.
+///
+/// a label matches as a whole dns label, optionally under regional subdomains
+/// (`eu.kwik.si`), so unrelated hosts such as `notkwik.si` are rejected.
+fn kwik_link_regex<'a>(hosts: impl IntoIterator<Item = &'a str>) -> Result<Regex> {
+    let labels = hosts
+        .into_iter()
+        .map(regex::escape)
+        .collect::<Vec<_>>()
+        .join("|");
+
+    Ok(Regex::new(&format!(
+        r#"\"(https?://(?:[a-zA-Z0-9-]+\.)*(?:{labels})\.[^/\s\"]+/[^/\s\"]+/[^\"\s]*)\""#
+    ))?)
+}
+
+/// points a kwik link at its file page: pahe.win sometimes hands out the `/d/` download
+/// path, which only accepts the form POST, instead of the `/f/` page that carries the form.
+fn normalize_file_url(link: &str) -> String {
+    match Url::parse(link) {
+        Ok(mut url) => {
+            if let Some(rest) = url.path().strip_prefix("/d/") {
+                let path = format!("/f/{rest}");
+                url.set_path(&path);
+            }
+            url.to_string()
+        }
+        Err(_) => link.to_string(),
+    }
+}
+
+/// reads `input` as a number written in `base` using the first `base` characters of
+/// `alphabet` as digits; characters outside those digits count as zero.
+///
+/// ```
+/// use pahe_core::kwik::{BASE_ALPHABET, decode_base};
+///
+/// assert_eq!(decode_base("ff", 16, BASE_ALPHABET).unwrap(), 255);
+/// assert_eq!(decode_base("zz", 36, BASE_ALPHABET).unwrap(), 1295);
+/// assert!(decode_base("1", 65, BASE_ALPHABET).is_err());
+///
+/// // digits are characters, not bytes
+/// assert_eq!(decode_base("γβ", 3, "αβγ").unwrap(), 7);
+/// assert!(decode_base("β", 4, "αβγ").is_err());
+/// ```
+pub fn decode_base(input: &str, base: usize, alphabet: &str) -> Result<u128> {
+    let digits: Vec<char> = alphabet.chars().take(base).collect();
+    if base < 2 || digits.len() < base {
+        return Err(KwikError::InvalidBase);
+    }
+
+    input.chars().try_fold(0u128, |value, ch| {
+        let digit = digits.iter().position(|&d| d == ch).unwrap_or(0) as u128;
+        value
+            .checked_mul(base as u128)
+            .and_then(|value| value.checked_add(digit))
+            .ok_or_else(|| KwikError::DecodeOverflow {
+                chunk: input.to_string(),
+            })
+    })
+}
+
+/// unpacks the payload of kwik's packed `eval` script.
+///
+/// every character is a run of `alphabet_key` digits ended by `alphabet_key[base]`; the
+/// digits spell its char code plus `offset` in `base`.
+///
+/// ```
+/// use pahe_core::kwik::decode_js_style;
+///
+/// // 'h' is 104 and 'i' 105; plus 7 that's 421 and 422 in base 5, written with "abcdefg"
+/// // and each ended by 'f'
+/// assert_eq!(decode_js_style("ecbfeccf", "abcdefg", 7, 5).unwrap(), "hi");
+/// ```
+pub fn decode_js_style(
+    encoded: &str,
+    alphabet_key: &str,
+    offset: i64,
+    base: usize,
+) -> Result<String> {
+    unpack(encoded, alphabet_key, offset, base, |chunk, base| {
+        decode_base(chunk, base, BASE_ALPHABET)
+    })
+}
+
+/// the loop behind [`decode_js_style`], reading each chunk's digits with `decode`.
+fn unpack(
+    encoded: &str,
+    alphabet_key: &str,
+    offset: i64,
+    base: usize,
+    decode: impl Fn(&str, usize) -> Result<u128>,
+) -> Result<String> {
+    let sentinel = alphabet_key
+        .chars()
+        .nth(base)
+        .ok_or(KwikError::InvalidAlphabetBaseIndex { base })?;
+
+    let mut output = String::new();
+    let chars: Vec<char> = encoded.chars().collect();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let mut chunk = String::new();
+        while i < chars.len() && chars[i] != sentinel {
+            chunk.push(chars[i]);
+            i += 1;
+        }
+        i += 1;
+
+        let mut replaced = chunk;
+        for (idx, c) in alphabet_key.chars().enumerate() {
+            replaced = replaced.replace(c, &idx.to_string());
+        }
+
+        let code = decode(&replaced, base)? as i128 - offset as i128;
+        let ch = u32::try_from(code).ok().and_then(char::from_u32);
+        output.push(ch.unwrap_or('\0'));
+    }
+
+    Ok(output)
 }
 
 impl KwikClient {
     /// creates a kwik client with shared cookie storage for get/post requests.
     pub fn new() -> Result<Self> {
-        info!("initializing kwik client");
+        Self::with_options(&ClientOptions::default())
+    }
+
+    /// creates a kwik client whose http clients honor the given transport options.
+    pub fn with_options(options: &ClientOptions) -> Result<Self> {
+        info!(
+            extra_roots = options.root_certificates.len(),
+            accept_invalid_certs = options.accept_invalid_certs,
+            kwik_hosts = ?options.kwik_hosts,
+            has_proxy = options.proxy.is_some(),
+            "initializing kwik client"
+        );
+        let kwik_link_re = kwik_link_regex(
+            DEFAULT_KWIK_HOSTS
+                .iter()
+                .copied()
+                .chain(options.kwik_hosts.iter().map(String::as_str)),
+        )?;
         let jar = Arc::new(Jar::default());
 
-        let client = Client::builder()
+        let client = options
+            .apply(Client::builder())
             .cookie_provider(jar.clone())
             .build()
             .map_err(|source| KwikError::BuildClient {
@@ -76,7 +259,8 @@ impl KwikClient {
                 source,
             })?;
 
-        let no_redirect_client = Client::builder()
+        let no_redirect_client = options
+            .apply(Client::builder())
             .cookie_provider(jar)
             .redirect(Policy::none())
             .build()
@@ -88,42 +272,58 @@ impl KwikClient {
         Ok(Self {
             client,
             no_redirect_client,
-            base_alphabet: "0123456789abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ+/"
-                .to_string(),
+            base_alphabet: BASE_ALPHABET.to_string(),
+            kwik_link_re,
+            user_agent: Some(options.user_agent().to_string()),
+            retries: DEFAULT_KWIK_RETRIES,
+            cache: None,
         })
     }
 
-    fn decode_base(&self, input: &str, from_base: usize, to_base: usize) -> Result<i64> {
-        let from_alphabet = &self.base_alphabet[..from_base];
-        let to_alphabet = &self.base_alphabet[..to_base];
+    /// remembers links resolved by [`KwikClient::resolve_direct_link`] for `ttl`, so
+    /// resolving the same pahe link again (e.g. on a retry) skips kwik entirely.
+    pub fn with_cache(mut self, ttl: Duration) -> Self {
+        self.cache = Some(LinkCache {
+            ttl,
+            entries: Mutex::new(HashMap::new()),
+        });
+        self
+    }
 
-        let mut value: i64 = 0;
-        for (idx, ch) in input.chars().rev().enumerate() {
-            if let Some(pos) = from_alphabet.find(ch) {
-                value += (pos as i64) * (from_base as i64).pow(idx as u32);
-            }
-        }
+    /// sends every request through `client` instead of the ones built from [`ClientOptions`],
+    /// so its proxy, timeouts, user agent and cookie store apply.
+    ///
+    /// the client needs a cookie store for kwik to accept its form. since the redirect to the
+    /// direct link can't be switched off per request, a client that follows it ends up at the
+    /// direct link, whose url is then taken instead of the `Location` header.
+    pub fn with_client(mut self, client: Client) -> Self {
+        self.client = client.clone();
+        self.no_redirect_client = client;
+        self.user_agent = None;
+        self
+    }
 
-        if value == 0 {
-            return Ok(to_alphabet
-                .chars()
-                .next()
-                .unwrap_or('0')
-                .to_string()
-                .parse::<i64>()?);
-        }
+    /// sets how many times [`KwikClient::resolve_file_with_configured_retries`] fetches a file page
+    /// before giving up; `1` means a single attempt. defaults to [`DEFAULT_KWIK_RETRIES`].
+    pub fn with_retries(mut self, retries: u8) -> Self {
+        self.retries = retries.max(1);
+        self
+    }
 
-        let mut v = value;
-        let mut out = String::new();
-        while v > 0 {
-            let i = (v % to_base as i64) as usize;
-            out.insert(0, to_alphabet.chars().nth(i).unwrap_or('0'));
-            v /= to_base as i64;
+    /// adds the configured user agent, leaving an injected client's own one in place.
+    fn with_user_agent(&self, request: RequestBuilder) -> RequestBuilder {
+        match &self.user_agent {
+            Some(user_agent) => request.header(USER_AGENT, user_agent),
+            None => request,
         }
+    }
 
-        Ok(out.parse::<i64>()?)
+    /// [`decode_base`] with the client's `base_alphabet`.
+    fn decode_base(&self, input: &str, base: usize) -> Result<u128> {
+        decode_base(input, base, &self.base_alphabet)
     }
 
+    /// [`decode_js_style`], reading the digits with the client's `base_alphabet`.
     fn decode_js_style(
         &self,
         encoded: &str,
@@ -131,33 +331,9 @@ impl KwikClient {
         offset: i64,
         base: usize,
     ) -> Result<String> {
-        let sentinel = alphabet_key
-            .chars()
-            .nth(base)
-            .ok_or(KwikError::InvalidAlphabetBaseIndex { base })?;
-
-        let mut output = String::new();
-        let chars: Vec<char> = encoded.chars().collect();
-        let mut i = 0;
-
-        while i < chars.len() {
-            let mut chunk = String::new();
-            while i < chars.len() && chars[i] != sentinel {
-                chunk.push(chars[i]);
-                i += 1;
-            }
-            i += 1;
-
-            let mut replaced = chunk;
-            for (idx, c) in alphabet_key.chars().enumerate() {
-                replaced = replaced.replace(c, &idx.to_string());
-            }
-
-            let code = self.decode_base(&replaced, base, 10)? - offset;
-            output.push(char::from_u32(code as u32).unwrap_or('\0'));
-        }
-
-        Ok(output)
+        unpack(encoded, alphabet_key, offset, base, |chunk, base| {
+            self.decode_base(chunk, base)
+        })
     }
 
     fn origin_from_url(url: &str) -> Option<String> {
@@ -170,14 +346,13 @@ impl KwikClient {
     fn extract_link_and_token(&self, decoded: &str) -> Result<(String, String)> {
         debug!("extracting kwik form action and token from decoded payload");
         let form_action_re = Regex::new(r#"<form[^>]*action=[\"']([^\"']+)[\"']"#)?;
-        let kwik_link_re = Regex::new(r#"\"(https?://kwik\.[^/\s\"]+/[^/\s\"]+/[^\"\s]*)\""#)?;
 
         // Prefer form action if present; this is what receives the POST.
         let link = form_action_re
             .captures(decoded)
             .and_then(|c| c.get(1).map(|m| m.as_str().to_string()))
             .or_else(|| {
-                kwik_link_re
+                self.kwik_link_re
                     .captures(decoded)
                     .and_then(|c| c.get(1).map(|m| m.as_str().to_string()))
             })
@@ -199,14 +374,12 @@ impl KwikClient {
     async fn fetch_kwik_direct(&self, kwik_link: &str, token: &str) -> Result<String> {
         info!(%kwik_link, "posting kwik direct-link form");
         let mut req = self
-            .no_redirect_client
-            .post(kwik_link)
+            .with_user_agent(self.no_redirect_client.post(kwik_link))
             .header(REFERER, kwik_link)
             .header(
-                USER_AGENT,
-                "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/138.0.0.0 Safari/537.36",
+                ACCEPT,
+                "text/html,application/xhtml+xml,application/xml;q=0.9,*/*;q=0.8",
             )
-            .header(ACCEPT, "text/html,application/xhtml+xml,application/xml;q=0.9,*/*;q=0.8")
             .header(CONTENT_TYPE, "application/x-www-form-urlencoded")
             .form(&[("_token", token)]);
 
@@ -220,6 +393,13 @@ impl KwikClient {
             source,
         })?;
 
+        if resp.status().is_success() && resp.url().as_str() != kwik_link {
+            // an injected client followed the redirect; don't read the file itself
+            let location = resp.url().to_string();
+            debug!(%kwik_link, %location, "direct link redirect was followed");
+            return Ok(location);
+        }
+
         if resp.status().as_u16() != 302 {
             let status = resp.status();
             let body = resp
@@ -278,7 +458,7 @@ impl KwikClient {
             })?
             .replace(['\n', '\r'], "");
 
-        let kwik_direct_re = Regex::new(r#"\"(https?://kwik\.[^/\s\"]+/[^/\s\"]+/[^\"\s]*)\""#)?;
+        let kwik_direct_re = &self.kwik_link_re;
         let packed_re = Regex::new(
             r#"\(\s*\"([^\",]*)\"\s*,\s*\d+\s*,\s*\"([^\",]*)\"\s*,\s*(\d+)\s*,\s*(\d+)\s*,\s*\d+[a-zA-Z]?\s*\)"#,
         )?;
@@ -303,24 +483,20 @@ impl KwikClient {
             kwik_direct_re
                 .captures(&decoded)
                 .and_then(|c| c.get(1).map(|m| m.as_str().to_string()))
-                .map(|v| v.replace("/d/", "/f/"))
         } else {
             None
         }
+        .map(|file_url| normalize_file_url(&file_url))
         .ok_or(KwikError::MissingKwikLink)?;
 
-        // let links = self.resolve_file(&file_url, 5).await?;
-        // info!(%pahe_link, "resolved kwik direct link");
-
+        debug!(%file_url, "resolved kwik file url");
         Ok(PaheLink::new(pahe_link, file_url))
     }
 
     async fn fetch_file_body(&self, file_url: impl AsRef<str>) -> Result<String> {
         let file_url = file_url.as_ref();
         let resp = self
-            .client
-            .get(file_url)
-            .header(USER_AGENT, CLIENT_UA)
+            .with_user_agent(self.client.get(file_url))
             .send()
             .await
             .map_err(|source| KwikError::Request {
@@ -350,11 +526,83 @@ impl KwikClient {
         Ok(body)
     }
 
+    /// resolves a `pahe.win` link all the way to a downloadable direct link.
+    ///
+    /// served from the cache when one is enabled with [`KwikClient::with_cache`].
+    pub async fn resolve_direct_link(&self, pahe_link: &str) -> Result<DirectLink> {
+        if let Some(link) = self.cache.as_ref().and_then(|cache| cache.get(pahe_link)) {
+            debug!(%pahe_link, "using cached direct link");
+            return Ok(link);
+        }
+
+        let pahe = self.resolve_pahe_link(pahe_link).await?;
+        let file = self
+            .resolve_file_with_configured_retries(&pahe.file_url)
+            .await?;
+        let link = DirectLink {
+            referer: pahe.url,
+            direct_link: file.downloadable,
+        };
+
+        if let Some(cache) = &self.cache {
+            cache.insert(pahe_link, link.clone());
+        }
+        Ok(link)
+    }
+
+    /// checks that a resolved link still serves its file, returning the declared size.
+    ///
+    /// kwik links expire after a while; an expired one fails with
+    /// [`KwikError::HttpStatus`] (usually `403` or `404`), so it can be resolved again.
+    pub async fn validate_link(&self, link: &DirectLink) -> Result<Option<u64>> {
+        let resp = self
+            .with_user_agent(self.client.head(&link.direct_link))
+            .header(REFERER, &link.referer)
+            .send()
+            .await
+            .map_err(|source| KwikError::Request {
+                context: format!("validating direct link {}", link.direct_link),
+                source,
+            })?;
+
+        let status = resp.status();
+        if !status.is_success() {
+            return Err(KwikError::HttpStatus {
+                context: format!("direct link {}", link.direct_link),
+                status,
+                body: String::new(),
+            });
+        }
+
+        debug!(direct_link = %link.direct_link, "direct link is alive");
+        Ok(resp
+            .headers()
+            .get(CONTENT_LENGTH)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.parse().ok()))
+    }
+
     /// resolves file from a `file_url` into downloadable and embed links
+    ///
+    /// the file page is fetched at most `retries` times (at least once) while its packed
+    /// payload is missing or fails to decode.
     pub async fn resolve_file(&self, file_url: impl AsRef<str>, retries: u8) -> Result<KwikFile> {
-        let file_url = file_url.as_ref();
+        self.resolve_file_with(file_url.as_ref(), retries.max(1))
+            .await
+    }
+
+    /// like [`KwikClient::resolve_file`], using the attempts set with
+    /// [`KwikClient::with_retries`].
+    pub async fn resolve_file_with_configured_retries(
+        &self,
+        file_url: impl AsRef<str>,
+    ) -> Result<KwikFile> {
+        self.resolve_file_with(file_url.as_ref(), self.retries)
+            .await
+    }
 
-        debug!(%file_url, "extracting kwik links");
+    async fn resolve_file_with(&self, file_url: &str, retries: u8) -> Result<KwikFile> {
+        debug!(%file_url, retries, "extracting kwik links");
 
         let url = Url::parse(file_url).expect("invalid kwik file url"); // TODO
 
@@ -367,8 +615,9 @@ impl KwikClient {
         let caps = if let Some(c) = packed_re.captures(&page) {
             c
         } else {
-            debug!(%file_url, retries_remaining = retries - 1, "packed payload not found; retrying");
-            return Box::pin(self.resolve_file(file_url, retries - 1)).await;
+            return self
+                .retry_file(file_url, retries, "packed payload not found")
+                .await;
         };
 
         let encoded = caps.get(1).map(|m| m.as_str()).unwrap_or_default();
@@ -385,13 +634,10 @@ impl KwikClient {
         let decoded = match self.decode_js_style(encoded, alphabet_key, offset, base) {
             Ok(v) => v,
             Err(err) => {
-                debug!(
-                    %file_url,
-                    retries_remaining = retries - 1,
-                    error = %err,
-                    "failed to decode packed payload; retrying"
-                );
-                return Box::pin(self.resolve_file(file_url, retries - 1)).await;
+                debug!(%file_url, error = %err, "failed to decode packed payload");
+                return self
+                    .retry_file(file_url, retries, "packed payload failed to decode")
+                    .await;
             }
         };
 
@@ -424,25 +670,34 @@ impl KwikClient {
         Ok(KwikFile::new(embed_link, download_link))
     }
 
+    /// spends one of the remaining attempts on `file_url`, or fails once they run out.
+    async fn retry_file(&self, file_url: &str, retries: u8, reason: &str) -> Result<KwikFile> {
+        let retries_remaining = retries.saturating_sub(1);
+        if retries_remaining == 0 {
+            info!(%file_url, %reason, "giving up on kwik file");
+            return Err(KwikError::RetryLimitExceeded {
+                link: file_url.to_string(),
+            });
+        }
+
+        debug!(%file_url, retries_remaining, %reason, "retrying kwik file");
+        Box::pin(self.resolve_file_with(file_url, retries_remaining)).await
+    }
+
     pub async fn extract_kwik_stream(&self, embed_link: impl AsRef<str>) -> Result<Stream> {
         let embed_link = embed_link.as_ref();
 
         // step 1: extract embed body
         info!(%embed_link, "extracting embed");
 
-        let resp =
-            self.client
-                .get(embed_link)
-                .header(
-                    USER_AGENT,
-                    "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/138.0.0.0 Safari/537.36",
-                )
-                .send()
-                .await
-                .map_err(|source| KwikError::Request {
-                    context: format!("loading embed {embed_link}"),
-                    source,
-                })?;
+        let resp = self
+            .with_user_agent(self.client.get(embed_link))
+            .send()
+            .await
+            .map_err(|source| KwikError::Request {
+                context: format!("loading embed {embed_link}"),
+                source,
+            })?;
 
         if !resp.status().is_success() {
             let status = resp.status();
@@ -525,3 +780,380 @@ impl KwikClient {
         Ok(result)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn captured(re: &Regex, body: &str) -> Option<String> {
+        re.captures(body)
+            .and_then(|c| c.get(1).map(|m| m.as_str().to_string()))
+    }
+
+    #[test]
+    fn kwik_link_regex_matches_default_and_mirror_hosts() {
+        let re = kwik_link_regex(DEFAULT_KWIK_HOSTS.iter().copied()).unwrap();
+
+        assert_eq!(
+            captured(&re, r#"x="https://kwik.si/f/abc123""#).as_deref(),
+            Some("https://kwik.si/f/abc123")
+        );
+        assert_eq!(
+            captured(&re, r#"x="https://kwikie.cx/f/abc123""#).as_deref(),
+            Some("https://kwikie.cx/f/abc123")
+        );
+        assert_eq!(
+            captured(&re, r#"x="https://eu.kwik.cx/f/abc123""#).as_deref(),
+            Some("https://eu.kwik.cx/f/abc123")
+        );
+    }
+
+    #[test]
+    fn kwik_link_regex_rejects_unrelated_hosts() {
+        let re = kwik_link_regex(DEFAULT_KWIK_HOSTS.iter().copied()).unwrap();
+
+        assert!(captured(&re, r#"x="https://notkwik.si/f/abc123""#).is_none());
+        assert!(captured(&re, r#"x="https://example.com/f/abc123""#).is_none());
+    }
+
+    /// packs `text` the way kwik does: every char code plus `offset`, written in `base`
+    /// with `key`'s digits and terminated by `key[base]`.
+    fn pack(text: &str, key: &str, offset: i64, base: usize) -> String {
+        let digits: Vec<char> = key.chars().collect();
+        let mut packed = String::new();
+        for ch in text.chars() {
+            let mut code = ch as i64 + offset;
+            let mut chunk = Vec::new();
+            while code > 0 {
+                chunk.push(digits[(code % base as i64) as usize]);
+                code /= base as i64;
+            }
+            packed.extend(chunk.iter().rev());
+            packed.push(digits[base]);
+        }
+        packed
+    }
+
+    #[test]
+    fn decode_base_reads_numbers_in_any_base() {
+        let client = KwikClient::new().unwrap();
+
+        assert_eq!(client.decode_base("ff", 16).unwrap(), 255);
+        assert_eq!(client.decode_base("777", 8).unwrap(), 511);
+        assert_eq!(client.decode_base("101", 2).unwrap(), 5);
+        assert_eq!(client.decode_base("0", 10).unwrap(), 0);
+        assert!(matches!(
+            client.decode_base("1", 65),
+            Err(KwikError::InvalidBase)
+        ));
+    }
+
+    #[test]
+    fn decode_base_counts_non_ascii_digits_as_chars() {
+        assert_eq!(super::decode_base("βγα", 3, "αβγδ").unwrap(), 15);
+        assert!(matches!(
+            super::decode_base("1", 3, "αβ"),
+            Err(KwikError::InvalidBase)
+        ));
+        assert!(matches!(
+            super::decode_base("1", 1, BASE_ALPHABET),
+            Err(KwikError::InvalidBase)
+        ));
+    }
+
+    #[test]
+    fn decode_base_reports_overflow() {
+        let client = KwikClient::new().unwrap();
+        let long = "z".repeat(40);
+
+        assert!(matches!(
+            client.decode_base(&long, 36),
+            Err(KwikError::DecodeOverflow { chunk }) if chunk == long
+        ));
+    }
+
+    #[test]
+    fn decode_js_style_unpacks_known_payloads() {
+        let client = KwikClient::new().unwrap();
+        let cases = [
+            ("<form action=\"x\">", "abcdefg", 7, 5),
+            ("https://kwik.si/f/abc", "QWERTYUIOPx", 42, 10),
+            ("token", "lmn", 3, 2),
+        ];
+
+        for (text, key, offset, base) in cases {
+            let packed = pack(text, key, offset, base);
+            assert_eq!(
+                client.decode_js_style(&packed, key, offset, base).unwrap(),
+                text,
+                "key={key} base={base}"
+            );
+            assert_eq!(
+                super::decode_js_style(&packed, key, offset, base).unwrap(),
+                text
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn cached_direct_links_skip_the_network_within_ttl() {
+        use wiremock::matchers::{method, path};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        let base = server.uri();
+        let form = format!(
+            r#"<form action="{base}/d/abc123" method="POST"><input type="hidden" name="_token" value="tok"></form> /e/embed1"#
+        );
+        let packed = pack(&form, "abcdefg", 7, 5);
+
+        Mock::given(method("GET"))
+            .and(path("/pahe"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_string(format!(r#"<a href="{base}/f/abc123">"#)),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/f/abc123"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(format!(
+                r#"eval(function(){{}}("{packed}", 12, "abcdefg", 7, 5, 30))"#
+            )))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/d/abc123"))
+            .respond_with(
+                ResponseTemplate::new(302).insert_header("location", "https://cdn.example/ep.mp4"),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        // the mock server lives on 127.0.0.1, so accept `127` as a kwik host label
+        let options = ClientOptions {
+            kwik_hosts: vec!["127".to_string()],
+            ..Default::default()
+        };
+        let client = KwikClient::with_options(&options)
+            .unwrap()
+            .with_cache(Duration::from_secs(60));
+        let pahe_link = format!("{base}/pahe");
+
+        let first = client.resolve_direct_link(&pahe_link).await.unwrap();
+        let second = client.resolve_direct_link(&pahe_link).await.unwrap();
+
+        assert_eq!(first.direct_link, "https://cdn.example/ep.mp4");
+        assert_eq!(second.direct_link, first.direct_link);
+        assert_eq!(second.referer, pahe_link);
+        server.verify().await;
+    }
+
+    #[tokio::test]
+    async fn injected_client_is_used_and_may_follow_the_redirect() {
+        use reqwest::header::{HeaderMap, HeaderValue};
+        use wiremock::matchers::{header, method, path};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        let base = server.uri();
+        let form = format!(
+            r#"<form action="{base}/d/abc123" method="POST"><input type="hidden" name="_token" value="tok"></form> /e/embed1"#
+        );
+        let packed = pack(&form, "abcdefg", 7, 5);
+
+        Mock::given(method("GET"))
+            .and(path("/pahe"))
+            .and(header("x-client", "injected"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_string(format!(r#"<a href="{base}/f/abc123">"#)),
+            )
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/f/abc123"))
+            .and(header("x-client", "injected"))
+            .and(header("user-agent", "my-agent/1.0"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(format!(
+                r#"eval(function(){{}}("{packed}", 12, "abcdefg", 7, 5, 30))"#
+            )))
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/d/abc123"))
+            .and(header("x-client", "injected"))
+            .respond_with(
+                ResponseTemplate::new(302).insert_header("location", format!("{base}/ep.mp4")),
+            )
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/ep.mp4"))
+            .respond_with(ResponseTemplate::new(200).set_body_string("video"))
+            .mount(&server)
+            .await;
+
+        let mut headers = HeaderMap::new();
+        headers.insert("x-client", HeaderValue::from_static("injected"));
+        let injected = Client::builder()
+            .default_headers(headers)
+            .user_agent("my-agent/1.0")
+            .cookie_store(true)
+            .build()
+            .unwrap();
+        let options = ClientOptions {
+            kwik_hosts: vec!["127".to_string()],
+            ..Default::default()
+        };
+        let client = KwikClient::with_options(&options)
+            .unwrap()
+            .with_client(injected);
+
+        let link = client
+            .resolve_direct_link(&format!("{base}/pahe"))
+            .await
+            .unwrap();
+        assert_eq!(link.direct_link, format!("{base}/ep.mp4"));
+    }
+
+    #[tokio::test]
+    async fn validate_link_reports_size_or_expiry() {
+        use wiremock::matchers::{header, method, path};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        Mock::given(method("HEAD"))
+            .and(path("/alive.mp4"))
+            .and(header("referer", "https://kwik.si/f/abc123"))
+            .respond_with(ResponseTemplate::new(200).set_body_bytes(vec![0u8; 1234]))
+            .mount(&server)
+            .await;
+        Mock::given(method("HEAD"))
+            .and(path("/expired.mp4"))
+            .respond_with(ResponseTemplate::new(404))
+            .mount(&server)
+            .await;
+
+        let client = KwikClient::new().unwrap();
+        let link = |name: &str| DirectLink {
+            referer: "https://kwik.si/f/abc123".to_string(),
+            direct_link: format!("{}/{name}", server.uri()),
+        };
+
+        assert_eq!(
+            client.validate_link(&link("alive.mp4")).await.unwrap(),
+            Some(1234)
+        );
+        assert!(matches!(
+            client.validate_link(&link("expired.mp4")).await,
+            Err(KwikError::HttpStatus { status, .. }) if status == reqwest::StatusCode::NOT_FOUND
+        ));
+    }
+
+    #[tokio::test]
+    async fn resolve_file_with_one_retry_gives_up_immediately() {
+        use wiremock::matchers::{method, path};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/f/abc123"))
+            .respond_with(ResponseTemplate::new(200).set_body_string("<html>no payload</html>"))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let client = KwikClient::new().unwrap().with_retries(1);
+        let file_url = format!("{}/f/abc123", server.uri());
+        let result = client.resolve_file_with_configured_retries(&file_url).await;
+
+        assert!(matches!(
+            result,
+            Err(KwikError::RetryLimitExceeded { link }) if link == file_url
+        ));
+    }
+
+    #[tokio::test]
+    async fn resolve_file_uses_the_given_retries() {
+        use wiremock::matchers::{method, path};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/f/abc123"))
+            .respond_with(ResponseTemplate::new(200).set_body_string("<html>no payload</html>"))
+            .expect(2)
+            .mount(&server)
+            .await;
+
+        let client = KwikClient::new().unwrap().with_retries(1);
+        let file_url = format!("{}/f/abc123", server.uri());
+        let result = client.resolve_file(&file_url, 2).await;
+
+        assert!(matches!(result, Err(KwikError::RetryLimitExceeded { .. })));
+    }
+
+    #[test]
+    fn normalize_file_url_rewrites_download_paths_only() {
+        assert_eq!(
+            normalize_file_url("https://kwik.si/d/abc123"),
+            "https://kwik.si/f/abc123"
+        );
+        assert_eq!(
+            normalize_file_url("https://kwik.cx/f/abc123"),
+            "https://kwik.cx/f/abc123"
+        );
+        assert_eq!(
+            normalize_file_url("https://kwik.cx/f/abc/d/x"),
+            "https://kwik.cx/f/abc/d/x"
+        );
+    }
+
+    #[tokio::test]
+    async fn resolve_pahe_link_normalizes_direct_download_links() {
+        use wiremock::matchers::{method, path};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        for (page, body) in [
+            ("/si", r#"<a href="https://kwik.si/d/abc123">"#),
+            ("/cx", r#"<a href="https://kwik.cx/f/def456">"#),
+        ] {
+            Mock::given(method("GET"))
+                .and(path(page))
+                .respond_with(ResponseTemplate::new(200).set_body_string(body))
+                .mount(&server)
+                .await;
+        }
+
+        let client = KwikClient::new().unwrap();
+        let si = client
+            .resolve_pahe_link(&format!("{}/si", server.uri()))
+            .await
+            .unwrap();
+        let cx = client
+            .resolve_pahe_link(&format!("{}/cx", server.uri()))
+            .await
+            .unwrap();
+
+        assert_eq!(si.file_url, "https://kwik.si/f/abc123");
+        assert_eq!(cx.file_url, "https://kwik.cx/f/def456");
+    }
+
+    #[test]
+    fn kwik_link_regex_accepts_configured_hosts() {
+        let body = r#"x="https://kwx.mirror.to/f/abc123""#;
+        let defaults = kwik_link_regex(DEFAULT_KWIK_HOSTS.iter().copied()).unwrap();
+        let configured = kwik_link_regex(["kwik", "kwx"]).unwrap();
+
+        assert!(captured(&defaults, body).is_none());
+        assert_eq!(
+            captured(&configured, body).as_deref(),
+            Some("https://kwx.mirror.to/f/abc123")
+        );
+    }
+}
@@ -1,10 +1,12 @@
 pub mod errors;
 pub mod kwik;
+pub mod options;
 pub mod parser;
 pub mod utils;
 
 pub use errors::{KwikError, Result};
 pub use kwik::{DirectLink, KwikClient};
+pub use options::{ClientOptions, DEFAULT_USER_AGENT};
 
 #[cfg(test)]
 mod test {
@@ -0,0 +1,56 @@
+use reqwest::{Certificate, ClientBuilder, Proxy};
+
+/// user agent sent when none is configured, matching a desktop chrome build.
+pub const DEFAULT_USER_AGENT: &str = "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/138.0.0.0 Safari/537.36";
+
+/// transport settings shared by every reqwest client the library builds.
+#[derive(Debug, Clone, Default)]
+pub struct ClientOptions {
+    /// extra trusted roots, e.g. the CA of a TLS-intercepting corporate proxy.
+    pub root_certificates: Vec<Certificate>,
+    /// skips certificate verification entirely. only meant as a last resort.
+    pub accept_invalid_certs: bool,
+    /// extra kwik mirror host labels (e.g. `kwikie`) accepted on top of the built-in ones.
+    pub kwik_hosts: Vec<String>,
+    /// routes every request through this proxy (`http://`, `https://` or `socks5://`).
+    pub proxy: Option<Proxy>,
+    /// overrides [`DEFAULT_USER_AGENT`] for every request.
+    pub user_agent: Option<String>,
+}
+
+impl ClientOptions {
+    /// user agent every client and request should send.
+    pub fn user_agent(&self) -> &str {
+        self.user_agent.as_deref().unwrap_or(DEFAULT_USER_AGENT)
+    }
+
+    /// the first setting that only takes effect through [`ClientOptions::apply`], if any is set.
+    pub fn builder_only_setting(&self) -> Option<&'static str> {
+        if self.proxy.is_some() {
+            Some("proxy")
+        } else if !self.root_certificates.is_empty() {
+            Some("root certificate")
+        } else if self.accept_invalid_certs {
+            Some("accept invalid certs")
+        } else if self.user_agent.is_some() {
+            Some("user agent")
+        } else {
+            None
+        }
+    }
+
+    /// applies these options to a reqwest client builder.
+    pub fn apply(&self, mut builder: ClientBuilder) -> ClientBuilder {
+        for cert in &self.root_certificates {
+            builder = builder.add_root_certificate(cert.clone());
+        }
+
+        if let Some(proxy) = &self.proxy {
+            builder = builder.proxy(proxy.clone());
+        }
+
+        builder
+            .user_agent(self.user_agent())
+            .danger_accept_invalid_certs(self.accept_invalid_certs)
+    }
+}
@@ -17,6 +17,31 @@ pub enum DownloaderError {
         status: reqwest::StatusCode,
     },
 
+    #[error("downloaded {actual} bytes but the server advertised {expected}")]
+    SizeMismatch { expected: u64, actual: u64 },
+
+    #[error("sha-256 mismatch: expected {expected}, got {actual}")]
+    ChecksumMismatch { expected: String, actual: String },
+
+    #[error("server answered a resume from byte {requested} with a different range")]
+    RangeMismatch { requested: u64, served: Option<u64> },
+
+    #[error("download was cancelled")]
+    Cancelled,
+
+    #[error("{context} timed out after {after:?}")]
+    Timeout {
+        context: String,
+        after: std::time::Duration,
+    },
+
+    #[error("worker task failed while {context}: {source}")]
+    Worker {
+        context: String,
+        #[source]
+        source: tokio::task::JoinError,
+    },
+
     #[error("io error while {context}: {source}")]
     Io {
         context: String,
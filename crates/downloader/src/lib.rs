@@ -1,14 +1,38 @@
 mod errors;
 
-use std::collections::BTreeMap;
+use std::collections::VecDeque;
+use std::io::SeekFrom;
 use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
 use std::time::{Duration, Instant};
 
-use errors::{DownloaderError, Result};
-use reqwest::{Client, StatusCode, header};
+use errors::Result;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use reqwest::{Client, ClientBuilder, StatusCode, header};
+use sha2::{Digest, Sha256};
 use tokio::fs::File;
-use tokio::io::AsyncWriteExt;
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWrite, AsyncWriteExt};
 use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+
+pub use errors::DownloaderError;
+pub use tokio_util::sync::CancellationToken;
+
+/// file size each automatically chosen connection is responsible for.
+const AUTO_BYTES_PER_CONNECTION: u64 = 20 * 1024 * 1024;
+const AUTO_MAX_CONNECTIONS: u64 = 16;
+
+/// connection count used for `connections(0)`: one per ~20 MiB, clamped to `1..=16`.
+pub fn auto_connections(total_bytes: u64) -> usize {
+    total_bytes
+        .div_ceil(AUTO_BYTES_PER_CONNECTION)
+        .clamp(1, AUTO_MAX_CONNECTIONS) as usize
+}
+
+/// segment size used by [`download_to_writer`] when reordering parallel chunks.
+pub const ORDERED_SEGMENT_SIZE: u64 = 4 * 1024 * 1024;
 
 #[derive(Debug, Clone)]
 pub struct DownloadRequest {
@@ -16,6 +40,26 @@ pub struct DownloadRequest {
     pub url: String,
     pub output: PathBuf,
     pub connections: usize,
+    /// minimum wall time between two `Progress` events.
+    pub progress_interval: Duration,
+    /// caps the combined speed of all connections; `None` means unlimited.
+    pub max_bytes_per_sec: Option<u64>,
+    /// time allowed to establish a connection (see [`DownloadRequest::configure_client`]).
+    pub connect_timeout: Duration,
+    /// time allowed for a request to return response headers.
+    pub read_timeout: Duration,
+    /// time a single connection may go without receiving any bytes.
+    pub stall_timeout: Duration,
+    /// hex sha-256 the finished file must match.
+    pub expected_sha256: Option<String>,
+    /// stops the download with [`DownloaderError::Cancelled`] when triggered.
+    pub cancel: Option<CancellationToken>,
+    /// proxy used by the client [`download`] builds.
+    pub proxy: Option<reqwest::Proxy>,
+    /// user agent set on the client [`download`] builds.
+    pub user_agent: Option<String>,
+    /// extra headers sent with every request next to the referer, e.g. an `Origin`.
+    pub headers: HeaderMap,
 }
 
 impl DownloadRequest {
@@ -25,13 +69,115 @@ impl DownloadRequest {
             url: url.into(),
             output,
             connections: 8,
+            progress_interval: Duration::from_millis(100),
+            max_bytes_per_sec: None,
+            connect_timeout: Duration::from_secs(15),
+            read_timeout: Duration::from_secs(30),
+            stall_timeout: Duration::from_secs(60),
+            expected_sha256: None,
+            cancel: None,
+            proxy: None,
+            user_agent: None,
+            headers: HeaderMap::new(),
         }
     }
 
+    /// sets the number of parallel connections; `0` picks one from the file size
+    /// (see [`auto_connections`]).
     pub fn connections(mut self, connections: usize) -> Self {
-        self.connections = connections.max(1);
+        self.connections = connections;
+        self
+    }
+
+    /// limits how often `Progress` events are emitted; `Duration::ZERO` reports every write.
+    pub fn progress_interval(mut self, interval: Duration) -> Self {
+        self.progress_interval = interval;
+        self
+    }
+
+    /// caps the download speed, shared across every parallel connection.
+    pub fn max_bytes_per_sec(mut self, limit: Option<u64>) -> Self {
+        self.max_bytes_per_sec = limit.filter(|limit| *limit > 0);
+        self
+    }
+
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = timeout;
+        self
+    }
+
+    pub fn read_timeout(mut self, timeout: Duration) -> Self {
+        self.read_timeout = timeout;
+        self
+    }
+
+    /// fails a connection that receives no bytes for this long.
+    pub fn stall_timeout(mut self, timeout: Duration) -> Self {
+        self.stall_timeout = timeout;
+        self
+    }
+
+    /// verifies the finished file against a hex sha-256 digest before moving it into place.
+    pub fn expected_sha256(mut self, digest: impl Into<String>) -> Self {
+        self.expected_sha256 = Some(digest.into().trim().to_ascii_lowercase());
+        self
+    }
+
+    /// lets the caller abort the download; the partial file is kept for a later resume.
+    pub fn cancellation(mut self, token: CancellationToken) -> Self {
+        self.cancel = Some(token);
+        self
+    }
+
+    /// routes the download through a proxy.
+    pub fn proxy(mut self, proxy: reqwest::Proxy) -> Self {
+        self.proxy = Some(proxy);
+        self
+    }
+
+    /// overrides reqwest's default user agent, e.g. to match the one used for kwik.
+    pub fn user_agent(mut self, user_agent: impl Into<String>) -> Self {
+        self.user_agent = Some(user_agent.into());
         self
     }
+
+    /// adds headers sent with the HEAD request and every chunk, replacing same-named ones.
+    pub fn headers(mut self, headers: HeaderMap) -> Self {
+        self.headers.extend(headers);
+        self
+    }
+
+    /// adds one header; see [`DownloadRequest::headers`].
+    pub fn header(mut self, name: HeaderName, value: HeaderValue) -> Self {
+        self.headers.insert(name, value);
+        self
+    }
+
+    /// the referer plus the extra headers, as sent with every request.
+    fn request_headers(&self) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        if let Ok(referer) = HeaderValue::from_str(&self.referer) {
+            headers.insert(header::REFERER, referer);
+        }
+        headers.extend(self.headers.clone());
+        headers
+    }
+
+    /// applies the connect timeout, proxy and user agent to a client builder.
+    ///
+    /// [`download`] does this itself; callers of [`download_with_client`] own their client.
+    /// the read and stall timeouts are enforced per request either way.
+    pub fn configure_client(&self, builder: ClientBuilder) -> ClientBuilder {
+        let mut builder = builder.connect_timeout(self.connect_timeout);
+        if let Some(proxy) = &self.proxy {
+            builder = builder.proxy(proxy.clone());
+        }
+        if let Some(user_agent) = &self.user_agent {
+            builder = builder.user_agent(user_agent);
+        }
+
+        builder
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -52,293 +198,941 @@ pub enum DownloadEvent {
     },
 }
 
+/// result of a lightweight reachability check against a direct link.
+#[derive(Debug, Clone)]
+pub struct ProbeResult {
+    /// status returned for the probing range request (`200` or `206`).
+    pub status: StatusCode,
+    /// full size of the remote file, when the server reports it.
+    pub total_bytes: Option<u64>,
+    /// whether the server honored the byte range.
+    pub supports_ranges: bool,
+}
+
 #[derive(Debug, Clone)]
 pub struct DownloadSummary {
     pub output: PathBuf,
     pub downloaded_bytes: u64,
     pub elapsed: Duration,
+    /// throughput figures, including the two above.
+    pub stats: DownloadStats,
 }
 
-pub async fn suggest_filename(referer: &str, url: &str) -> Result<String> {
-    let client = Client::new();
-    suggest_filename_with_client(&client, referer, url).await
+/// throughput figures for a finished download.
+#[derive(Debug, Clone)]
+pub struct DownloadStats {
+    /// total bytes written to the output.
+    pub bytes: u64,
+    pub elapsed: Duration,
+    /// `bytes` divided by `elapsed`, in bytes per second.
+    pub average_bps: f64,
+    /// connections that actually transferred data (1 for single-stream downloads).
+    pub connections_used: usize,
+}
+
+#[cfg(any(test, feature = "test-util"))]
+impl DownloadStats {
+    /// a single-connection download of `bytes` that took `elapsed`.
+    pub fn fixture(bytes: u64, elapsed: Duration) -> Self {
+        Self {
+            bytes,
+            elapsed,
+            average_bps: bytes as f64 / elapsed.as_secs_f64(),
+            connections_used: 1,
+        }
+    }
+}
+
+/// byte counter shared by the single-stream and parallel paths.
+///
+/// every transfer reports through the caller's `on_event` callback via this type,
+/// so `Progress`/`Finished` events look the same regardless of how bytes arrive.
+/// `Progress` events are throttled to `min_interval`; `Finished` always fires.
+struct Progress<'a, F> {
+    on_event: &'a mut F,
+    started_at: Instant,
+    last_emit: Option<Instant>,
+    min_interval: Duration,
+    downloaded: u64,
+    total: Option<u64>,
+    /// bytes at the front of the partial file known to be complete, when that isn't simply
+    /// its length: the parallel path pre-sizes the file and fills it out of order.
+    resume_point: Option<u64>,
 }
 
-pub async fn download<F>(request: DownloadRequest, mut on_event: F) -> Result<DownloadSummary>
+impl<'a, F> Progress<'a, F>
 where
     F: FnMut(DownloadEvent) + Send,
 {
-    let client = Client::new();
+    fn new(on_event: &'a mut F, total: Option<u64>, min_interval: Duration) -> Self {
+        Self {
+            on_event,
+            started_at: Instant::now(),
+            last_emit: None,
+            min_interval,
+            downloaded: 0,
+            total,
+            resume_point: None,
+        }
+    }
 
-    let head = client
-        .head(&request.url)
-        .header(header::REFERER, &request.referer)
-        .send()
-        .await
-        .map_err(|source| DownloaderError::Request {
-            context: "sending HEAD request".to_string(),
-            source,
-        })?;
+    fn advance(&mut self, bytes: u64) {
+        self.downloaded = self.downloaded.saturating_add(bytes);
+        if let Some(point) = &mut self.resume_point {
+            *point = point.saturating_add(bytes);
+        }
 
-    let size = head
-        .headers()
-        .get(header::CONTENT_LENGTH)
-        .and_then(|v| v.to_str().ok())
-        .and_then(|v| v.parse::<u64>().ok());
+        let now = Instant::now();
+        let complete = self.total.is_some_and(|total| self.downloaded >= total);
+        let due = self
+            .last_emit
+            .is_none_or(|last| now.duration_since(last) >= self.min_interval);
+        if !due && !complete {
+            return;
+        }
 
-    let accepts_ranges = head
-        .headers()
-        .get(header::ACCEPT_RANGES)
-        .and_then(|v| v.to_str().ok())
-        .is_some_and(|v| v.eq_ignore_ascii_case("bytes"));
+        self.last_emit = Some(now);
+        (self.on_event)(DownloadEvent::Progress {
+            downloaded_bytes: self.downloaded,
+            total_bytes: self.total,
+            elapsed: self.started_at.elapsed(),
+        });
+    }
 
-    on_event(DownloadEvent::Started {
-        total_bytes: size,
-        connections: request.connections,
-        supports_ranges: accepts_ranges,
-    });
+    /// counts `offset` bytes that were already on disk before this run.
+    fn resume_at(&mut self, offset: u64) {
+        self.downloaded = offset;
+        self.resume_point = Some(offset);
+    }
 
-    if request.connections == 1 || size.is_none() || !accepts_ranges {
-        return single_stream_download(
-            &client,
-            &request.referer,
-            &request.url,
-            &request.output,
-            size,
-            &mut on_event,
-        )
-        .await;
+    /// forgets everything counted so far, for when a download starts over from scratch.
+    fn restart(&mut self) {
+        self.downloaded = 0;
+        self.last_emit = None;
+        self.resume_point = None;
     }
 
-    parallel_download(
-        &client,
-        &request.referer,
-        &request.url,
-        &request.output,
-        size.unwrap_or(0),
-        request.connections,
-        &mut on_event,
-    )
-    .await
-}
+    /// errors when the advertised size is known and the bytes written don't match it.
+    fn verify_size(&self) -> Result<()> {
+        match self.total {
+            Some(expected) if expected != self.downloaded => Err(DownloaderError::SizeMismatch {
+                expected,
+                actual: self.downloaded,
+            }),
+            _ => Ok(()),
+        }
+    }
 
-async fn suggest_filename_with_client(client: &Client, referer: &str, url: &str) -> Result<String> {
-    let response = client
-        .head(url)
-        .header(header::REFERER, referer)
-        .send()
-        .await
-        .map_err(|source| DownloaderError::Request {
-            context: "requesting filename metadata".to_string(),
-            source,
-        })?;
+    fn finish(self, output: &Path, connections_used: usize) -> DownloadSummary {
+        let stats = self.finish_stats(connections_used);
+        DownloadSummary {
+            output: output.to_path_buf(),
+            downloaded_bytes: stats.bytes,
+            elapsed: stats.elapsed,
+            stats,
+        }
+    }
 
-    if !response.status().is_success() {
-        return Err(DownloaderError::HttpStatus {
-            context: "requesting filename metadata".to_string(),
-            status: response.status(),
+    fn finish_stats(self, connections_used: usize) -> DownloadStats {
+        let elapsed = self.started_at.elapsed();
+        (self.on_event)(DownloadEvent::Finished {
+            downloaded_bytes: self.downloaded,
+            elapsed,
         });
-    }
 
-    if let Some(content_disposition) = response
-        .headers()
-        .get(header::CONTENT_DISPOSITION)
-        .and_then(|v| v.to_str().ok())
-        && let Some(filename) = parse_content_disposition_filename(content_disposition)
-    {
-        return Ok(filename);
+        let average_bps = if elapsed.is_zero() {
+            0.0
+        } else {
+            self.downloaded as f64 / elapsed.as_secs_f64()
+        };
+
+        DownloadStats {
+            bytes: self.downloaded,
+            elapsed,
+            average_bps,
+            connections_used,
+        }
     }
+}
 
-    Ok(filename_from_url(url))
+/// shared pacing for every worker of one download.
+///
+/// each read books its bytes on a timeline running at `bytes_per_sec`; readers that get
+/// ahead of it sleep until their slot, so the budget splits itself across however many
+/// workers are active. idle time is not banked, so a stall is never followed by a burst.
+struct RateLimiter {
+    bytes_per_sec: u64,
+    next_free: tokio::sync::Mutex<Instant>,
 }
 
-fn parse_content_disposition_filename(content_disposition: &str) -> Option<String> {
-    for segment in content_disposition.split(';').map(str::trim) {
-        if let Some(value) = segment.strip_prefix("filename*=UTF-8''") {
-            let decoded = percent_decode_filename(value);
-            if !decoded.is_empty() {
-                return Some(decoded);
-            }
+impl RateLimiter {
+    fn new(bytes_per_sec: u64) -> Self {
+        Self {
+            bytes_per_sec,
+            next_free: tokio::sync::Mutex::new(Instant::now()),
         }
+    }
 
-        if let Some(value) = segment.strip_prefix("filename=") {
-            let clean = value.trim_matches('"').trim();
-            if !clean.is_empty() {
-                return Some(clean.to_string());
-            }
-        }
+    async fn throttle(&self, bytes: u64) {
+        let cost = Duration::from_secs_f64(bytes as f64 / self.bytes_per_sec as f64);
+        let due = {
+            let mut next_free = self.next_free.lock().await;
+            *next_free = (*next_free).max(Instant::now()) + cost;
+            *next_free
+        };
+        tokio::time::sleep_until(due.into()).await;
     }
+}
 
-    None
+/// everything a single request of one download needs.
+#[derive(Clone)]
+struct Transfer {
+    client: Client,
+    /// referer and extra headers sent with each request.
+    headers: HeaderMap,
+    url: String,
+    /// strong etag or last-modified date of the file, sent as `If-Range` when resuming.
+    validator: Option<String>,
+    limiter: Option<Arc<RateLimiter>>,
+    read_timeout: Duration,
+    stall_timeout: Duration,
 }
 
-fn percent_decode_filename(value: &str) -> String {
-    let mut bytes = Vec::with_capacity(value.len());
-    let mut iter = value.as_bytes().iter().copied();
+impl Transfer {
+    /// sends `request`, failing if the response headers take longer than the read timeout.
+    async fn send(
+        &self,
+        request: reqwest::RequestBuilder,
+        context: impl Fn() -> String,
+    ) -> Result<reqwest::Response> {
+        within(self.read_timeout, &context, request.send())
+            .await?
+            .map_err(|source| DownloaderError::Request {
+                context: context(),
+                source,
+            })
+    }
 
-    while let Some(b) = iter.next() {
-        if b == b'%' {
-            let hi = iter.next();
-            let lo = iter.next();
-            if let (Some(hi), Some(lo)) = (hi, lo)
-                && let (Some(hi), Some(lo)) = (hex_value(hi), hex_value(lo))
-            {
-                bytes.push((hi << 4) | lo);
-                continue;
-            }
-            bytes.push(b'%');
-            if let Some(hi) = hi {
-                bytes.push(hi);
-            }
-            if let Some(lo) = lo {
-                bytes.push(lo);
-            }
-            continue;
-        }
+    /// reads the next body chunk, failing if the connection stalls.
+    async fn next_chunk(
+        &self,
+        response: &mut reqwest::Response,
+        context: impl Fn() -> String,
+    ) -> Result<Option<bytes::Bytes>> {
+        within(self.stall_timeout, &context, response.chunk())
+            .await?
+            .map_err(|source| DownloaderError::Request {
+                context: context(),
+                source,
+            })
+    }
 
-        bytes.push(b);
+    async fn throttle(&self, bytes: u64) {
+        if let Some(limiter) = &self.limiter {
+            limiter.throttle(bytes).await;
+        }
     }
+}
 
-    String::from_utf8_lossy(&bytes).to_string()
+/// runs `fut` until it finishes or `cancel` fires; dropping it aborts every worker.
+async fn cancellable<T>(
+    cancel: Option<&CancellationToken>,
+    fut: impl std::future::Future<Output = Result<T>>,
+) -> Result<T> {
+    let Some(cancel) = cancel else {
+        return fut.await;
+    };
+
+    tokio::select! {
+        result = fut => result,
+        _ = cancel.cancelled() => Err(DownloaderError::Cancelled),
+    }
 }
 
-fn hex_value(c: u8) -> Option<u8> {
-    match c {
-        b'0'..=b'9' => Some(c - b'0'),
-        b'a'..=b'f' => Some(c - b'a' + 10),
-        b'A'..=b'F' => Some(c - b'A' + 10),
-        _ => None,
+/// a spawned worker that is aborted when its handle is dropped, so an early return or
+/// cancellation never leaves chunk tasks running in the background.
+struct Worker<T>(JoinHandle<T>);
+
+impl<T> Drop for Worker<T> {
+    fn drop(&mut self) {
+        self.0.abort();
     }
 }
 
-fn filename_from_url(url: &str) -> String {
-    reqwest::Url::parse(url)
-        .ok()
-        .and_then(|url| {
-            url.path_segments()
-                .and_then(|mut segments| segments.next_back().map(str::to_string))
+/// fails with [`DownloaderError::Timeout`] when `fut` doesn't finish within `limit`.
+async fn within<T>(
+    limit: Duration,
+    context: impl Fn() -> String,
+    fut: impl std::future::Future<Output = T>,
+) -> Result<T> {
+    tokio::time::timeout(limit, fut)
+        .await
+        .map_err(|_| DownloaderError::Timeout {
+            context: context(),
+            after: limit,
         })
-        .filter(|name| !name.is_empty())
-        .unwrap_or_else(|| "download.bin".to_string())
 }
 
-async fn single_stream_download(
-    client: &Client,
-    referer: &str,
-    url: &str,
-    output: &Path,
-    total_size: Option<u64>,
-    on_event: &mut (impl FnMut(DownloadEvent) + Send),
-) -> Result<DownloadSummary> {
-    let output_str = output.to_string_lossy();
-    let started_at = Instant::now();
-    let mut response = client
-        .get(url)
-        .header(header::REFERER, referer)
-        .send()
-        .await
+pub async fn suggest_filename(referer: &str, url: &str) -> Result<String> {
+    let client = Client::new();
+    suggest_filename_with_client(&client, referer, url).await
+}
+
+pub async fn download<F>(request: DownloadRequest, on_event: F) -> Result<DownloadSummary>
+where
+    F: FnMut(DownloadEvent) + Send,
+{
+    let client = request
+        .configure_client(Client::builder())
+        .build()
         .map_err(|source| DownloaderError::Request {
-            context: "sending GET request".to_string(),
+            context: "building http client".to_string(),
             source,
         })?;
+    download_with_client(&client, request, on_event).await
+}
 
-    if !response.status().is_success() {
-        return Err(DownloaderError::HttpStatus {
-            context: "downloading file".to_string(),
-            status: response.status(),
-        });
+/// same as [`download`], but sends every request through a caller-configured client
+/// (custom roots, proxies, ...).
+pub async fn download_with_client<F>(
+    client: &Client,
+    request: DownloadRequest,
+    mut on_event: F,
+) -> Result<DownloadSummary>
+where
+    F: FnMut(DownloadEvent) + Send,
+{
+    let partial = partial_path(&request.output);
+    let validator_file = validator_path(&request.output);
+    let partial_len = tokio::fs::metadata(&partial)
+        .await
+        .map(|metadata| metadata.len())
+        .unwrap_or(0);
+    let (saved_validator, saved_offset) = read_resume_state(&validator_file).await;
+    let partial_len = saved_offset.map_or(partial_len, |offset| offset.min(partial_len));
+    let (transfer, size, connections, resume_from) = start_transfer(
+        client,
+        &request,
+        partial_len,
+        saved_validator.as_deref(),
+        &mut on_event,
+    )
+    .await?;
+    let mut progress = Progress::new(&mut on_event, size, request.progress_interval);
+
+    ensure_parent_dir(&request.output).await?;
+    if resume_from == 0 {
+        // nothing of a pre-sized parallel file counts as done until a worker reports it
+        let resume_point = (connections > 1).then_some(0);
+        remember_validator(&validator_file, transfer.validator.as_deref(), resume_point).await;
     }
+    let result = cancellable(request.cancel.as_ref(), async {
+        if resume_from > 0 {
+            resume_download(&transfer, &partial, resume_from, &mut progress).await
+        } else if connections == 1 {
+            single_stream_download(&transfer, &partial, &mut progress).await
+        } else {
+            parallel_download(&transfer, &partial, connections, &mut progress).await
+        }
+    })
+    .await
+    .and_then(|connections_used| progress.verify_size().map(|()| connections_used));
+    let result = match (result, &request.expected_sha256) {
+        (Ok(connections_used), Some(expected)) => verify_sha256(&partial, expected)
+            .await
+            .map(|()| connections_used),
+        (result, _) => result,
+    };
 
-    ensure_parent_dir(output).await?;
-    let mut file = File::create(output)
+    let connections_used = match result {
+        Ok(connections_used) => connections_used,
+        Err(DownloaderError::Cancelled) => {
+            let validator = transfer.validator.as_deref();
+            remember_validator(&validator_file, validator, progress.resume_point).await;
+            return Err(DownloaderError::Cancelled);
+        }
+        Err(err) => {
+            let _ = tokio::fs::remove_file(&partial).await;
+            let _ = tokio::fs::remove_file(&validator_file).await;
+            return Err(err);
+        }
+    };
+
+    let _ = tokio::fs::remove_file(&validator_file).await;
+    tokio::fs::rename(&partial, &request.output)
         .await
         .map_err(|source| DownloaderError::Io {
-            context: format!("creating output file {output_str}"),
+            context: format!("moving {} into place", partial.display()),
             source,
         })?;
 
-    let mut downloaded = 0u64;
+    Ok(progress.finish(&request.output, connections_used))
+}
 
-    loop {
-        let maybe_chunk = response
-            .chunk()
-            .await
-            .map_err(|source| DownloaderError::Request {
-                context: "reading response body".to_string(),
-                source,
-            })?;
+async fn verify_sha256(path: &Path, expected: &str) -> Result<()> {
+    let actual = sha256_file(path).await?;
+    check_sha256(expected, actual)
+}
 
-        let Some(chunk) = maybe_chunk else {
-            break;
-        };
+fn check_sha256(expected: &str, actual: String) -> Result<()> {
+    if actual != expected {
+        return Err(DownloaderError::ChecksumMismatch {
+            expected: expected.to_string(),
+            actual,
+        });
+    }
+
+    Ok(())
+}
+
+/// hashes a file front to back, so parallel chunks are digested in offset order.
+async fn sha256_file(path: &Path) -> Result<String> {
+    let mut file = File::open(path)
+        .await
+        .map_err(|source| DownloaderError::Io {
+            context: format!("opening {} for hashing", path.display()),
+            source,
+        })?;
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0u8; 64 * 1024];
 
-        file.write_all(&chunk)
+    loop {
+        let read = file
+            .read(&mut buffer)
             .await
             .map_err(|source| DownloaderError::Io {
-                context: format!("writing output file {output_str}"),
+                context: format!("hashing {}", path.display()),
                 source,
             })?;
-
-        downloaded = downloaded.saturating_add(chunk.len() as u64);
-        on_event(DownloadEvent::Progress {
-            downloaded_bytes: downloaded,
-            total_bytes: total_size,
-            elapsed: started_at.elapsed(),
-        });
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
     }
 
-    let elapsed = started_at.elapsed();
-    on_event(DownloadEvent::Finished {
-        downloaded_bytes: downloaded,
-        elapsed,
-    });
-
-    Ok(DownloadSummary {
-        output: output.to_path_buf(),
-        downloaded_bytes: downloaded,
-        elapsed,
-    })
+    Ok(format!("{:x}", hasher.finalize()))
 }
 
-async fn parallel_download(
-    client: &Client,
-    referer: &str,
-    url: &str,
-    output: &Path,
-    total_size: u64,
-    connections: usize,
-    on_event: &mut (impl FnMut(DownloadEvent) + Send),
-) -> Result<DownloadSummary> {
-    let output_str = output.to_string_lossy();
-    if total_size == 0 {
-        return single_stream_download(client, referer, url, output, Some(total_size), on_event)
-            .await;
+/// streams the download into `writer` instead of a file, e.g. stdout piped into ffmpeg.
+///
+/// a generic writer can't seek, so with several connections the file is fetched as
+/// consecutive segments of [`ORDERED_SEGMENT_SIZE`] that are written strictly in order;
+/// at most `connections` segments are buffered at once. `expected_sha256` is checked against
+/// the bytes as they are written; they have already reached `writer` by the time a mismatch
+/// is known, so it only fails the call.
+pub async fn download_to_writer<W, F>(
+    request: DownloadRequest,
+    writer: &mut W,
+    on_event: F,
+) -> Result<DownloadStats>
+where
+    W: AsyncWrite + Unpin,
+    F: FnMut(DownloadEvent) + Send,
+{
+    let client = request
+        .configure_client(Client::builder())
+        .build()
+        .map_err(|source| DownloaderError::Request {
+            context: "building http client".to_string(),
+            source,
+        })?;
+    download_to_writer_with_client(&client, request, writer, on_event).await
+}
+
+/// same as [`download_to_writer`], but sends every request through a caller-configured client.
+pub async fn download_to_writer_with_client<W, F>(
+    client: &Client,
+    request: DownloadRequest,
+    writer: &mut W,
+    mut on_event: F,
+) -> Result<DownloadStats>
+where
+    W: AsyncWrite + Unpin,
+    F: FnMut(DownloadEvent) + Send,
+{
+    let (transfer, size, connections, _) =
+        start_transfer(client, &request, 0, None, &mut on_event).await?;
+    let mut progress = Progress::new(&mut on_event, size, request.progress_interval);
+    let mut writer = HashingWriter {
+        inner: writer,
+        hasher: request.expected_sha256.as_ref().map(|_| Sha256::new()),
+    };
+
+    let connections_used = cancellable(request.cancel.as_ref(), async {
+        if connections == 1 {
+            stream_body(&transfer, &mut writer, "output", &mut progress).await
+        } else {
+            ordered_parallel_download(&transfer, &mut writer, connections, &mut progress).await
+        }
+    })
+    .await?;
+    writer.flush().await.map_err(|source| DownloaderError::Io {
+        context: "flushing output".to_string(),
+        source,
+    })?;
+    progress.verify_size()?;
+    if let (Some(expected), Some(hasher)) = (&request.expected_sha256, writer.hasher) {
+        check_sha256(expected, format!("{:x}", hasher.finalize()))?;
     }
 
-    let workers = connections.max(1).min(total_size as usize);
-    let chunk_size = total_size.div_ceil(workers as u64);
-    let (tx, mut rx) = mpsc::channel::<Result<(usize, Vec<u8>)>>(workers);
+    Ok(progress.finish_stats(connections_used))
+}
 
-    for idx in 0..workers {
-        let start = idx as u64 * chunk_size;
-        if start >= total_size {
-            continue;
+/// passes writes through to `inner`, hashing exactly the bytes it accepted.
+struct HashingWriter<'a, W> {
+    inner: &'a mut W,
+    hasher: Option<Sha256>,
+}
+
+impl<W> AsyncWrite for HashingWriter<'_, W>
+where
+    W: AsyncWrite + Unpin,
+{
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<std::io::Result<usize>> {
+        let this = &mut *self;
+        let poll = Pin::new(&mut *this.inner).poll_write(cx, buf);
+        if let (Poll::Ready(Ok(written)), Some(hasher)) = (&poll, &mut this.hasher) {
+            hasher.update(&buf[..*written]);
         }
-        let end = ((idx as u64 + 1) * chunk_size).min(total_size) - 1;
-        let client = client.clone();
-        let referer = referer.to_string();
-        let url = url.to_string();
-        let tx = tx.clone();
+        poll
+    }
 
-        tokio::spawn(async move {
-            let result = fetch_chunk(client, referer, url, idx, start, end).await;
-            let _ = tx.send(result).await;
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut *self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut *self.inner).poll_shutdown(cx)
+    }
+}
+
+/// sends the HEAD request, settles the connection count, reports `Started` and prepares
+/// the shared transfer state.
+///
+/// `partial_len` bytes already on disk are resumed over a single connection when the server
+/// supports ranges, they are short of the full size and `saved_validator`, recorded when they
+/// were written, still matches the file's etag or last-modified date; the offset to resume
+/// from is returned last (`0` for a fresh download).
+async fn start_transfer<F>(
+    client: &Client,
+    request: &DownloadRequest,
+    partial_len: u64,
+    saved_validator: Option<&str>,
+    on_event: &mut F,
+) -> Result<(Transfer, Option<u64>, usize, u64)>
+where
+    F: FnMut(DownloadEvent) + Send,
+{
+    let head = within(
+        request.read_timeout,
+        || "sending HEAD request".to_string(),
+        client
+            .head(&request.url)
+            .headers(request.request_headers())
+            .send(),
+    )
+    .await?
+    .map_err(|source| DownloaderError::Request {
+        context: "sending HEAD request".to_string(),
+        source,
+    })?;
+
+    let size = head
+        .headers()
+        .get(header::CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<u64>().ok());
+
+    let accepts_ranges = head
+        .headers()
+        .get(header::ACCEPT_RANGES)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.eq_ignore_ascii_case("bytes"));
+
+    // without a matching validator the bytes on disk may belong to another file
+    let validator = resume_validator(head.headers());
+    let resume_from = match (size, validator.as_deref()) {
+        (Some(total), Some(validator))
+            if accepts_ranges && partial_len < total && saved_validator == Some(validator) =>
+        {
+            partial_len
+        }
+        _ => 0,
+    };
+
+    let connections = match size {
+        Some(_) if !accepts_ranges => 1,
+        None => 1,
+        Some(_) if resume_from > 0 => 1,
+        Some(total) if request.connections == 0 => auto_connections(total),
+        Some(_) => request.connections,
+    };
+
+    on_event(DownloadEvent::Started {
+        total_bytes: size,
+        connections,
+        supports_ranges: accepts_ranges,
+    });
+
+    let transfer = Transfer {
+        client: client.clone(),
+        headers: request.request_headers(),
+        url: request.url.clone(),
+        validator,
+        limiter: request
+            .max_bytes_per_sec
+            .map(|limit| Arc::new(RateLimiter::new(limit))),
+        read_timeout: request.read_timeout,
+        stall_timeout: request.stall_timeout,
+    };
+
+    Ok((transfer, size, connections, resume_from))
+}
+
+/// the strong etag, or else the last-modified date, that identifies this version of the file.
+///
+/// weak etags can't be used with `If-Range`, so they are skipped.
+fn resume_validator(headers: &HeaderMap) -> Option<String> {
+    let value = |name| {
+        headers
+            .get(name)
+            .and_then(|v| v.to_str().ok())
+            .map(str::trim)
+            .filter(|v| !v.is_empty())
+    };
+    value(header::ETAG)
+        .filter(|etag| !etag.starts_with("W/"))
+        .or_else(|| value(header::LAST_MODIFIED))
+        .map(String::from)
+}
+
+/// sibling file holding the validator of the file a `.pahe-tmp` download belongs to.
+fn validator_path(output: &Path) -> PathBuf {
+    let mut name = output.as_os_str().to_owned();
+    name.push(".pahe-tmp.validator");
+    PathBuf::from(name)
+}
+
+/// records `validator` for a download that starts from zero, or forgets a stale one.
+///
+/// `resume_point` goes on a second line when the partial file's length doesn't say how much
+/// of it is complete. failing to write it only costs the ability to resume.
+async fn remember_validator(path: &Path, validator: Option<&str>, resume_point: Option<u64>) {
+    let _ = match (validator, resume_point) {
+        (Some(validator), Some(offset)) => {
+            tokio::fs::write(path, format!("{validator}\n{offset}")).await
+        }
+        (Some(validator), None) => tokio::fs::write(path, validator).await,
+        (None, _) => tokio::fs::remove_file(path).await,
+    };
+}
+
+/// the validator and, if one was recorded, the resume offset saved by [`remember_validator`].
+async fn read_resume_state(path: &Path) -> (Option<String>, Option<u64>) {
+    let Ok(saved) = tokio::fs::read_to_string(path).await else {
+        return (None, None);
+    };
+    match saved.split_once('\n') {
+        Some((validator, offset)) => (
+            Some(validator.to_string()),
+            // an unreadable offset means nothing on disk can be trusted
+            Some(offset.trim().parse().unwrap_or(0)),
+        ),
+        None => (Some(saved), None),
+    }
+}
+
+/// sibling file a download is written to until it is complete and verified.
+fn partial_path(output: &Path) -> PathBuf {
+    let mut name = output.as_os_str().to_owned();
+    name.push(".pahe-tmp");
+    PathBuf::from(name)
+}
+
+/// checks that `url` is still downloadable by requesting its first byte.
+///
+/// a `Range: bytes=0-0` GET is used instead of HEAD since some CDNs answer HEAD
+/// differently from the actual transfer. the body is never read.
+pub async fn probe_with_client(client: &Client, referer: &str, url: &str) -> Result<ProbeResult> {
+    let response = client
+        .get(url)
+        .header(header::RANGE, "bytes=0-0")
+        .header(header::REFERER, referer)
+        .send()
+        .await
+        .map_err(|source| DownloaderError::Request {
+            context: "probing direct link".to_string(),
+            source,
+        })?;
+
+    let status = response.status();
+    if !status.is_success() {
+        return Err(DownloaderError::HttpStatus {
+            context: "probing direct link".to_string(),
+            status,
         });
     }
 
-    drop(tx);
+    let supports_ranges = status == StatusCode::PARTIAL_CONTENT;
+    let total_bytes = if supports_ranges {
+        response
+            .headers()
+            .get(header::CONTENT_RANGE)
+            .and_then(|v| v.to_str().ok())
+            .and_then(parse_content_range_total)
+    } else {
+        response.content_length()
+    };
+
+    Ok(ProbeResult {
+        status,
+        total_bytes,
+        supports_ranges,
+    })
+}
+
+/// extracts the first byte from a `Content-Range: bytes 100-199/12345` header.
+fn parse_content_range_start(content_range: &str) -> Option<u64> {
+    content_range
+        .trim()
+        .strip_prefix("bytes ")
+        .and_then(|range| range.split_once('-'))
+        .and_then(|(start, _)| start.trim().parse::<u64>().ok())
+}
+
+/// extracts the complete length from a `Content-Range: bytes 0-0/12345` header.
+fn parse_content_range_total(content_range: &str) -> Option<u64> {
+    content_range
+        .rsplit_once('/')
+        .and_then(|(_, total)| total.trim().parse::<u64>().ok())
+}
+
+pub async fn suggest_filename_with_client(
+    client: &Client,
+    referer: &str,
+    url: &str,
+) -> Result<String> {
+    let response = client
+        .head(url)
+        .header(header::REFERER, referer)
+        .send()
+        .await
+        .map_err(|source| DownloaderError::Request {
+            context: "requesting filename metadata".to_string(),
+            source,
+        })?;
+
+    if !response.status().is_success() {
+        return Err(DownloaderError::HttpStatus {
+            context: "requesting filename metadata".to_string(),
+            status: response.status(),
+        });
+    }
+
+    if let Some(content_disposition) = response
+        .headers()
+        .get(header::CONTENT_DISPOSITION)
+        .and_then(|v| v.to_str().ok())
+        && let Some(filename) = parse_content_disposition_filename(content_disposition)
+    {
+        return Ok(sanitize_filename(&filename));
+    }
+
+    Ok(sanitize_filename(&filename_from_url(url)))
+}
+
+/// makes a server-suggested name safe to create inside the output directory.
+///
+/// path separators are collapsed into `_` and `.`/`..` components dropped, so the name can't
+/// leave the directory; characters windows rejects become `_`. other unicode is kept.
+pub fn sanitize_filename(name: &str) -> String {
+    let sanitized = name
+        .split(['/', '\\'])
+        .map(str::trim)
+        .filter(|part| !matches!(*part, "" | "." | ".."))
+        .map(|part| {
+            part.chars()
+                .map(|c| match c {
+                    '<' | '>' | ':' | '"' | '|' | '?' | '*' => '_',
+                    c if c.is_control() => '_',
+                    c => c,
+                })
+                .collect::<String>()
+        })
+        .collect::<Vec<_>>()
+        .join("_");
+    let sanitized = sanitized.trim_end_matches(['.', ' ']);
+
+    if sanitized.is_empty() {
+        "download.bin".to_string()
+    } else {
+        sanitized.to_string()
+    }
+}
+
+/// reads the file name from a `Content-Disposition` header.
+///
+/// an extended `filename*=charset'lang'value` (rfc 5987) wins over a plain `filename=`
+/// wherever it appears; quoted values may contain `;` and backslash-escaped quotes.
+fn parse_content_disposition_filename(content_disposition: &str) -> Option<String> {
+    let mut plain = None;
+
+    for param in split_header_params(content_disposition) {
+        let Some((name, value)) = param.split_once('=') else {
+            continue;
+        };
+        match name.trim().to_ascii_lowercase().as_str() {
+            "filename*" => {
+                if let Some(decoded) = decode_extended_value(value.trim())
+                    && !decoded.trim().is_empty()
+                {
+                    return Some(decoded);
+                }
+            }
+            "filename" if plain.is_none() => {
+                let clean = unquote(value.trim());
+                let clean = clean.trim();
+                if !clean.is_empty() {
+                    plain = Some(clean.to_string());
+                }
+            }
+            _ => {}
+        }
+    }
+
+    plain
+}
+
+/// splits header parameters on the `;`s outside quoted strings.
+fn split_header_params(value: &str) -> Vec<&str> {
+    let mut params = Vec::new();
+    let mut start = 0;
+    let mut quoted = false;
+    let mut escaped = false;
+
+    for (idx, c) in value.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if quoted => escaped = true,
+            '"' => quoted = !quoted,
+            ';' if !quoted => {
+                params.push(value[start..idx].trim());
+                start = idx + 1;
+            }
+            _ => {}
+        }
+    }
+    params.push(value[start..].trim());
+    params
+}
 
-    ensure_parent_dir(output).await?;
+/// strips the quotes of a quoted string and resolves its backslash escapes; bare tokens
+/// are returned as they are.
+fn unquote(value: &str) -> String {
+    let Some(inner) = value
+        .strip_prefix('"')
+        .map(|rest| rest.strip_suffix('"').unwrap_or(rest))
+    else {
+        return value.to_string();
+    };
+
+    let mut out = String::with_capacity(inner.len());
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => out.extend(chars.next()),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// decodes an rfc 5987 `charset'lang'percent-encoded` value; only utf-8 and iso-8859-1
+/// are understood.
+fn decode_extended_value(value: &str) -> Option<String> {
+    let value = unquote(value);
+    let (charset, rest) = value.split_once('\'')?;
+    let (_lang, encoded) = rest.split_once('\'')?;
+
+    match charset.trim().to_ascii_lowercase().as_str() {
+        "utf-8" => Some(percent_decode_filename(encoded)),
+        "iso-8859-1" => Some(
+            percent_decode_bytes(encoded)
+                .into_iter()
+                .map(char::from)
+                .collect(),
+        ),
+        _ => None,
+    }
+}
+
+fn percent_decode_filename(value: &str) -> String {
+    String::from_utf8_lossy(&percent_decode_bytes(value)).to_string()
+}
+
+fn percent_decode_bytes(value: &str) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(value.len());
+    let mut iter = value.as_bytes().iter().copied();
+
+    while let Some(b) = iter.next() {
+        if b == b'%' {
+            let hi = iter.next();
+            let lo = iter.next();
+            if let (Some(hi), Some(lo)) = (hi, lo)
+                && let (Some(hi), Some(lo)) = (hex_value(hi), hex_value(lo))
+            {
+                bytes.push((hi << 4) | lo);
+                continue;
+            }
+            bytes.push(b'%');
+            if let Some(hi) = hi {
+                bytes.push(hi);
+            }
+            if let Some(lo) = lo {
+                bytes.push(lo);
+            }
+            continue;
+        }
+
+        bytes.push(b);
+    }
+
+    bytes
+}
+
+fn hex_value(c: u8) -> Option<u8> {
+    match c {
+        b'0'..=b'9' => Some(c - b'0'),
+        b'a'..=b'f' => Some(c - b'a' + 10),
+        b'A'..=b'F' => Some(c - b'A' + 10),
+        _ => None,
+    }
+}
+
+fn filename_from_url(url: &str) -> String {
+    reqwest::Url::parse(url)
+        .ok()
+        .and_then(|url| {
+            url.path_segments()
+                .and_then(|mut segments| segments.next_back().map(str::to_string))
+        })
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| "download.bin".to_string())
+}
+
+async fn single_stream_download<F>(
+    transfer: &Transfer,
+    output: &Path,
+    progress: &mut Progress<'_, F>,
+) -> Result<usize>
+where
+    F: FnMut(DownloadEvent) + Send,
+{
+    let output_str = output.to_string_lossy();
     let mut file = File::create(output)
         .await
         .map_err(|source| DownloaderError::Io {
@@ -346,64 +1140,377 @@ async fn parallel_download(
             source,
         })?;
 
-    let mut next = 0usize;
-    let mut pending = BTreeMap::new();
-    let mut downloaded = 0u64;
-    let started_at = Instant::now();
+    stream_body(transfer, &mut file, &output_str, progress).await
+}
 
-    while let Some(msg) = rx.recv().await {
-        let (idx, bytes) = msg?;
-        pending.insert(idx, bytes);
+/// appends the bytes after `offset` to an interrupted single-stream download.
+///
+/// the range is conditional on the validator the partial file was written under, so the
+/// server answers with the full body, and the file is rewritten, if it changed since.
+async fn resume_download<F>(
+    transfer: &Transfer,
+    output: &Path,
+    offset: u64,
+    progress: &mut Progress<'_, F>,
+) -> Result<usize>
+where
+    F: FnMut(DownloadEvent) + Send,
+{
+    let output_str = output.to_string_lossy();
+    let mut request = transfer
+        .client
+        .get(&transfer.url)
+        .headers(transfer.headers.clone())
+        .header(header::RANGE, format!("bytes={offset}-"));
+    if let Some(validator) = &transfer.validator {
+        request = request.header(header::IF_RANGE, validator);
+    }
+    let mut response = transfer
+        .send(request, || format!("resuming from byte {offset}"))
+        .await?;
 
-        while let Some(bytes) = pending.remove(&next) {
-            file.write_all(&bytes)
+    match response.status() {
+        StatusCode::PARTIAL_CONTENT => {
+            // bytes from anywhere else would be spliced in at the wrong place
+            let served = response
+                .headers()
+                .get(header::CONTENT_RANGE)
+                .and_then(|v| v.to_str().ok())
+                .and_then(parse_content_range_start);
+            if served != Some(offset) {
+                return Err(DownloaderError::RangeMismatch {
+                    requested: offset,
+                    served,
+                });
+            }
+            let mut file = open_at(output, offset).await?;
+            progress.resume_at(offset);
+            write_body(transfer, &mut response, &mut file, &output_str, progress).await
+        }
+        status if status.is_success() => {
+            let mut file = File::create(output)
                 .await
                 .map_err(|source| DownloaderError::Io {
-                    context: format!("writing output file {output_str}"),
+                    context: format!("creating output file {output_str}"),
                     source,
                 })?;
-            downloaded += bytes.len() as u64;
-            on_event(DownloadEvent::Progress {
-                downloaded_bytes: downloaded,
-                total_bytes: Some(total_size),
-                elapsed: started_at.elapsed(),
-            });
-            next += 1;
+            write_body(transfer, &mut response, &mut file, &output_str, progress).await
         }
+        status => Err(DownloaderError::HttpStatus {
+            context: "resuming download".to_string(),
+            status,
+        }),
     }
+}
 
-    let elapsed = started_at.elapsed();
-    on_event(DownloadEvent::Finished {
-        downloaded_bytes: downloaded,
-        elapsed,
-    });
+/// downloads the whole body over one connection, writing it to `writer` as it arrives.
+async fn stream_body<W, F>(
+    transfer: &Transfer,
+    writer: &mut W,
+    target: &str,
+    progress: &mut Progress<'_, F>,
+) -> Result<usize>
+where
+    W: AsyncWrite + Unpin,
+    F: FnMut(DownloadEvent) + Send,
+{
+    let mut response = transfer
+        .send(
+            transfer
+                .client
+                .get(&transfer.url)
+                .headers(transfer.headers.clone()),
+            || "sending GET request".to_string(),
+        )
+        .await?;
+
+    if !response.status().is_success() {
+        return Err(DownloaderError::HttpStatus {
+            context: "downloading file".to_string(),
+            status: response.status(),
+        });
+    }
+
+    write_body(transfer, &mut response, writer, target, progress).await
+}
+
+/// copies the rest of `response` into `writer`.
+async fn write_body<W, F>(
+    transfer: &Transfer,
+    response: &mut reqwest::Response,
+    writer: &mut W,
+    target: &str,
+    progress: &mut Progress<'_, F>,
+) -> Result<usize>
+where
+    W: AsyncWrite + Unpin,
+    F: FnMut(DownloadEvent) + Send,
+{
+    loop {
+        let maybe_chunk = transfer
+            .next_chunk(response, || "reading response body".to_string())
+            .await?;
+
+        let Some(chunk) = maybe_chunk else {
+            break;
+        };
+        transfer.throttle(chunk.len() as u64).await;
+
+        writer
+            .write_all(&chunk)
+            .await
+            .map_err(|source| DownloaderError::Io {
+                context: format!("writing {target}"),
+                source,
+            })?;
+
+        progress.advance(chunk.len() as u64);
+    }
+
+    Ok(1)
+}
+
+/// parallel download into a non-seekable writer: segments are fetched concurrently but
+/// written in order, with at most `connections` of them held in memory.
+async fn ordered_parallel_download<W, F>(
+    transfer: &Transfer,
+    writer: &mut W,
+    connections: usize,
+    progress: &mut Progress<'_, F>,
+) -> Result<usize>
+where
+    W: AsyncWrite + Unpin,
+    F: FnMut(DownloadEvent) + Send,
+{
+    let total_size = progress.total.unwrap_or(0);
+    if total_size == 0 {
+        return stream_body(transfer, writer, "output", progress).await;
+    }
+
+    let segments = total_size.div_ceil(ORDERED_SEGMENT_SIZE);
+    let connections_used = (connections.max(1) as u64).min(segments) as usize;
+    let mut in_flight = VecDeque::with_capacity(connections_used);
+    let mut next_segment = 0u64;
+
+    loop {
+        while in_flight.len() < connections_used && next_segment < segments {
+            let start = next_segment * ORDERED_SEGMENT_SIZE;
+            let end = (start + ORDERED_SEGMENT_SIZE).min(total_size) - 1;
+            let idx = next_segment as usize;
+            let transfer = transfer.clone();
+            in_flight.push_back(Worker(tokio::spawn(async move {
+                fetch_segment(&transfer, idx, (start, end)).await
+            })));
+            next_segment += 1;
+        }
+
+        let Some(mut worker) = in_flight.pop_front() else {
+            break;
+        };
+
+        let segment = (&mut worker.0)
+            .await
+            .map_err(|source| DownloaderError::Worker {
+                context: "downloading segment".to_string(),
+                source,
+            })??;
+        // nothing has reached `writer` before the first segment, so the whole body can
+        // still be streamed instead; dropping `in_flight` aborts the other workers.
+        let segment = match segment {
+            Some(segment) => segment,
+            None if progress.downloaded == 0 => {
+                drop(in_flight);
+                return stream_body(transfer, writer, "output", progress).await;
+            }
+            None => {
+                return Err(DownloaderError::HttpStatus {
+                    context: "downloading segment".to_string(),
+                    status: StatusCode::OK,
+                });
+            }
+        };
+
+        writer
+            .write_all(&segment)
+            .await
+            .map_err(|source| DownloaderError::Io {
+                context: "writing output".to_string(),
+                source,
+            })?;
+        progress.advance(segment.len() as u64);
+    }
+
+    Ok(connections_used)
+}
+
+/// fetches one byte range fully into memory, or `None` when the server ignored `Range`.
+async fn fetch_segment(
+    transfer: &Transfer,
+    idx: usize,
+    (start, end): (u64, u64),
+) -> Result<Option<Vec<u8>>> {
+    let mut response = transfer
+        .send(
+            transfer
+                .client
+                .get(&transfer.url)
+                .header(header::RANGE, format!("bytes={start}-{end}"))
+                .headers(transfer.headers.clone()),
+            || format!("downloading segment {idx}"),
+        )
+        .await?;
+
+    // a plain `200` means the server ignored `Range` and is sending the whole file.
+    if response.status() == StatusCode::OK
+        && !response.headers().contains_key(header::CONTENT_RANGE)
+    {
+        return Ok(None);
+    }
+
+    if response.status() != StatusCode::PARTIAL_CONTENT {
+        return Err(DownloaderError::HttpStatus {
+            context: format!("downloading segment {idx}"),
+            status: response.status(),
+        });
+    }
+
+    let mut segment = Vec::with_capacity((end - start + 1) as usize);
+    while let Some(bytes) = transfer
+        .next_chunk(&mut response, || format!("reading segment {idx}"))
+        .await?
+    {
+        transfer.throttle(bytes.len() as u64).await;
+        segment.extend_from_slice(&bytes);
+    }
+
+    Ok(Some(segment))
+}
+
+async fn parallel_download<F>(
+    transfer: &Transfer,
+    output: &Path,
+    connections: usize,
+    progress: &mut Progress<'_, F>,
+) -> Result<usize>
+where
+    F: FnMut(DownloadEvent) + Send,
+{
+    let output_str = output.to_string_lossy();
+    let total_size = progress.total.unwrap_or(0);
+    if total_size == 0 {
+        return single_stream_download(transfer, output, progress).await;
+    }
+
+    let file = File::create(output)
+        .await
+        .map_err(|source| DownloaderError::Io {
+            context: format!("creating output file {output_str}"),
+            source,
+        })?;
+    file.set_len(total_size)
+        .await
+        .map_err(|source| DownloaderError::Io {
+            context: format!("preallocating output file {output_str}"),
+            source,
+        })?;
+    drop(file);
+
+    let workers = connections.max(1).min(total_size as usize);
+    let chunk_size = total_size.div_ceil(workers as u64);
+    let (tx, mut rx) = mpsc::channel::<Result<ChunkEvent>>(workers * 4);
+    let mut spawned = Vec::with_capacity(workers);
+
+    for idx in 0..workers {
+        let start = idx as u64 * chunk_size;
+        if start >= total_size {
+            continue;
+        }
+        let end = ((idx as u64 + 1) * chunk_size).min(total_size) - 1;
+        let transfer = transfer.clone();
+        let output = output.to_path_buf();
+        let tx = tx.clone();
+
+        spawned.push(Worker(tokio::spawn(async move {
+            let result = fetch_chunk(&transfer, &output, idx, (start, end), &tx).await;
+            if let Err(err) = result {
+                let _ = tx.send(Err(err)).await;
+            }
+        })));
+    }
+
+    drop(tx);
+
+    // returning early drops `spawned`, which aborts the remaining workers.
+    let mut written = vec![0; workers];
+    progress.resume_point = Some(0);
+    while let Some(msg) = rx.recv().await {
+        match msg? {
+            ChunkEvent::Written(idx, len) => {
+                written[idx] += len;
+                progress.advance(len);
+                progress.resume_point = Some(completed_prefix(&written, chunk_size));
+            }
+            ChunkEvent::RangeIgnored => {
+                drop(spawned);
+                progress.restart();
+                return single_stream_download(transfer, output, progress).await;
+            }
+        }
+    }
+
+    Ok(spawned.len())
+}
+
+/// bytes from the start of a parallel download that are complete, given what each chunk
+/// of `chunk_size` has written so far.
+fn completed_prefix(written: &[u64], chunk_size: u64) -> u64 {
+    let mut prefix = 0;
+    for &bytes in written {
+        prefix += bytes;
+        if bytes < chunk_size {
+            break;
+        }
+    }
+    prefix
+}
 
-    Ok(DownloadSummary {
-        output: output.to_path_buf(),
-        downloaded_bytes: downloaded,
-        elapsed,
-    })
+/// what a [`fetch_chunk`] worker reports back to [`parallel_download`].
+enum ChunkEvent {
+    /// bytes written at the offset of the chunk with the given index.
+    Written(usize, u64),
+    /// the server answered a ranged request with the whole body, so ranges can't be used.
+    RangeIgnored,
 }
 
+/// streams one byte range straight to its offset in `output`, reporting every write on `tx`.
+///
+/// only one network chunk per worker is held in memory at a time.
 async fn fetch_chunk(
-    client: Client,
-    referer: String,
-    url: String,
+    transfer: &Transfer,
+    output: &Path,
     idx: usize,
-    start: u64,
-    end: u64,
-) -> Result<(usize, Vec<u8>)> {
+    (start, end): (u64, u64),
+    tx: &mpsc::Sender<Result<ChunkEvent>>,
+) -> Result<()> {
     let range = format!("bytes={start}-{end}");
-    let response = client
-        .get(&url)
-        .header(header::RANGE, range)
-        .header(header::REFERER, referer)
-        .send()
-        .await
-        .map_err(|source| DownloaderError::Request {
-            context: format!("downloading chunk {idx}"),
-            source,
-        })?;
+    let mut response = transfer
+        .send(
+            transfer
+                .client
+                .get(&transfer.url)
+                .header(header::RANGE, range)
+                .headers(transfer.headers.clone()),
+            || format!("downloading chunk {idx}"),
+        )
+        .await?;
+
+    // a plain `200` means the server ignored `Range` and is sending the whole file.
+    if response.status() == StatusCode::OK
+        && !response.headers().contains_key(header::CONTENT_RANGE)
+    {
+        let _ = tx.send(Ok(ChunkEvent::RangeIgnored)).await;
+        return Ok(());
+    }
 
     if response.status() != StatusCode::PARTIAL_CONTENT && !response.status().is_success() {
         return Err(DownloaderError::HttpStatus {
@@ -412,15 +1519,47 @@ async fn fetch_chunk(
         });
     }
 
-    let bytes = response
-        .bytes()
+    let mut file = open_at(output, start).await?;
+    while let Some(bytes) = transfer
+        .next_chunk(&mut response, || format!("reading chunk {idx}"))
+        .await?
+    {
+        transfer.throttle(bytes.len() as u64).await;
+        file.write_all(&bytes)
+            .await
+            .map_err(|source| DownloaderError::Io {
+                context: format!("writing chunk {idx} to {}", output.display()),
+                source,
+            })?;
+        let _ = tx
+            .send(Ok(ChunkEvent::Written(idx, bytes.len() as u64)))
+            .await;
+    }
+
+    file.flush().await.map_err(|source| DownloaderError::Io {
+        context: format!("flushing chunk {idx} to {}", output.display()),
+        source,
+    })
+}
+
+/// opens an existing `output` for writing at `offset` without truncating it.
+async fn open_at(output: &Path, offset: u64) -> Result<File> {
+    let mut file = tokio::fs::OpenOptions::new()
+        .write(true)
+        .open(output)
         .await
-        .map_err(|source| DownloaderError::Request {
-            context: format!("reading chunk {idx}"),
+        .map_err(|source| DownloaderError::Io {
+            context: format!("opening output file {}", output.display()),
+            source,
+        })?;
+    file.seek(SeekFrom::Start(offset))
+        .await
+        .map_err(|source| DownloaderError::Io {
+            context: format!("seeking output file {} to {offset}", output.display()),
             source,
         })?;
 
-    Ok((idx, bytes.to_vec()))
+    Ok(file)
 }
 
 async fn ensure_parent_dir(output: &Path) -> Result<()> {
@@ -442,7 +1581,145 @@ async fn ensure_parent_dir(output: &Path) -> Result<()> {
 
 #[cfg(test)]
 mod tests {
-    use super::{filename_from_url, parse_content_disposition_filename};
+    use std::path::Path;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    use reqwest::header::{self, HeaderName, HeaderValue};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    use super::{
+        DownloadEvent, DownloadRequest, DownloaderError, ORDERED_SEGMENT_SIZE, Progress,
+        RateLimiter, auto_connections, completed_prefix, download, download_to_writer,
+        filename_from_url, open_at, parse_content_disposition_filename, parse_content_range_start,
+        parse_content_range_total, partial_path, sanitize_filename, sha256_file, validator_path,
+        within,
+    };
+
+    /// how a [`TestServer`] answers GETs carrying a `Range` header.
+    #[derive(Clone, Copy, PartialEq)]
+    enum Ranges {
+        /// no `accept-ranges`; every GET gets the whole body.
+        Unsupported,
+        /// advertises ranges but answers every GET with the whole body and `200`.
+        Ignored,
+        /// answers `bytes=N-` with the tail from `N` and `bytes=N-M` with that slice.
+        Honored,
+        /// answers every ranged GET with the range starting at byte 0.
+        FromStart,
+        /// sends one byte of a `206` and then waits for the client to hang up.
+        Hang,
+    }
+
+    /// a scripted http/1.1 server for one file, closing every connection after one response.
+    ///
+    /// HEAD advertises `advertised` bytes, plus the etag `"v1"` when ranges are honored in
+    /// some form. the lowercased head of every request is kept in `requests`, and `closed`
+    /// counts the connections a [`Ranges::Hang`] server saw the client hang up.
+    struct TestServer {
+        url: String,
+        requests: Arc<Mutex<Vec<String>>>,
+        closed: Arc<AtomicUsize>,
+    }
+
+    impl TestServer {
+        async fn start(body: &'static [u8], ranges: Ranges) -> Self {
+            Self::start_advertising(body, body.len(), ranges).await
+        }
+
+        async fn start_advertising(body: &'static [u8], advertised: usize, ranges: Ranges) -> Self {
+            let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let addr = listener.local_addr().unwrap();
+            let server = Self {
+                url: format!("http://{addr}/episode.mp4"),
+                requests: Arc::default(),
+                closed: Arc::default(),
+            };
+            let (requests, closed) = (Arc::clone(&server.requests), Arc::clone(&server.closed));
+            tokio::spawn(async move {
+                loop {
+                    let (socket, _) = listener.accept().await.unwrap();
+                    let (requests, closed) = (Arc::clone(&requests), Arc::clone(&closed));
+                    tokio::spawn(async move {
+                        Self::answer(socket, body, advertised, ranges, &requests, &closed).await;
+                    });
+                }
+            });
+
+            server
+        }
+
+        async fn answer(
+            mut socket: tokio::net::TcpStream,
+            body: &'static [u8],
+            advertised: usize,
+            ranges: Ranges,
+            requests: &Mutex<Vec<String>>,
+            closed: &AtomicUsize,
+        ) {
+            let mut request = [0u8; 1024];
+            let read = socket.read(&mut request).await.unwrap_or(0);
+            let head = String::from_utf8_lossy(&request[..read]).to_ascii_lowercase();
+            requests.lock().unwrap().push(head.clone());
+
+            let mut headers = String::new();
+            if ranges != Ranges::Unsupported {
+                headers.push_str("accept-ranges: bytes\r\n");
+            }
+            if matches!(ranges, Ranges::Honored | Ranges::FromStart) {
+                headers.push_str("etag: \"v1\"\r\n");
+            }
+            if head.starts_with("head") {
+                let response = format!(
+                    "HTTP/1.1 200 OK\r\ncontent-length: {advertised}\r\n{headers}connection: close\r\n\r\n"
+                );
+                let _ = socket.write_all(response.as_bytes()).await;
+                return;
+            }
+
+            let range = head
+                .split("range: bytes=")
+                .nth(1)
+                .and_then(|range| range.lines().next())
+                .and_then(|range| range.trim().split_once('-'))
+                .and_then(|(start, end)| {
+                    let end = end
+                        .parse::<usize>()
+                        .map_or(body.len() - 1, |end| end.min(body.len() - 1));
+                    Some((start.parse::<usize>().ok()?, end))
+                });
+            let (status, served) = match (ranges, range) {
+                (Ranges::Hang, Some(_)) => {
+                    let _ = socket
+                        .write_all(b"HTTP/1.1 206 Partial Content\r\ncontent-length: 5\r\n\r\nx")
+                        .await;
+                    while socket.read(&mut request).await.is_ok_and(|read| read > 0) {}
+                    closed.fetch_add(1, Ordering::SeqCst);
+                    return;
+                }
+                (Ranges::Honored, Some((start, end))) => ("206 Partial Content", start..=end),
+                (Ranges::FromStart, Some((_, end))) => ("206 Partial Content", 0..=end),
+                _ => ("200 OK", 0..=body.len() - 1),
+            };
+            if status != "200 OK" {
+                headers.push_str(&format!(
+                    "content-range: bytes {}-{}/{}\r\n",
+                    served.start(),
+                    served.end(),
+                    body.len()
+                ));
+            }
+            let served = &body[served];
+            let mut response = format!(
+                "HTTP/1.1 {status}\r\ncontent-length: {}\r\n{headers}connection: close\r\n\r\n",
+                served.len()
+            )
+            .into_bytes();
+            response.extend_from_slice(served);
+            let _ = socket.write_all(&response).await;
+        }
+    }
 
     #[test]
     fn parses_quoted_filename() {
@@ -462,6 +1739,54 @@ mod tests {
         );
     }
 
+    #[test]
+    fn parses_language_tagged_extended_filename() {
+        let value = "attachment; filename=\"fallback.mp4\"; filename*=utf-8'en'Frieren%20%E2%80%93%2001.mp4";
+        assert_eq!(
+            parse_content_disposition_filename(value).as_deref(),
+            Some("Frieren – 01.mp4")
+        );
+        assert_eq!(
+            parse_content_disposition_filename("attachment; filename*=ISO-8859-1''caf%E9.mp4")
+                .as_deref(),
+            Some("café.mp4")
+        );
+    }
+
+    #[test]
+    fn parses_quoted_filename_with_semicolon_and_escapes() {
+        let value = r#"attachment; filename="Part 1; \"Begin\".mkv"; size=42"#;
+        assert_eq!(
+            parse_content_disposition_filename(value).as_deref(),
+            Some(r#"Part 1; "Begin".mkv"#)
+        );
+    }
+
+    #[test]
+    fn sanitize_filename_replaces_reserved_characters() {
+        assert_eq!(sanitize_filename("Re:Zero - 01?.mp4"), "Re_Zero - 01_.mp4");
+        assert_eq!(
+            sanitize_filename("葬送のフリーレン 01.mp4"),
+            "葬送のフリーレン 01.mp4"
+        );
+    }
+
+    #[test]
+    fn sanitize_filename_stays_inside_the_output_dir() {
+        assert_eq!(sanitize_filename("../evil"), "evil");
+        assert_eq!(sanitize_filename("..\\..\\season/01.mp4"), "season_01.mp4");
+        assert_eq!(sanitize_filename(".."), "download.bin");
+    }
+
+    #[test]
+    fn parses_content_range_total() {
+        assert_eq!(
+            parse_content_range_total("bytes 0-0/734003200"),
+            Some(734003200)
+        );
+        assert_eq!(parse_content_range_total("bytes 0-0/*"), None);
+    }
+
     #[test]
     fn gets_filename_from_url_path() {
         assert_eq!(
@@ -469,4 +1794,607 @@ mod tests {
             "file-01.mp4"
         );
     }
+
+    #[test]
+    fn progress_reports_cumulative_bytes_then_finishes() {
+        let mut events = Vec::new();
+        let mut on_event = |event| events.push(event);
+        let mut progress = Progress::new(&mut on_event, Some(10), Duration::ZERO);
+        progress.advance(4);
+        progress.advance(6);
+        let summary = progress.finish(Path::new("out.mp4"), 1);
+
+        assert_eq!(summary.stats.bytes, 10);
+        assert!(matches!(
+            events[1],
+            DownloadEvent::Progress {
+                downloaded_bytes: 10,
+                total_bytes: Some(10),
+                ..
+            }
+        ));
+        assert!(matches!(
+            events[2],
+            DownloadEvent::Finished {
+                downloaded_bytes: 10,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn progress_throttles_events_but_always_finishes() {
+        let mut events = Vec::new();
+        let mut on_event = |event| events.push(event);
+        let mut progress = Progress::new(&mut on_event, Some(100), Duration::from_secs(60));
+        for _ in 0..10 {
+            progress.advance(5);
+        }
+        progress.finish(Path::new("out.mp4"), 1);
+
+        assert_eq!(events.len(), 2);
+        assert!(matches!(
+            events[0],
+            DownloadEvent::Progress {
+                downloaded_bytes: 5,
+                ..
+            }
+        ));
+        assert!(matches!(
+            events[1],
+            DownloadEvent::Finished {
+                downloaded_bytes: 50,
+                ..
+            }
+        ));
+    }
+
+    #[tokio::test]
+    async fn out_of_order_chunks_land_at_their_offsets() {
+        let output = std::env::temp_dir().join(format!(
+            "pahe-downloader-offsets-{}.bin",
+            std::process::id()
+        ));
+        let file = tokio::fs::File::create(&output).await.unwrap();
+        file.set_len(8).await.unwrap();
+        drop(file);
+
+        let mut second = open_at(&output, 4).await.unwrap();
+        second.write_all(b"5678").await.unwrap();
+        second.flush().await.unwrap();
+        let mut first = open_at(&output, 0).await.unwrap();
+        first.write_all(b"1234").await.unwrap();
+        first.flush().await.unwrap();
+
+        let written = tokio::fs::read(&output).await.unwrap();
+        let _ = tokio::fs::remove_file(&output).await;
+        assert_eq!(written, b"12345678");
+    }
+
+    #[tokio::test]
+    async fn rate_limiter_paces_reads_to_the_cap() {
+        let limiter = RateLimiter::new(10_000);
+        let started = std::time::Instant::now();
+        for _ in 0..4 {
+            limiter.throttle(500).await;
+        }
+
+        assert!(started.elapsed() >= Duration::from_millis(180));
+    }
+
+    #[tokio::test]
+    async fn within_reports_stalled_futures_as_timeouts() {
+        let result = within(
+            Duration::from_millis(20),
+            || "reading chunk 0".to_string(),
+            std::future::pending::<()>(),
+        )
+        .await;
+
+        match result {
+            Err(DownloaderError::Timeout { context, after }) => {
+                assert_eq!(context, "reading chunk 0");
+                assert_eq!(after, Duration::from_millis(20));
+            }
+            other => panic!("unexpected result: {other:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn download_errors_when_body_is_shorter_than_advertised() {
+        let url = TestServer::start_advertising(b"hello", 10, Ranges::Unsupported)
+            .await
+            .url;
+        let output =
+            std::env::temp_dir().join(format!("pahe-downloader-short-{}.bin", std::process::id()));
+
+        let result = download(
+            DownloadRequest::new("", url, output.clone()).connections(1),
+            |_| {},
+        )
+        .await;
+        let _ = tokio::fs::remove_file(&output).await;
+
+        match result {
+            Err(DownloaderError::SizeMismatch { expected, actual }) => {
+                assert_eq!(expected, 10);
+                assert_eq!(actual, 5);
+            }
+            other => panic!("unexpected result: {other:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn failed_download_leaves_no_file_behind() {
+        let url = TestServer::start_advertising(b"hello", 10, Ranges::Unsupported)
+            .await
+            .url;
+        let output =
+            std::env::temp_dir().join(format!("pahe-downloader-failed-{}.bin", std::process::id()));
+
+        let result = download(
+            DownloadRequest::new("", url, output.clone()).connections(1),
+            |_| {},
+        )
+        .await;
+
+        assert!(result.is_err());
+        assert!(!output.exists());
+        assert!(!partial_path(&output).exists());
+    }
+
+    #[tokio::test]
+    async fn sha256_file_hashes_known_input() {
+        let path =
+            std::env::temp_dir().join(format!("pahe-downloader-sha-{}.bin", std::process::id()));
+        tokio::fs::write(&path, b"abc").await.unwrap();
+
+        let digest = sha256_file(&path).await.unwrap();
+        let _ = tokio::fs::remove_file(&path).await;
+
+        assert_eq!(
+            digest,
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+
+    #[tokio::test]
+    async fn download_rejects_wrong_checksum() {
+        let url = TestServer::start_advertising(b"hello", 5, Ranges::Unsupported)
+            .await
+            .url;
+        let output = std::env::temp_dir().join(format!(
+            "pahe-downloader-checksum-{}.bin",
+            std::process::id()
+        ));
+
+        let result = download(
+            DownloadRequest::new("", url, output.clone())
+                .connections(1)
+                .expected_sha256("00"),
+            |_| {},
+        )
+        .await;
+
+        assert!(matches!(
+            result,
+            Err(DownloaderError::ChecksumMismatch { ref expected, .. }) if expected == "00"
+        ));
+        assert!(!output.exists());
+    }
+
+    #[tokio::test]
+    async fn download_reports_stats_for_the_served_body() {
+        let url = TestServer::start_advertising(b"hello", 5, Ranges::Unsupported)
+            .await
+            .url;
+        let output =
+            std::env::temp_dir().join(format!("pahe-downloader-stats-{}.bin", std::process::id()));
+
+        let summary = download(
+            DownloadRequest::new("", url, output.clone()).connections(1),
+            |_| {},
+        )
+        .await
+        .expect("download should succeed");
+        let _ = tokio::fs::remove_file(&output).await;
+
+        assert_eq!(summary.stats.bytes, 5);
+        assert_eq!(summary.downloaded_bytes, 5);
+        assert_eq!(summary.elapsed, summary.stats.elapsed);
+        assert_eq!(summary.stats.connections_used, 1);
+        assert!(summary.stats.average_bps >= 0.0);
+    }
+
+    #[tokio::test]
+    async fn download_reports_progress_through_events() {
+        let url = TestServer::start_advertising(b"hello", 5, Ranges::Unsupported)
+            .await
+            .url;
+        let output =
+            std::env::temp_dir().join(format!("pahe-downloader-events-{}.bin", std::process::id()));
+
+        let mut events = Vec::new();
+        download(
+            DownloadRequest::new("", url, output.clone()).connections(1),
+            |event| events.push(event),
+        )
+        .await
+        .expect("download should succeed");
+        let _ = tokio::fs::remove_file(&output).await;
+
+        assert!(matches!(
+            events.first(),
+            Some(DownloadEvent::Started {
+                total_bytes: Some(5),
+                connections: 1,
+                ..
+            })
+        ));
+        assert!(matches!(
+            events.last(),
+            Some(DownloadEvent::Finished {
+                downloaded_bytes: 5,
+                ..
+            })
+        ));
+        let progress: Vec<u64> = events
+            .iter()
+            .filter_map(|event| match event {
+                DownloadEvent::Progress {
+                    downloaded_bytes, ..
+                } => Some(*downloaded_bytes),
+                _ => None,
+            })
+            .collect();
+        assert!(progress.windows(2).all(|pair| pair[0] <= pair[1]));
+        assert!(progress.iter().all(|&bytes| bytes <= 5));
+    }
+
+    #[tokio::test]
+    async fn extra_headers_are_sent_with_every_request() {
+        let TestServer { url, requests, .. } =
+            TestServer::start(b"hello", Ranges::Unsupported).await;
+        let output = std::env::temp_dir().join(format!(
+            "pahe-downloader-headers-{}.bin",
+            std::process::id()
+        ));
+
+        download(
+            DownloadRequest::new("https://kwik.si/f/abc", url, output.clone())
+                .header(header::ORIGIN, HeaderValue::from_static("https://kwik.si"))
+                .header(
+                    HeaderName::from_static("x-pahe-test"),
+                    HeaderValue::from_static("1"),
+                ),
+            |_| {},
+        )
+        .await
+        .expect("download should succeed");
+        let _ = tokio::fs::remove_file(&output).await;
+
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests.len(), 2);
+        assert!(requests[0].starts_with("head "));
+        for request in requests.iter() {
+            assert!(request.contains("referer: https://kwik.si/f/abc\r\n"));
+            assert!(request.contains("origin: https://kwik.si\r\n"));
+            assert!(request.contains("x-pahe-test: 1\r\n"));
+        }
+    }
+
+    #[tokio::test]
+    async fn download_to_writer_streams_into_a_sink() {
+        let url = TestServer::start_advertising(b"hello", 5, Ranges::Unsupported)
+            .await
+            .url;
+        let mut sink = Vec::new();
+
+        let stats = download_to_writer(
+            DownloadRequest::new("", url, "unused".into()).connections(1),
+            &mut sink,
+            |_| {},
+        )
+        .await
+        .expect("download should succeed");
+
+        assert_eq!(sink, b"hello");
+        assert_eq!(stats.bytes, 5);
+    }
+
+    #[tokio::test]
+    async fn download_to_writer_checks_the_streamed_bytes_against_the_digest() {
+        let hello = "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824";
+        let url = TestServer::start_advertising(b"hello", 5, Ranges::Unsupported)
+            .await
+            .url;
+        let mut sink = Vec::new();
+        download_to_writer(
+            DownloadRequest::new("", url, "unused".into()).expected_sha256(hello),
+            &mut sink,
+            |_| {},
+        )
+        .await
+        .expect("matching digest should pass");
+
+        let url = TestServer::start_advertising(b"hello", 5, Ranges::Unsupported)
+            .await
+            .url;
+        let err = download_to_writer(
+            DownloadRequest::new("", url, "unused".into()).expected_sha256("00"),
+            &mut Vec::new(),
+            |_| {},
+        )
+        .await
+        .expect_err("a different digest should fail");
+        assert!(matches!(err, DownloaderError::ChecksumMismatch { .. }));
+    }
+
+    #[tokio::test]
+    async fn cancelled_download_stops_workers_and_keeps_partial_file() {
+        let TestServer { url, closed, .. } = TestServer::start(b"0123456789", Ranges::Hang).await;
+        let output =
+            std::env::temp_dir().join(format!("pahe-downloader-cancel-{}.bin", std::process::id()));
+        let cancel = tokio_util::sync::CancellationToken::new();
+        let trigger = cancel.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(200)).await;
+            trigger.cancel();
+        });
+
+        let result = download(
+            DownloadRequest::new("", url, output.clone())
+                .connections(2)
+                .cancellation(cancel),
+            |_| {},
+        )
+        .await;
+
+        assert!(matches!(result, Err(DownloaderError::Cancelled)));
+        let partial = partial_path(&output);
+        assert!(partial.exists());
+        let _ = tokio::fs::remove_file(&partial).await;
+
+        let deadline = std::time::Instant::now() + Duration::from_secs(2);
+        while closed.load(Ordering::SeqCst) < 2 && std::time::Instant::now() < deadline {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        assert_eq!(closed.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn auto_connections_scales_with_size() {
+        const MIB: u64 = 1024 * 1024;
+
+        assert_eq!(auto_connections(0), 1);
+        assert_eq!(auto_connections(3 * MIB), 1);
+        assert_eq!(auto_connections(20 * MIB), 1);
+        assert_eq!(auto_connections(21 * MIB), 2);
+        assert_eq!(auto_connections(200 * MIB), 10);
+        assert_eq!(auto_connections(2048 * MIB), 16);
+        assert_eq!(auto_connections(u64::MAX), 16);
+    }
+
+    #[tokio::test]
+    async fn ignored_ranges_fall_back_to_a_single_stream() {
+        let url = TestServer::start(b"hello world", Ranges::Ignored).await.url;
+        let output = std::env::temp_dir().join(format!(
+            "pahe-downloader-no-ranges-{}.bin",
+            std::process::id()
+        ));
+
+        let summary = download(
+            DownloadRequest::new("", url, output.clone()).connections(4),
+            |_| {},
+        )
+        .await
+        .expect("download should succeed");
+
+        let written = tokio::fs::read(&output).await.unwrap();
+        let _ = tokio::fs::remove_file(&output).await;
+        assert_eq!(written, b"hello world");
+        assert_eq!(summary.stats.bytes, 11);
+        assert_eq!(summary.stats.connections_used, 1);
+    }
+
+    #[tokio::test]
+    async fn ordered_download_streams_the_body_once_when_ranges_are_ignored() {
+        // two segments, so the ordered path is taken rather than a single stream
+        let body: &'static [u8] = vec![7; ORDERED_SEGMENT_SIZE as usize + 5].leak();
+        let url = TestServer::start(body, Ranges::Ignored).await.url;
+        let mut sink = Vec::new();
+
+        let stats = download_to_writer(
+            DownloadRequest::new("", url, "unused".into()).connections(4),
+            &mut sink,
+            |_| {},
+        )
+        .await
+        .expect("download should succeed");
+
+        assert_eq!(sink, body);
+        assert_eq!(stats.connections_used, 1);
+    }
+
+    #[tokio::test]
+    async fn parallel_download_with_a_short_last_chunk_keeps_the_served_size() {
+        let url = TestServer::start(b"0123456789", Ranges::Honored).await.url;
+        let output = std::env::temp_dir().join(format!(
+            "pahe-downloader-prealloc-{}.bin",
+            std::process::id()
+        ));
+
+        let summary = download(
+            DownloadRequest::new("", url, output.clone()).connections(3),
+            |_| {},
+        )
+        .await
+        .expect("download should succeed");
+
+        let len = tokio::fs::metadata(&output).await.unwrap().len();
+        let written = tokio::fs::read(&output).await.unwrap();
+        let _ = tokio::fs::remove_file(&output).await;
+        assert_eq!(summary.stats.connections_used, 3);
+        assert_eq!(len, 10);
+        assert_eq!(written, b"0123456789");
+    }
+
+    #[tokio::test]
+    async fn partial_file_is_resumed_from_its_length() {
+        let url = TestServer::start(b"hello world", Ranges::Honored).await.url;
+        let output =
+            std::env::temp_dir().join(format!("pahe-downloader-resume-{}.bin", std::process::id()));
+        tokio::fs::write(partial_path(&output), b"hello")
+            .await
+            .unwrap();
+        tokio::fs::write(validator_path(&output), "\"v1\"")
+            .await
+            .unwrap();
+
+        let mut events = Vec::new();
+        let summary = download(
+            DownloadRequest::new("", url, output.clone()).connections(4),
+            |event| events.push(event),
+        )
+        .await
+        .expect("download should resume");
+
+        let written = tokio::fs::read(&output).await.unwrap();
+        let _ = tokio::fs::remove_file(&output).await;
+        assert_eq!(written, b"hello world");
+        assert_eq!(summary.stats.bytes, 11);
+        assert_eq!(summary.stats.connections_used, 1);
+        assert!(matches!(
+            events[0],
+            DownloadEvent::Started { connections: 1, .. }
+        ));
+    }
+
+    #[tokio::test]
+    async fn partial_file_is_discarded_when_the_validator_changed() {
+        let url = TestServer::start(b"hello world", Ranges::Honored).await.url;
+        let output = std::env::temp_dir().join(format!(
+            "pahe-downloader-resume-stale-{}.bin",
+            std::process::id()
+        ));
+        tokio::fs::write(partial_path(&output), b"HELLO")
+            .await
+            .unwrap();
+        tokio::fs::write(validator_path(&output), "\"v0\"")
+            .await
+            .unwrap();
+
+        let mut events = Vec::new();
+        let summary = download(
+            DownloadRequest::new("", url, output.clone()).connections(2),
+            |event| events.push(event),
+        )
+        .await
+        .expect("download should start over");
+
+        let written = tokio::fs::read(&output).await.unwrap();
+        let _ = tokio::fs::remove_file(&output).await;
+        assert_eq!(written, b"hello world");
+        assert_eq!(summary.stats.connections_used, 2);
+        assert!(matches!(
+            events[0],
+            DownloadEvent::Started { connections: 2, .. }
+        ));
+        assert!(!validator_path(&output).exists());
+    }
+
+    #[tokio::test]
+    async fn partial_file_is_rewritten_when_ranges_are_ignored() {
+        let url = TestServer::start(b"hello world", Ranges::Ignored).await.url;
+        let output = std::env::temp_dir().join(format!(
+            "pahe-downloader-resume-ignored-{}.bin",
+            std::process::id()
+        ));
+        tokio::fs::write(partial_path(&output), b"HELLO")
+            .await
+            .unwrap();
+
+        download(DownloadRequest::new("", url, output.clone()), |_| {})
+            .await
+            .expect("download should start over");
+
+        let written = tokio::fs::read(&output).await.unwrap();
+        let _ = tokio::fs::remove_file(&output).await;
+        assert_eq!(written, b"hello world");
+    }
+
+    #[tokio::test]
+    async fn presized_partial_file_resumes_from_the_recorded_offset() {
+        let url = TestServer::start(b"hello world", Ranges::Honored).await.url;
+        let output = std::env::temp_dir().join(format!(
+            "pahe-downloader-resume-presized-{}.bin",
+            std::process::id()
+        ));
+        tokio::fs::write(partial_path(&output), b"hello\0\0\0\0\0\0")
+            .await
+            .unwrap();
+        tokio::fs::write(validator_path(&output), "\"v1\"\n5")
+            .await
+            .unwrap();
+
+        let mut events = Vec::new();
+        let summary = download(
+            DownloadRequest::new("", url, output.clone()).connections(4),
+            |event| events.push(event),
+        )
+        .await
+        .expect("download should resume");
+
+        let written = tokio::fs::read(&output).await.unwrap();
+        let _ = tokio::fs::remove_file(&output).await;
+        assert_eq!(written, b"hello world");
+        assert_eq!(summary.stats.bytes, 11);
+        assert!(matches!(
+            events[0],
+            DownloadEvent::Started { connections: 1, .. }
+        ));
+    }
+
+    #[tokio::test]
+    async fn resume_is_rejected_when_the_server_sends_another_range() {
+        let url = TestServer::start(b"hello world", Ranges::FromStart)
+            .await
+            .url;
+        let output = std::env::temp_dir().join(format!(
+            "pahe-downloader-resume-shifted-{}.bin",
+            std::process::id()
+        ));
+        tokio::fs::write(partial_path(&output), b"hello")
+            .await
+            .unwrap();
+        tokio::fs::write(validator_path(&output), "\"v1\"")
+            .await
+            .unwrap();
+
+        let err = download(DownloadRequest::new("", url, output.clone()), |_| {})
+            .await
+            .expect_err("a range from another offset must not be spliced in");
+
+        assert!(matches!(
+            err,
+            DownloaderError::RangeMismatch {
+                requested: 5,
+                served: Some(0)
+            }
+        ));
+        assert!(!partial_path(&output).exists());
+    }
+
+    #[test]
+    fn completed_prefix_stops_at_the_first_unfinished_chunk() {
+        assert_eq!(completed_prefix(&[0, 4, 4], 4), 0);
+        assert_eq!(completed_prefix(&[4, 2, 4], 4), 6);
+        assert_eq!(completed_prefix(&[4, 4, 3], 4), 11);
+    }
+
+    #[test]
+    fn content_range_start_is_parsed() {
+        assert_eq!(parse_content_range_start("bytes 100-199/1000"), Some(100));
+        assert_eq!(parse_content_range_start("bytes */1000"), None);
+    }
 }
@@ -1,13 +1,53 @@
+use crate::client::RequestTimeouts;
+use crate::cookies::{NetscapeCookie, cookie_header, parse_cookies_txt};
 use crate::prelude::*;
-use tracing::{debug, info};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tracing::{debug, info, warn};
 
 const BASE_DOMAIN: &str = "animepahe.si";
 const REDIRECTOR_DOMAIN: &str = "pahe.win";
+const CLEARANCE_COOKIE_PREFIX: &str = "__ddg";
+
+/// problems detected in a configured cookie header before any request is made.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CookieHeaderIssue {
+    /// no `name=value` pair could be parsed from the header.
+    NoValidPairs,
+    /// pairs were parsed but none of them is a ddos-guard `__ddg*` clearance cookie.
+    MissingClearance,
+}
+
+impl std::fmt::Display for CookieHeaderIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NoValidPairs => write!(
+                f,
+                "no `name=value` cookie pairs found; paste only the cookie header value"
+            ),
+            Self::MissingClearance => write!(
+                f,
+                "no `{CLEARANCE_COOKIE_PREFIX}*` clearance cookies found; ddos-guard will likely block requests"
+            ),
+        }
+    }
+}
 
 pub struct PaheBuilder {
     base_domain: String,
     redirect_domain: String,
     cookies: Option<String>,
+    cookie_pairs: Vec<String>,
+    options: ClientOptions,
+    strict_cookies: bool,
+    proxy: Option<String>,
+    cookies_file: Option<PathBuf>,
+    scheme: String,
+    client: Option<reqwest::Client>,
+    timeout: Option<Duration>,
+    connect_timeout: Option<Duration>,
+    api_retries: u8,
+    min_request_interval: Duration,
 }
 
 impl PaheBuilder {
@@ -17,6 +57,17 @@ impl PaheBuilder {
             base_domain: BASE_DOMAIN.to_string(),
             redirect_domain: REDIRECTOR_DOMAIN.to_string(),
             cookies: None,
+            cookie_pairs: Vec::new(),
+            options: ClientOptions::default(),
+            strict_cookies: false,
+            proxy: None,
+            cookies_file: None,
+            scheme: "https".to_string(),
+            client: None,
+            timeout: None,
+            connect_timeout: None,
+            api_retries: DEFAULT_API_RETRIES,
+            min_request_interval: DEFAULT_MIN_REQUEST_INTERVAL,
         }
     }
 
@@ -26,43 +77,341 @@ impl PaheBuilder {
         self
     }
 
+    /// appends a single `name=value` cookie, merged with any [`PaheBuilder::cookies_str`] header.
+    pub fn cookie(mut self, name: &str, value: &str) -> Self {
+        self.cookie_pairs
+            .push(format!("{}={}", name.trim(), value.trim()));
+        self
+    }
+
+    /// loads cookies from a netscape `cookies.txt` file when the client is built.
+    ///
+    /// entries for the base domain are merged with any other configured cookies; the whole
+    /// file is kept so [`PaheClient::save_cookies`] can write it back.
+    pub fn cookies_file(mut self, path: impl AsRef<Path>) -> Self {
+        self.cookies_file = Some(path.as_ref().to_path_buf());
+        self
+    }
+
+    /// makes [`PaheBuilder::build`] fail instead of warn when the cookie header looks malformed.
+    pub fn strict_cookies(mut self, strict: bool) -> Self {
+        self.strict_cookies = strict;
+        self
+    }
+
+    /// trusts an extra root certificate, e.g. a corporate proxy's CA.
+    pub fn root_certificate(mut self, cert: reqwest::Certificate) -> Self {
+        self.options.root_certificates.push(cert);
+        self
+    }
+
+    /// disables tls certificate verification for every request.
+    ///
+    /// this makes the connection trivially interceptable; prefer [`PaheBuilder::root_certificate`].
+    pub fn danger_accept_invalid_certs(mut self, accept: bool) -> Self {
+        self.options.accept_invalid_certs = accept;
+        self
+    }
+
+    /// routes animepahe and kwik requests through a proxy, e.g. `socks5://127.0.0.1:9050`.
+    ///
+    /// the url is validated by [`PaheBuilder::build`].
+    pub fn proxy(mut self, url: &str) -> Self {
+        self.proxy = Some(url.trim().to_string());
+        self
+    }
+
+    /// overrides the browser user agent sent to animepahe and kwik.
+    pub fn user_agent(mut self, user_agent: &str) -> Self {
+        self.options.user_agent = Some(user_agent.to_string());
+        self
+    }
+
+    /// caps how long an animepahe request may take in total, connecting included.
+    ///
+    /// defaults to [`DEFAULT_REQUEST_TIMEOUT`]; kwik and downloads aren't affected.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// caps how long connecting to animepahe may take, e.g. a hung tls handshake.
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = Some(timeout);
+        self
+    }
+
+    /// sets how many extra attempts a release api request gets after a 5xx or network error;
+    /// `0` fails on the first one. defaults to [`DEFAULT_API_RETRIES`].
+    pub fn api_retries(mut self, retries: u8) -> Self {
+        self.api_retries = retries;
+        self
+    }
+
+    /// spaces animepahe requests at least `interval` apart, so walking a long series doesn't
+    /// trip ddos-guard. defaults to [`DEFAULT_MIN_REQUEST_INTERVAL`]; `Duration::ZERO` turns
+    /// it off.
+    pub fn min_request_interval(mut self, interval: Duration) -> Self {
+        self.min_request_interval = interval;
+        self
+    }
+
+    /// replaces all transport options at once.
+    pub fn client_options(mut self, options: ClientOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// sends every animepahe and kwik request through `client` instead of building new ones.
+    ///
+    /// its connection pool, proxy, timeouts and user agent apply as configured; setting a proxy,
+    /// certificates, timeouts or user agent on this builder as well is an error. configured cookies are
+    /// sent as a `Cookie` header on animepahe requests, taking precedence over the client's own
+    /// cookie store, and cookies refreshed by animepahe aren't seen by
+    /// [`PaheClient::save_cookies`]. kwik needs the client to have a cookie store.
+    pub fn with_client(mut self, client: reqwest::Client) -> Self {
+        self.client = Some(client);
+        self
+    }
+
     /// sets the base domain for the client.
     pub fn base_domain(mut self, domain: &str) -> Self {
         self.base_domain = domain.to_string();
         self
     }
 
-    /// sets the redirect domain for the client.
+    /// points the client at a mirror or local server, e.g. `http://127.0.0.1:8080`.
+    ///
+    /// unlike [`PaheBuilder::base_domain`] this also sets the scheme and keeps the port.
+    pub fn base_url(mut self, url: &str) -> Result<Self> {
+        let url = reqwest::Url::parse(url).map_err(|_| PaheError::AnimepaheBaseUrl)?;
+        let host = url.host_str().ok_or(PaheError::AnimepaheBaseUrl)?;
+        self.base_domain = match url.port() {
+            Some(port) => format!("{host}:{port}"),
+            None => host.to_string(),
+        };
+        self.scheme = url.scheme().to_string();
+        Ok(self)
+    }
+
+    /// sets the domain of the mirror links picked from play pages; defaults to `pahe.win`.
     pub fn redirect_domain(mut self, domain: &str) -> Self {
         self.redirect_domain = domain.to_string();
         self
     }
 
+    fn cookie_header(&self, loaded: &[NetscapeCookie]) -> Option<String> {
+        let mut parts: Vec<&str> = self
+            .cookies
+            .iter()
+            .flat_map(|cookies| cookies.split(';'))
+            .map(str::trim)
+            .filter(|part| !part.is_empty())
+            .collect();
+        parts.extend(self.cookie_pairs.iter().map(String::as_str));
+
+        let loaded = cookie_header(loaded, &self.base_domain);
+        parts.extend(loaded.as_deref());
+
+        if parts.is_empty() {
+            return None;
+        }
+
+        Some(parts.join("; "))
+    }
+
+    fn load_cookies_file(&self) -> Result<Vec<NetscapeCookie>> {
+        let Some(path) = &self.cookies_file else {
+            return Ok(Vec::new());
+        };
+        let text = std::fs::read_to_string(path).map_err(|source| PaheError::CookiesFile {
+            action: "read",
+            path: path.clone(),
+            source,
+        })?;
+        let cookies = parse_cookies_txt(&text);
+        debug!(path = %path.display(), cookies = cookies.len(), "loaded cookies file");
+        Ok(cookies)
+    }
+
+    /// checks the configured cookies without building a client.
+    ///
+    /// returns `None` when no cookies are configured at all. cookies from
+    /// [`PaheBuilder::cookies_file`] are only checked by [`PaheBuilder::build`].
+    pub fn validate_cookies(&self) -> Option<CookieHeaderIssue> {
+        cookie_header_issue(&self.cookie_header(&[])?)
+    }
+
     /// builds a [`PaheClient`] using the configured options.
     pub fn build(&self) -> Result<PaheClient> {
+        let loaded = self.load_cookies_file()?;
+        let cookie_header = self.cookie_header(&loaded);
+
+        if let Some(issue) = cookie_header.as_deref().and_then(cookie_header_issue) {
+            if self.strict_cookies {
+                return Err(PaheError::InvalidCookies { issue });
+            }
+            warn!(%issue, "cookie header looks malformed");
+        }
+
+        let mut options = self.options.clone();
+        if let Some(url) = &self.proxy {
+            options.proxy = Some(parse_proxy(url)?);
+        }
+        if self.client.is_some() {
+            let timeout =
+                (self.timeout.is_some() || self.connect_timeout.is_some()).then_some("timeout");
+            if let Some(setting) = options.builder_only_setting().or(timeout) {
+                return Err(PaheError::InjectedClientSetting { setting });
+            }
+        }
+        let timeouts = RequestTimeouts {
+            total: self.timeout.unwrap_or(DEFAULT_REQUEST_TIMEOUT),
+            connect: self.connect_timeout,
+        };
+
         info!(
             base_domain = %self.base_domain,
             redirect_domain = %self.redirect_domain,
-            has_cookie_header = self.cookies.is_some(),
+            has_cookie_header = cookie_header.is_some(),
+            has_proxy = options.proxy.is_some(),
+            injected_client = self.client.is_some(),
             "building PaheClient"
         );
 
-        if let Some(cookies) = &self.cookies {
+        if cookie_header.is_some() {
             debug!("building client with explicit clearance cookie header");
-            return PaheClient::new_with_clearance_cookie(
-                self.base_domain.clone(),
-                self.redirect_domain.clone(),
-                cookies,
-            );
+        } else {
+            debug!("building client without explicit clearance cookie header");
         }
 
-        debug!("building client without explicit clearance cookie header");
-        PaheClient::new(self.base_domain.clone(), self.redirect_domain.clone())
+        PaheClient::with_options(
+            self.base_domain.clone(),
+            self.redirect_domain.clone(),
+            cookie_header,
+            loaded,
+            options,
+            timeouts,
+            self.client.clone(),
+        )
+        .map(|client| {
+            client
+                .with_scheme(&self.scheme)
+                .with_api_retries(self.api_retries)
+                .with_min_request_interval(self.min_request_interval)
+        })
     }
 }
 
+fn cookie_header_issue(header: &str) -> Option<CookieHeaderIssue> {
+    let names: Vec<&str> = header
+        .split(';')
+        .filter_map(|part| part.split_once('='))
+        .map(|(name, _)| name.trim())
+        .filter(|name| !name.is_empty() && !name.contains(char::is_whitespace))
+        .collect();
+
+    if names.is_empty() {
+        Some(CookieHeaderIssue::NoValidPairs)
+    } else if !names
+        .iter()
+        .any(|name| name.starts_with(CLEARANCE_COOKIE_PREFIX))
+    {
+        Some(CookieHeaderIssue::MissingClearance)
+    } else {
+        None
+    }
+}
+
+/// parses a proxy url for every scheme reqwest supports (`http`, `https`, `socks5`, `socks5h`).
+pub fn parse_proxy(url: &str) -> Result<reqwest::Proxy> {
+    reqwest::Proxy::all(url).map_err(|source| PaheError::InvalidProxy {
+        url: url.to_string(),
+        source,
+    })
+}
+
 impl Default for PaheBuilder {
     fn default() -> Self {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validate_cookies_accepts_clearance_header() {
+        let builder = PaheBuilder::new().cookies_str("__ddg1_=abc; __ddg2_=def; theme=dark");
+
+        assert_eq!(builder.validate_cookies(), None);
+        assert_eq!(PaheBuilder::new().validate_cookies(), None);
+    }
+
+    #[test]
+    fn validate_cookies_flags_pasted_curl_command() {
+        let builder =
+            PaheBuilder::new().cookies_str("curl 'https://animepahe.si/' -H 'accept: */*'");
+
+        assert_eq!(
+            builder.validate_cookies(),
+            Some(CookieHeaderIssue::NoValidPairs)
+        );
+    }
+
+    #[test]
+    fn validate_cookies_flags_missing_clearance() {
+        let builder = PaheBuilder::new().cookie("theme", "dark");
+
+        assert_eq!(
+            builder.validate_cookies(),
+            Some(CookieHeaderIssue::MissingClearance)
+        );
+    }
+
+    #[test]
+    fn proxy_is_stored_and_validated_on_build() {
+        let builder = PaheBuilder::new().proxy(" socks5://127.0.0.1:9050 ");
+        assert_eq!(builder.proxy.as_deref(), Some("socks5://127.0.0.1:9050"));
+        assert!(builder.build().is_ok());
+
+        let result = PaheBuilder::new().proxy("socks5://bad host:9050").build();
+        assert!(matches!(
+            result,
+            Err(PaheError::InvalidProxy { url, .. }) if url == "socks5://bad host:9050"
+        ));
+    }
+
+    #[test]
+    fn injected_client_rejects_builder_transport_settings() {
+        let build = |builder: PaheBuilder| builder.with_client(reqwest::Client::new()).build();
+
+        assert!(build(PaheBuilder::new().cookies_str("__ddg2_=abc")).is_ok());
+        assert!(matches!(
+            build(PaheBuilder::new().proxy("socks5://127.0.0.1:9050")),
+            Err(PaheError::InjectedClientSetting { setting: "proxy" })
+        ));
+        assert!(matches!(
+            build(PaheBuilder::new().user_agent("pahe-test/1.0")),
+            Err(PaheError::InjectedClientSetting {
+                setting: "user agent"
+            })
+        ));
+    }
+
+    #[test]
+    fn strict_build_rejects_malformed_cookies() {
+        let result = PaheBuilder::new()
+            .cookies_str("not a cookie")
+            .strict_cookies(true)
+            .build();
+
+        assert!(matches!(
+            result,
+            Err(PaheError::InvalidCookies {
+                issue: CookieHeaderIssue::NoValidPairs
+            })
+        ));
+    }
+}
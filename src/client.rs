@@ -1,26 +1,101 @@
+use futures::stream::{self, StreamExt};
 use regex::Regex;
-use reqwest::cookie::Jar;
+use reqwest::cookie::{CookieStore, Jar};
 use reqwest::header::{
     ACCEPT, ACCEPT_LANGUAGE, COOKIE, HeaderMap, HeaderValue, ORIGIN, REFERER, USER_AGENT,
 };
 use reqwest::{Client as ReqwestClient, Url};
-use scraper::{Html, Selector};
+use scraper::{ElementRef, Html, Selector};
 use serde::Deserialize;
-use std::sync::Arc;
+use std::path::Path;
+use std::sync::{Arc, LazyLock};
+use std::time::Duration;
+use tokio::time::Instant;
 use tracing::{debug, info};
 
-use pahe_core::{DirectLink, KwikClient, kwik::Stream};
+use pahe_core::kwik::{KwikFile, PaheLink, Stream};
+use pahe_core::{ClientOptions, DirectLink, KwikClient};
 
+use crate::cookies::{NetscapeCookie, write_cookies_txt};
 use crate::errors::{PaheError, Result};
 
-#[derive(Debug, Clone)]
+static VARIANT_SIZE_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?i)(\d+(?:\.\d+)?)\s*(KiB|MiB|GiB|KB|MB|GB)\b").expect("size regex must compile")
+});
+
+/// a `[...]` or `(...)` segment of a variant label.
+static VARIANT_BRACKETED_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"[\[(]\s*([^\[\]()]+?)\s*[\])]").expect("bracket regex must compile")
+});
+
+/// `1920x1080`-style dimensions; the second group is the height.
+static VARIANT_DIMENSIONS_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?:^|\D)\d{3,4}\s*[x×]\s*(\d{3,4})(?:\D|$)")
+        .expect("dimensions regex must compile")
+});
+
+/// a `720p` height glued to surrounding text, as in `SubsPlease·720p(140MB)`.
+static VARIANT_HEIGHT_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?i)(?:^|\D)(\d{3,4})p(?:[^a-z0-9]|$)").expect("height regex must compile")
+});
+
+/// total time an animepahe request may take unless [`crate::builder::PaheBuilder::timeout`]
+/// says otherwise.
+pub const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// extra attempts made when the release api fails with a 5xx or a network error, unless
+/// [`crate::builder::PaheBuilder::api_retries`] says otherwise.
+pub const DEFAULT_API_RETRIES: u8 = 2;
+const API_RETRY_DELAY: Duration = Duration::from_millis(500);
+
+/// least time between two animepahe requests unless
+/// [`crate::builder::PaheBuilder::min_request_interval`] says otherwise.
+pub const DEFAULT_MIN_REQUEST_INTERVAL: Duration = Duration::from_millis(300);
+
+/// lowercase snippets found on ddos-guard and cloudflare challenge pages.
+const CHALLENGE_MARKERS: [&str; 6] = [
+    "ddos-guard",
+    "checking your browser before accessing",
+    "just a moment",
+    "cf-browser-verification",
+    "cf_chl_opt",
+    "__cf_chl",
+];
+
+/// extra attempts made when a play page loads without any download anchors.
+const PLAY_PAGE_RETRIES: usize = 2;
+const PLAY_PAGE_RETRY_DELAY: Duration = Duration::from_millis(750);
+
+/// most release pages walked for one series (30 episodes each), in case the api keeps
+/// answering with data and never reports a last page.
+const MAX_RELEASE_PAGES: i32 = 500;
+
+/// series metadata scraped from an animepahe anime page.
+#[derive(Debug, Clone, Default)]
 pub struct Anime {
     pub id: String,
     pub title: Option<String>,
+    pub synopsis: Option<String>,
+    /// full size poster image url.
+    pub poster: Option<String>,
+    /// airing status such as `Finished Airing`.
+    pub status: Option<String>,
+    /// release format such as `TV` or `Movie`.
+    pub kind: Option<String>,
+    /// airing season such as `Fall 2023`.
+    pub season: Option<String>,
+    pub year: Option<u32>,
+    pub genres: Vec<String>,
+    /// episode count from the info sidebar; `None` while it is still unknown.
+    pub episodes: Option<u32>,
 }
 
 /// download variant metadata parsed from a single animepahe play page.
+///
+/// built by the play page parser or [`EpisodeVariant::from_anchor_html`], so new fields
+/// can be added without breaking callers.
 #[derive(Debug, Clone)]
+#[non_exhaustive]
 pub struct EpisodeVariant {
     /// mirror link hosted on `pahe.win` that can be resolved into a direct file url.
     pub dpahe_link: String,
@@ -32,6 +107,297 @@ pub struct EpisodeVariant {
     pub lang: String,
     /// bluray encoded.
     pub bluray: bool,
+    /// file size declared in the label, in bytes; `None` when the label has none.
+    pub size_bytes: Option<u64>,
+    /// fansub or release group named in the label, e.g. `SubsPlease`.
+    pub group: Option<String>,
+}
+
+impl EpisodeVariant {
+    /// reads a variant from the inner html of its play page anchor, e.g.
+    /// `SubsPlease &middot; 1080p (1.2GB) <span class="badge">BD</span>`.
+    pub fn from_anchor_html(dpahe_link: impl Into<String>, html: &str) -> Self {
+        let fragment = Html::parse_fragment(html);
+        Self::from_anchor(dpahe_link.into(), fragment.root_element())
+    }
+
+    /// reads the label, badges and markup of a download anchor.
+    fn from_anchor(dpahe_link: String, anchor: ElementRef<'_>) -> Self {
+        let span_sel = Selector::parse("span").unwrap();
+
+        let block = anchor.inner_html();
+        let full_text = anchor.text().collect::<Vec<_>>().join(" ").to_lowercase();
+        // the label without its badge spans
+        let label = anchor
+            .children()
+            .filter_map(|node| node.value().as_text().map(|text| &**text))
+            .collect::<String>();
+
+        // resolution, from the markup when the visible text has none
+        let resolution = match parse_resolution(&full_text) {
+            0 => parse_resolution(&block),
+            resolution => resolution,
+        };
+
+        // audio language
+        let mut lang = "jp".to_string();
+
+        let mut bluray = false;
+
+        for span in anchor.select(&span_sel) {
+            let content = span.text().collect::<String>().trim().to_lowercase();
+            match content.as_str() {
+                "bd" => {
+                    bluray = true;
+                }
+                "eng" => {
+                    lang = "en".to_string();
+                    break;
+                }
+                "chi" => {
+                    lang = "zh".to_string();
+                    break;
+                }
+                _ => {}
+            }
+        }
+
+        // dubbed releases don't always carry the `eng` badge
+        if lang == "jp"
+            && full_text
+                .split(|c: char| !c.is_alphanumeric())
+                .any(|w| w == "dub")
+        {
+            lang = "en".to_string();
+        }
+
+        Self {
+            dpahe_link,
+            size_bytes: parse_size_bytes(&full_text),
+            source_text: block,
+            resolution,
+            lang,
+            bluray,
+            group: parse_group(&label),
+        }
+    }
+}
+
+#[cfg(any(test, feature = "test-util"))]
+impl EpisodeVariant {
+    /// a `SubsPlease` variant in `lang` (`jp` or `en`) labelled with `resolution` and `size`,
+    /// linked as `https://pahe.win/{resolution}-{lang}-{size}`.
+    pub fn fixture(resolution: i32, lang: &str, size: &str) -> Self {
+        let badge = if lang == "en" {
+            " <span>eng</span>"
+        } else {
+            ""
+        };
+        Self::from_anchor_html(
+            format!("https://pahe.win/{resolution}-{lang}-{size}"),
+            &format!("SubsPlease &middot; {resolution}p ({size}){badge}"),
+        )
+    }
+}
+
+/// scrapes title, synopsis, poster, the info sidebar and genres from an anime page.
+fn parse_anime_page(id: String, text: &str) -> Anime {
+    let doc = Html::parse_document(text);
+    let select = |selector: &str| Selector::parse(selector).expect("invalid selector");
+    let collapse = |text: &str| text.split_whitespace().collect::<Vec<_>>().join(" ");
+
+    let title = doc
+        .select(&select(".title-wrapper h1 span"))
+        .next()
+        .and_then(|span| span.text().next().map(String::from));
+
+    // keep the synopsis' `<br>` paragraph breaks as newlines
+    let synopsis = doc.select(&select(".anime-synopsis")).next().map(|el| {
+        let mut raw = String::new();
+        for child in el.children() {
+            match child.value() {
+                scraper::Node::Text(text) => raw.push_str(text),
+                scraper::Node::Element(element) if element.name() == "br" => raw.push('\n'),
+                _ => {
+                    if let Some(element) = scraper::ElementRef::wrap(child) {
+                        raw.extend(element.text());
+                    }
+                }
+            }
+        }
+        raw.lines()
+            .map(collapse)
+            .filter(|line| !line.is_empty())
+            .collect::<Vec<_>>()
+            .join("\n")
+    });
+
+    let poster = doc
+        .select(&select(".anime-poster a"))
+        .next()
+        .and_then(|a| a.value().attr("href"))
+        .or_else(|| {
+            doc.select(&select(".anime-poster img"))
+                .next()
+                .and_then(|img| img.value().attr("data-src"))
+        })
+        .map(String::from);
+
+    // sidebar rows look like `<p><strong>Type:</strong> <a>TV</a></p>`
+    let mut info = std::collections::HashMap::new();
+    for row in doc.select(&select(".anime-info p")) {
+        let row = collapse(&row.text().collect::<Vec<_>>().join(" "));
+        if let Some((key, value)) = row.split_once(':') {
+            info.insert(key.trim().to_ascii_lowercase(), value.trim().to_string());
+        }
+    }
+    let mut field = |key: &str| info.remove(key).filter(|value| !value.is_empty());
+
+    let kind = field("type");
+    let status = field("status");
+    let season = field("season");
+    let aired = field("aired");
+    let episodes = field("episodes").and_then(|count| count.parse().ok());
+    let year = season
+        .iter()
+        .chain(aired.iter())
+        .flat_map(|text| text.split(|c: char| !c.is_ascii_digit()))
+        .find(|digits| digits.len() == 4)
+        .and_then(|digits| digits.parse().ok());
+
+    let genres = doc
+        .select(&select(".anime-genre li a"))
+        .map(|a| collapse(&a.text().collect::<String>()))
+        .filter(|genre| !genre.is_empty())
+        .collect();
+
+    Anime {
+        id,
+        title,
+        synopsis: synopsis.filter(|synopsis| !synopsis.is_empty()),
+        poster,
+        status,
+        kind,
+        season,
+        year,
+        genres,
+        episodes,
+    }
+}
+
+/// file size declared in a variant label (e.g. `(1.2GB)`, `· 700 MB`, `349MiB`), in bytes.
+///
+/// animepahe's `MB`/`GB` are binary units, so they scale the same as `MiB`/`GiB`.
+fn parse_size_bytes(text: &str) -> Option<u64> {
+    let caps = VARIANT_SIZE_RE.captures(text)?;
+    let value = caps.get(1)?.as_str().parse::<f64>().ok()?;
+    let multiplier = match caps.get(2)?.as_str().to_ascii_uppercase().as_str() {
+        "KB" | "KIB" => 1024.0,
+        "MB" | "MIB" => 1024.0 * 1024.0,
+        "GB" | "GIB" => 1024.0 * 1024.0 * 1024.0,
+        _ => return None,
+    };
+    Some((value * multiplier) as u64)
+}
+
+/// resolution wanted by [`PaheClient::select_variant`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QualityPreference {
+    Highest,
+    Lowest,
+    /// this resolution (e.g. `720`), falling back to the highest one when it isn't offered.
+    Exact(i32),
+    /// this resolution and no other; selection fails with
+    /// [`PaheError::NoSelectableVariant`] when it isn't offered.
+    ExactStrict(i32),
+}
+
+impl std::str::FromStr for QualityPreference {
+    type Err = PaheError;
+
+    /// parses `highest`, `lowest`, `720` or `720p`.
+    fn from_str(raw: &str) -> Result<Self> {
+        let normalized = raw.trim().to_ascii_lowercase();
+        match normalized.as_str() {
+            "highest" => Ok(Self::Highest),
+            "lowest" => Ok(Self::Lowest),
+            other => other
+                .trim_end_matches('p')
+                .parse::<i32>()
+                .map(Self::Exact)
+                .map_err(|_| PaheError::InvalidQuality(raw.to_string())),
+        }
+    }
+}
+
+impl std::fmt::Display for QualityPreference {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Highest => f.write_str("highest"),
+            Self::Lowest => f.write_str("lowest"),
+            Self::Exact(resolution) | Self::ExactStrict(resolution) => {
+                write!(f, "{resolution}p")
+            }
+        }
+    }
+}
+
+impl QualityPreference {
+    /// parses a comma separated fallback list such as `1080p,720p,highest`.
+    pub fn parse_list(raw: &str) -> Result<Vec<Self>> {
+        let preferences = raw
+            .split(',')
+            .map(str::trim)
+            .filter(|part| !part.is_empty())
+            .map(str::parse)
+            .collect::<Result<Vec<Self>>>()?;
+
+        if preferences.is_empty() {
+            return Err(PaheError::InvalidQuality(raw.to_string()));
+        }
+        Ok(preferences)
+    }
+}
+
+fn filter_by_lang(variants: Vec<EpisodeVariant>, lang: &str) -> Vec<EpisodeVariant> {
+    variants
+        .into_iter()
+        .filter(|variant| match lang {
+            "en" | "jp" | "zh" => variant.lang == lang,
+            "any" => true,
+            _ => false,
+        })
+        .collect()
+}
+
+/// the resolution `pref` settles on within `pool`, if it is offered at all.
+fn offered_resolution(pool: &[EpisodeVariant], pref: QualityPreference) -> Option<i32> {
+    let mut resolutions = pool.iter().map(|variant| variant.resolution);
+    match pref {
+        QualityPreference::Highest => resolutions.max(),
+        QualityPreference::Lowest => resolutions.min(),
+        QualityPreference::Exact(target) | QualityPreference::ExactStrict(target) => {
+            resolutions.any(|res| res == target).then_some(target)
+        }
+    }
+}
+
+/// several encodes can share a resolution; break the tie by declared size.
+fn pick_by_size(
+    pool: Vec<EpisodeVariant>,
+    target: i32,
+    prefer_smaller: bool,
+) -> Result<EpisodeVariant> {
+    let ties = pool
+        .into_iter()
+        .filter(|variant| variant.resolution == target);
+    let selected = if prefer_smaller {
+        ties.min_by_key(|variant| variant.size_bytes.unwrap_or(u64::MAX))
+    } else {
+        ties.max_by_key(|variant| variant.size_bytes)
+    };
+
+    selected.ok_or(PaheError::NoSelectableVariant)
 }
 
 /// selection result that pairs a play page with the chosen variant.
@@ -43,24 +409,247 @@ pub struct EpisodeSelection {
     pub variant: EpisodeVariant,
 }
 
+/// a single release api entry: episode number, play page and the metadata animepahe keeps
+/// for it.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EpisodeInfo {
+    /// episode number as reported by the release api.
+    pub episode: u32,
+    /// episode title, `None` when the api has none or it is blank.
+    pub title: Option<String>,
+    /// the release api's episode `session` id, the last segment of `play_link`.
+    pub session: String,
+    /// animepahe play page url for this episode.
+    pub play_link: String,
+    /// when the episode was added, as sent by the api (e.g. `2023-10-06 14:20:43`).
+    pub aired_at: Option<String>,
+    /// thumbnail image url.
+    pub snapshot: Option<String>,
+    /// runtime as sent by the api (e.g. `00:24:10`).
+    pub duration: Option<String>,
+    /// whether animepahe marks the episode as filler.
+    pub filler: bool,
+}
+
+impl EpisodeInfo {
+    /// the `(episode, play link)` pair used by the link-only apis.
+    pub fn link(&self) -> (u32, String) {
+        (self.episode, self.play_link.clone())
+    }
+}
+
+/// every episode of a series gathered by walking the release api until it runs dry.
+#[derive(Debug, Clone)]
+pub struct SeriesEpisodes {
+    /// episode count reported by the release api's `total` field.
+    pub reported_total: i32,
+    /// every entry actually returned by the api.
+    pub episodes: Vec<EpisodeInfo>,
+}
+
+impl SeriesEpisodes {
+    /// number of episodes actually observed across all release pages.
+    pub fn observed_count(&self) -> i32 {
+        self.episodes.len() as i32
+    }
+
+    /// `(episode, play link)` pairs for every observed episode.
+    pub fn links(&self) -> Vec<(u32, String)> {
+        self.episodes.iter().map(EpisodeInfo::link).collect()
+    }
+
+    /// whether the reported total agrees with the observed entries.
+    pub fn is_consistent(&self) -> bool {
+        self.reported_total == self.observed_count()
+    }
+}
+
+/// outcome of probing animepahe with the configured clearance cookies.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CookieStatus {
+    /// requests go through without a challenge.
+    Ok,
+    /// ddos-guard challenged the request and no cookies are configured.
+    Missing,
+    /// ddos-guard challenged the request even though cookies are configured.
+    Stale,
+}
+
 #[derive(Debug, Deserialize)]
 struct ReleasePage {
     total: i32,
+    #[serde(default)]
+    last_page: Option<i32>,
+    #[serde(default)]
     data: Vec<ReleaseItem>,
 }
 
+/// the release api either answers with a page or, when rate limited / given a bad id,
+/// with a 200 carrying an error object.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum ReleaseResponse {
+    Page(ReleasePage),
+    Error(ApiErrorBody),
+}
+
+#[derive(Debug, Deserialize)]
+struct ApiErrorBody {
+    #[serde(default)]
+    error: Option<String>,
+    #[serde(default)]
+    message: Option<String>,
+}
+
+impl ApiErrorBody {
+    fn into_message(self) -> String {
+        self.message
+            .or(self.error)
+            .unwrap_or_else(|| "unrecognized response".to_string())
+    }
+}
+
+/// a series matched by the animepahe search api.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct SearchResult {
+    /// numeric animepahe id.
+    pub id: u64,
+    pub title: String,
+    /// series uuid used in `/anime/{session}` links.
+    pub session: String,
+    /// release format such as `TV`, `Movie` or `OVA`.
+    #[serde(rename = "type", default)]
+    pub kind: Option<String>,
+    /// episode count, `None` when animepahe doesn't know it yet.
+    #[serde(default, deserialize_with = "non_zero")]
+    pub episodes: Option<u32>,
+    /// airing status such as `Finished Airing`.
+    #[serde(default)]
+    pub status: Option<String>,
+    #[serde(default)]
+    pub year: Option<u32>,
+    /// poster image url.
+    #[serde(default)]
+    pub poster: Option<String>,
+}
+
+/// the search api reports unknown episode counts as `0`.
+fn non_zero<'de, D>(deserializer: D) -> std::result::Result<Option<u32>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let value: Option<u32> = Deserialize::deserialize(deserializer)?;
+    Ok(value.filter(|value| *value > 0))
+}
+
+#[derive(Debug, Deserialize)]
+struct SearchPage {
+    total: u32,
+    #[serde(default)]
+    data: Vec<SearchResult>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum SearchResponse {
+    Page(SearchPage),
+    Error(ApiErrorBody),
+}
+
 #[derive(Debug, Deserialize)]
 struct ReleaseItem {
     episode: u32,
     session: String,
+    #[serde(default)]
+    title: Option<String>,
+    #[serde(default)]
+    created_at: Option<String>,
+    #[serde(default)]
+    snapshot: Option<String>,
+    #[serde(default)]
+    duration: Option<String>,
+    #[serde(default, deserialize_with = "filler_flag")]
+    filler: bool,
+}
+
+/// the release api marks filler episodes with `1`, but `null` and booleans show up too.
+fn filler_flag<'de, D>(deserializer: D) -> std::result::Result<bool, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Flag {
+        Number(u64),
+        Bool(bool),
+    }
+
+    let flag: Option<Flag> = Deserialize::deserialize(deserializer)?;
+    Ok(match flag {
+        Some(Flag::Number(value)) => value != 0,
+        Some(Flag::Bool(value)) => value,
+        None => false,
+    })
+}
+
+/// fails with [`PaheError::NoEpisodesAvailable`] when the release api knows no episodes.
+fn ensure_released(id: &str, page: &ReleasePage) -> Result<()> {
+    if page.total == 0 && page.data.is_empty() {
+        info!(anime_id = %id, "series has no released episodes");
+        return Err(PaheError::NoEpisodesAvailable {
+            anime_id: id.to_string(),
+        });
+    }
+    Ok(())
+}
+
+/// whether retrying a failed api request may help.
+fn is_transient(err: &PaheError) -> bool {
+    match err {
+        PaheError::Request { .. } => true,
+        PaheError::HttpStatus { status, .. } => status.is_server_error(),
+        _ => false,
+    }
+}
+
+/// trims an optional api string, treating blank values as missing.
+fn non_blank(value: Option<String>) -> Option<String> {
+    value
+        .map(|value| value.trim().to_string())
+        .filter(|value| !value.is_empty())
+}
+
+/// time limits for animepahe requests; kwik keeps its own.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct RequestTimeouts {
+    pub(crate) total: Duration,
+    pub(crate) connect: Option<Duration>,
+}
+
+impl Default for RequestTimeouts {
+    fn default() -> Self {
+        Self {
+            total: DEFAULT_REQUEST_TIMEOUT,
+            connect: None,
+        }
+    }
 }
 
 pub struct PaheClient {
     base_domain: String,
+    scheme: String,
     redirect_domain: String,
     client: ReqwestClient,
     kwik: KwikClient,
     cookie_header: Option<String>,
+    cookie_jar: Arc<Jar>,
+    cookies_file: Vec<NetscapeCookie>,
+    /// `None` when an injected client brings its own.
+    user_agent: Option<String>,
+    api_retries: u8,
+    min_request_interval: Duration,
+    /// when the previous animepahe request went out; held while waiting so requests queue up.
+    last_request: tokio::sync::Mutex<Option<Instant>>,
 }
 
 impl PaheClient {
@@ -68,7 +657,15 @@ impl PaheClient {
     ///
     /// this is enough when animepahe is accessible without triggering ddos-guard.
     pub fn new(base_domain: String, redirect_domain: String) -> Result<Self> {
-        Self::with_cookie_header(base_domain, redirect_domain, None)
+        Self::with_options(
+            base_domain,
+            redirect_domain,
+            None,
+            Vec::new(),
+            ClientOptions::default(),
+            RequestTimeouts::default(),
+            None,
+        )
     }
 
     /// creates a client with a browser-exported cookie header.
@@ -79,18 +676,31 @@ impl PaheClient {
         redirect_domain: String,
         cookie_header: impl Into<String>,
     ) -> Result<Self> {
-        Self::with_cookie_header(base_domain, redirect_domain, Some(cookie_header.into()))
+        Self::with_options(
+            base_domain,
+            redirect_domain,
+            Some(cookie_header.into()),
+            Vec::new(),
+            ClientOptions::default(),
+            RequestTimeouts::default(),
+            None,
+        )
     }
 
-    fn with_cookie_header(
+    pub(crate) fn with_options(
         base_domain: String,
         redirect_domain: String,
         cookie_header: Option<String>,
+        cookies_file: Vec<NetscapeCookie>,
+        options: ClientOptions,
+        timeouts: RequestTimeouts,
+        injected: Option<ReqwestClient>,
     ) -> Result<Self> {
         info!(
             %base_domain,
             %redirect_domain,
             has_cookie_header = cookie_header.is_some(),
+            accept_invalid_certs = options.accept_invalid_certs,
             "initializing pahe client"
         );
         let jar = Arc::new(Jar::default());
@@ -109,20 +719,129 @@ impl PaheClient {
             debug!(loaded_cookies, "loaded cookies into reqwest cookie jar");
         }
 
-        let client = ReqwestClient::builder()
-            .cookie_provider(jar)
-            .build()
-            .map_err(PaheError::BuildClient)?;
+        let kwik = KwikClient::with_options(&options)?;
+        let (client, kwik, user_agent) = match injected {
+            Some(client) => (client.clone(), kwik.with_client(client), None),
+            None => {
+                let mut builder = options
+                    .apply(ReqwestClient::builder())
+                    .cookie_provider(Arc::clone(&jar))
+                    .timeout(timeouts.total);
+                if let Some(connect) = timeouts.connect {
+                    builder = builder.connect_timeout(connect);
+                }
+                let client = builder.build().map_err(PaheError::BuildClient)?;
+                (client, kwik, Some(options.user_agent().to_string()))
+            }
+        };
 
         Ok(Self {
+            scheme: "https".to_string(),
             base_domain,
             redirect_domain,
             client,
-            kwik: KwikClient::new()?,
+            kwik,
             cookie_header,
+            cookie_jar: jar,
+            cookies_file,
+            user_agent,
+            api_retries: DEFAULT_API_RETRIES,
+            min_request_interval: DEFAULT_MIN_REQUEST_INTERVAL,
+            last_request: tokio::sync::Mutex::new(None),
+        })
+    }
+
+    /// creates a client whose api and play links point at `base_url` (e.g. a local mock server).
+    #[cfg(test)]
+    fn with_base_url(base_url: &str, redirect_domain: &str) -> Result<Self> {
+        crate::builder::PaheBuilder::new()
+            .base_url(base_url)?
+            .redirect_domain(redirect_domain)
+            .build()
+    }
+
+    pub(crate) fn with_scheme(mut self, scheme: &str) -> Self {
+        self.scheme = scheme.to_string();
+        self
+    }
+
+    pub(crate) fn with_api_retries(mut self, retries: u8) -> Self {
+        self.api_retries = retries;
+        self
+    }
+
+    pub(crate) fn with_min_request_interval(mut self, interval: Duration) -> Self {
+        self.min_request_interval = interval;
+        self
+    }
+
+    /// waits until the configured interval has passed since the previous animepahe request.
+    async fn pace(&self) {
+        let mut last = self.last_request.lock().await;
+        if let Some(previous) = *last {
+            tokio::time::sleep_until(previous + self.min_request_interval).await;
+        }
+        *last = Some(Instant::now());
+    }
+
+    /// animepahe domain this client sends requests to.
+    pub fn base_domain(&self) -> &str {
+        &self.base_domain
+    }
+
+    /// link to the series page of `anime_id` on this client's domain.
+    pub fn anime_link(&self, anime_id: &str) -> String {
+        self.url(&format!("/anime/{anime_id}"))
+    }
+
+    /// link to the play page of episode `session` of `anime_id` on this client's domain.
+    pub fn play_link(&self, anime_id: &str, session: &str) -> String {
+        self.url(&format!("/play/{anime_id}/{session}"))
+    }
+
+    /// writes the cookie jar to a netscape `cookies.txt` file.
+    ///
+    /// cookies refreshed by animepahe (e.g. rotated `__ddg*` clearance values) replace the ones
+    /// loaded through [`crate::builder::PaheBuilder::cookies_file`]; entries for other domains are
+    /// written back unchanged.
+    pub fn save_cookies(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        let mut cookies = self.cookies_file.clone();
+
+        let base = Url::parse(&self.url("/")).map_err(|_| PaheError::AnimepaheBaseUrl)?;
+        let host = base.host_str().unwrap_or(&self.base_domain);
+        let current = self
+            .cookie_jar
+            .cookies(&base)
+            .and_then(|header| header.to_str().map(str::to_string).ok())
+            .unwrap_or_default();
+
+        for (name, value) in current
+            .split(';')
+            .filter_map(|pair| pair.trim().split_once('='))
+        {
+            match cookies
+                .iter_mut()
+                .find(|cookie| cookie.name == name && cookie.matches_host(host))
+            {
+                Some(cookie) => cookie.value = value.to_string(),
+                None => cookies.push(NetscapeCookie::session(host, name, value)),
+            }
+        }
+
+        debug!(path = %path.display(), cookies = cookies.len(), "saving cookies file");
+        std::fs::write(path, write_cookies_txt(&cookies)).map_err(|source| PaheError::CookiesFile {
+            action: "write",
+            path: path.to_path_buf(),
+            source,
         })
     }
 
+    /// builds an absolute animepahe url from a path such as `/api?m=release`.
+    fn url(&self, path: &str) -> String {
+        format!("{}://{}{path}", self.scheme, self.base_domain)
+    }
+
     fn headers(&self, referer: &str, is_api: bool) -> HeaderMap {
         debug!(%referer, is_api, "building request headers");
         let mut headers = HeaderMap::new();
@@ -135,13 +854,18 @@ impl PaheClient {
             }),
         );
         headers.insert(ACCEPT_LANGUAGE, HeaderValue::from_static("en-US,en;q=0.9"));
-        headers.insert(USER_AGENT, HeaderValue::from_static("Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/138.0.0.0 Safari/537.36"));
+
+        if let Some(user_agent) = &self.user_agent
+            && let Ok(v) = HeaderValue::from_str(user_agent)
+        {
+            headers.insert(USER_AGENT, v);
+        }
 
         if let Ok(v) = HeaderValue::from_str(referer) {
             headers.insert(REFERER, v);
         }
 
-        if let Ok(v) = HeaderValue::from_str(format!("https://{}/", self.base_domain).as_ref()) {
+        if let Ok(v) = HeaderValue::from_str(&self.url("/")) {
             headers.insert(ORIGIN, v);
         }
 
@@ -167,10 +891,12 @@ impl PaheClient {
         Ok(id)
     }
 
+    /// whether `body` is a ddos-guard or cloudflare challenge rather than animepahe itself.
+    ///
+    /// matched case-insensitively, so localized guard pages are caught by their markup.
     fn detect_ddos_guard(body: &str) -> bool {
-        body.contains("DDoS-Guard")
-            || body.contains("/.well-known/ddos-guard/js-challenge")
-            || body.contains("Checking your browser before accessing")
+        let body = body.to_ascii_lowercase();
+        CHALLENGE_MARKERS.iter().any(|marker| body.contains(marker))
     }
 
     async fn ensure_success_or_ddg(
@@ -190,12 +916,13 @@ impl PaheClient {
             .await
             .unwrap_or_else(|_| "<failed to read error body>".to_string());
 
-        if status.as_u16() == 403 && Self::detect_ddos_guard(&body) {
-            info!(%context, "ddos-guard challenge detected");
+        // cloudflare answers with 503 and ddos-guard with 403, so the status alone says little
+        if Self::detect_ddos_guard(&body) {
+            info!(%context, %status, "bot challenge detected");
             let hint = if cookie_hint {
-                "DDoS-Guard challenge detected even with provided cookie header. Refresh cookies from a real browser session."
+                "Challenge detected even with provided cookie header. Refresh cookies from a real browser session."
             } else {
-                "DDoS-Guard challenge detected. Solve challenge in a real browser and initialize .cookies_str(COOKIES)"
+                "Challenge detected. Solve challenge in a real browser and initialize .cookies_str(COOKIES)"
             };
             return Err(PaheError::DdosGuard {
                 context: context.to_string(),
@@ -210,10 +937,60 @@ impl PaheClient {
         })
     }
 
+    /// makes one lightweight request to tell whether ddos-guard lets this client through.
+    ///
+    /// useful to fail fast on stale cookies before resolving a whole batch.
+    pub async fn check_cookies(&self) -> Result<CookieStatus> {
+        info!("checking clearance cookies");
+        let home = self.url("/");
+        self.pace().await;
+        let resp = self
+            .client
+            .get(&home)
+            .headers(self.headers(&home, false))
+            .send()
+            .await
+            .map_err(|source| PaheError::Request {
+                context: "checking clearance cookies".to_string(),
+                source,
+            })?;
+
+        let status = resp.status();
+        if status.is_success() {
+            debug!("clearance cookies accepted");
+            return Ok(CookieStatus::Ok);
+        }
+
+        let body = resp
+            .text()
+            .await
+            .unwrap_or_else(|_| "<failed to read error body>".to_string());
+
+        if Self::detect_ddos_guard(&body) {
+            let cookie_status = if self.cookie_header.is_some() {
+                CookieStatus::Stale
+            } else {
+                CookieStatus::Missing
+            };
+            info!(
+                ?cookie_status,
+                "ddos-guard challenge hit while checking cookies"
+            );
+            return Ok(cookie_status);
+        }
+
+        Err(PaheError::HttpStatus {
+            context: "animepahe home page".to_string(),
+            status,
+            body,
+        })
+    }
+
     pub async fn get_series_metadata(&self, series_link: &str) -> Result<Anime> {
         info!(%series_link, "fetching series metadata");
         let id = Self::anime_id(series_link)?;
 
+        self.pace().await;
         let resp = self
             .client
             .get(series_link)
@@ -232,114 +1009,306 @@ impl PaheClient {
         )
         .await?;
 
-        let doc =
-            Html::parse_document(&resp.text().await.map_err(|source| PaheError::Request {
-                context: "".to_string(),
+        let text = resp.text().await.map_err(|source| PaheError::Request {
+            context: "".to_string(),
+            source,
+        })?;
+        let anime = parse_anime_page(id, &text);
+
+        debug!(
+            anime_id = %anime.id,
+            title = anime.title.as_deref().unwrap_or("<none>"),
+            year = ?anime.year,
+            genres = anime.genres.len(),
+            "parsed series metadata"
+        );
+        Ok(anime)
+    }
+
+    /// searches animepahe for series matching `query`.
+    ///
+    /// errors with [`PaheError::NoSearchResults`] when nothing matches.
+    pub async fn search_series(&self, query: &str) -> Result<Vec<SearchResult>> {
+        info!(%query, "searching series");
+        self.pace().await;
+        let resp = self
+            .client
+            .get(self.url("/api"))
+            .query(&[("m", "search"), ("q", query)])
+            .headers(self.headers(&self.url("/"), true))
+            .send()
+            .await
+            .map_err(|source| PaheError::Request {
+                context: format!("searching for {query}"),
                 source,
-            })?);
+            })?;
+
+        let resp =
+            Self::ensure_success_or_ddg(resp, "animepahe search", self.cookie_header.is_some())
+                .await?;
 
-        let mut title = None;
+        let parsed: SearchResponse = resp.json().await.map_err(|source| PaheError::Json {
+            context: "parsing search results json".to_string(),
+            source,
+        })?;
 
-        let sel = Selector::parse(".title-wrapper h1 span").expect("invalid selector");
-        if let Some(first) = doc.select(&sel).next() {
-            title = first.text().next().map(String::from);
+        let results = match parsed {
+            SearchResponse::Page(page) => {
+                debug!(%query, total = page.total, "parsed search results");
+                page.data
+            }
+            SearchResponse::Error(body) => {
+                return Err(PaheError::ApiError {
+                    context: "animepahe search".to_string(),
+                    message: body.into_message(),
+                });
+            }
         };
 
-        debug!(
-            anime_id = %id,
-            title = title.as_deref().unwrap_or("<none>"),
-            "parsed series metadata"
-        );
-        Ok(Anime { id, title })
+        if results.is_empty() {
+            return Err(PaheError::NoSearchResults {
+                query: query.to_string(),
+            });
+        }
+
+        Ok(results)
     }
 
-    /// returns the total number of episodes reported by animepahe for a series.
-    pub async fn get_series_episode_count(&self, id: &str) -> Result<i32> {
-        info!(anime_id = %id, "fetching series episode count");
-        let url = format!(
-            "https://{}/api?m=release&id={id}&sort=episode_asc&page=1",
-            self.base_domain
-        );
+    /// loads a release page, retrying 5xx responses and network errors. ddos-guard blocks and
+    /// api error objects fail right away.
+    async fn fetch_release_page(&self, id: &str, page: i32) -> Result<ReleasePage> {
+        let mut attempt = 0;
+
+        loop {
+            match self.fetch_release_page_once(id, page).await {
+                Err(err) if attempt < self.api_retries && is_transient(&err) => {
+                    attempt += 1;
+                    info!(anime_id = %id, page, attempt, error = %err, "release api failed; retrying");
+                    tokio::time::sleep(API_RETRY_DELAY).await;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    async fn fetch_release_page_once(&self, id: &str, page: i32) -> Result<ReleasePage> {
+        debug!(anime_id = %id, page, "loading release page");
+        let url = self.url(&format!(
+            "/api?m=release&id={id}&sort=episode_asc&page={page}"
+        ));
 
+        self.pace().await;
         let resp = self
             .client
             .get(url)
-            .headers(self.headers(format!("https://{}/", self.base_domain).as_ref(), true))
+            .headers(self.headers(&self.url("/"), true))
             .send()
             .await
             .map_err(|source| PaheError::Request {
-                context: "requesting animepahe release api".to_string(),
+                context: format!("loading api page {page}"),
                 source,
             })?;
 
         let resp = Self::ensure_success_or_ddg(
             resp,
-            "animepahe release api",
+            &format!("animepahe page {page}"),
             self.cookie_header.is_some(),
         )
         .await?;
 
-        let parsed: ReleasePage = resp.json().await.map_err(|source| PaheError::Json {
-            context: "parsing release api json".to_string(),
+        let parsed: ReleaseResponse = resp.json().await.map_err(|source| PaheError::Json {
+            context: format!("parsing release page {page} json"),
             source,
         })?;
+
+        match parsed {
+            ReleaseResponse::Page(parsed) => {
+                debug!(page, entries = parsed.data.len(), "parsed release page");
+                Ok(parsed)
+            }
+            ReleaseResponse::Error(body) => {
+                let message = body.into_message();
+                info!(anime_id = %id, page, %message, "release api returned an error object");
+                Err(PaheError::ApiError {
+                    context: format!("animepahe page {page}"),
+                    message,
+                })
+            }
+        }
+    }
+
+    /// returns the total number of episodes reported by animepahe for a series.
+    ///
+    /// this trusts the api's `total` field; use [`PaheClient::fetch_all_episodes`]
+    /// when the number of episodes that can actually be fetched matters. a series without
+    /// any release yet fails with [`PaheError::NoEpisodesAvailable`].
+    pub async fn get_series_episode_count(&self, id: &str) -> Result<i32> {
+        info!(anime_id = %id, "fetching series episode count");
+        let parsed = self.fetch_release_page(id, 1).await?;
         debug!(anime_id = %id, total = parsed.total, "parsed episode count");
+        ensure_released(id, &parsed)?;
         Ok(parsed.total)
     }
 
-    /// collects animepahe play links for an inclusive episode range.
+    /// collects play links for every episode of a series.
     ///
-    /// internally this walks api pages in chunks of 30 episodes.
-    pub async fn fetch_series_episode_links(
+    /// release pages are walked until one comes back empty (or the api's last page
+    /// is reached), so trailing entries missing from the reported `total` are kept. at most
+    /// 500 pages are read.
+    pub async fn fetch_all_episodes(&self, id: &str) -> Result<SeriesEpisodes> {
+        self.fetch_all_episodes_with(id, |_| {}).await
+    }
+
+    /// collects `(episode, play link)` pairs for a whole series, without knowing its episode
+    /// count up front. see [`PaheClient::fetch_all_episodes`] for how pages are walked.
+    pub async fn fetch_all_episode_links(&self, id: &str) -> Result<Vec<(u32, String)>> {
+        Ok(self.fetch_all_episodes(id).await?.links())
+    }
+
+    /// like [`PaheClient::fetch_all_episodes`], calling `on_page` with each page's episodes
+    /// as soon as it is parsed.
+    pub async fn fetch_all_episodes_with<F>(
         &self,
         id: &str,
-        from_episode: i32,
-        to_episode: i32,
-    ) -> Result<Vec<(u32, String)>> {
-        let start_page = ((from_episode - 1) / 30) + 1;
-        let end_page = ((to_episode - 1) / 30) + 1;
-        info!(
-            anime_id = %id,
-            from_episode,
-            to_episode,
-            start_page,
-            end_page,
-            "fetching series episode links"
-        );
-        let mut links = Vec::new();
+        mut on_page: F,
+    ) -> Result<SeriesEpisodes>
+    where
+        F: FnMut(&[EpisodeInfo]),
+    {
+        info!(anime_id = %id, "fetching all series episodes");
+        let mut reported_total = 0;
+        let mut episodes = Vec::new();
+        let mut page = 1;
+
+        loop {
+            let parsed = self.fetch_release_page(id, page).await?;
+            if page == 1 {
+                reported_total = parsed.total;
+            }
 
-        for page in start_page..=end_page {
-            debug!(page, "loading release page");
-            let url = format!(
-                "https://{}/api?m=release&id={id}&sort=episode_asc&page={page}",
-                self.base_domain
+            if parsed.data.is_empty() {
+                break;
+            }
+
+            let page_start = episodes.len();
+            for item in parsed.data {
+                episodes.push(self.episode_info(id, item));
+            }
+            on_page(&episodes[page_start..]);
+
+            if parsed.last_page.is_some_and(|last| page >= last) {
+                break;
+            }
+            if page >= MAX_RELEASE_PAGES {
+                info!(anime_id = %id, page, "release pages keep going; stopping at the page cap");
+                break;
+            }
+            page += 1;
+        }
+
+        let episodes = SeriesEpisodes {
+            reported_total,
+            episodes,
+        };
+
+        if !episodes.is_consistent() {
+            info!(
+                anime_id = %id,
+                reported_total = episodes.reported_total,
+                observed = episodes.observed_count(),
+                "release api total disagrees with fetched episodes"
             );
+        }
 
-            let resp = self
-                .client
-                .get(url)
-                .headers(self.headers(format!("https://{}/", self.base_domain).as_ref(), true))
-                .send()
-                .await
-                .map_err(|source| PaheError::Request {
-                    context: format!("loading api page {page}"),
-                    source,
-                })?;
-
-            let resp = Self::ensure_success_or_ddg(
-                resp,
-                &format!("animepahe page {page}"),
-                self.cookie_header.is_some(),
-            )
+        info!(
+            anime_id = %id,
+            observed = episodes.observed_count(),
+            "finished fetching all series episodes"
+        );
+        Ok(episodes)
+    }
+
+    /// collects animepahe play links for an inclusive episode range.
+    ///
+    /// internally this walks api pages in chunks of 30 episodes. a series without any
+    /// release yet fails with [`PaheError::NoEpisodesAvailable`], while a range past the
+    /// last episode just comes back empty.
+    pub async fn fetch_series_episode_links(
+        &self,
+        id: &str,
+        from_episode: i32,
+        to_episode: i32,
+    ) -> Result<Vec<(u32, String)>> {
+        let episodes = self
+            .fetch_series_episodes_with(id, from_episode, to_episode, |_| {})
             .await?;
+        Ok(episodes.iter().map(EpisodeInfo::link).collect())
+    }
 
-            let parsed: ReleasePage = resp.json().await.map_err(|source| PaheError::Json {
-                context: format!("parsing release page {page} json"),
-                source,
-            })?;
-            debug!(page, entries = parsed.data.len(), "parsed release page");
+    /// like [`PaheClient::fetch_series_episode_links`], but keeps each episode's metadata
+    /// (title, air date, snapshot, duration and filler flag).
+    pub async fn fetch_series_episodes(
+        &self,
+        id: &str,
+        from_episode: i32,
+        to_episode: i32,
+    ) -> Result<Vec<EpisodeInfo>> {
+        self.fetch_series_episodes_with(id, from_episode, to_episode, |_| {})
+            .await
+    }
+
+    /// like [`PaheClient::fetch_series_episodes`], but calls `on_page` with the episodes taken from each release page as soon as it is parsed.
+    pub async fn fetch_series_episodes_with<F>(
+        &self,
+        id: &str,
+        from_episode: i32,
+        to_episode: i32,
+        on_page: F,
+    ) -> Result<Vec<EpisodeInfo>>
+    where
+        F: FnMut(&[EpisodeInfo]),
+    {
+        let range = self
+            .fetch_series_range_with(id, from_episode, to_episode, on_page)
+            .await?;
+        Ok(range.episodes)
+    }
+
+    /// like [`PaheClient::fetch_series_episodes_with`], but also keeps the episode count the
+    /// release api reported, e.g. to tell how far a range overshot the last episode.
+    pub async fn fetch_series_range_with<F>(
+        &self,
+        id: &str,
+        from_episode: i32,
+        to_episode: i32,
+        mut on_page: F,
+    ) -> Result<SeriesEpisodes>
+    where
+        F: FnMut(&[EpisodeInfo]),
+    {
+        let start_page = ((from_episode - 1) / 30) + 1;
+        let end_page = ((to_episode - 1) / 30) + 1;
+        info!(
+            anime_id = %id,
+            from_episode,
+            to_episode,
+            start_page,
+            end_page,
+            "fetching series episode links"
+        );
+        let mut reported_total = 0;
+        let mut episodes = Vec::new();
 
-            let mut current_index = (start_page - 1) * 30;
+        for page in start_page..=end_page {
+            let parsed = self.fetch_release_page(id, page).await?;
+            if page == start_page {
+                ensure_released(id, &parsed)?;
+                reported_total = parsed.total;
+            }
+            let page_start = episodes.len();
+
+            // each release page holds 30 episodes, so this page's base depends on `page`.
+            let mut current_index = (page - 1) * 30;
 
             for item in parsed.data {
                 current_index += 1;
@@ -352,24 +1321,38 @@ impl PaheClient {
                     break;
                 }
 
-                links.push((
-                    item.episode,
-                    format!("https://{}/play/{id}/{}", self.base_domain, item.session),
-                ));
+                episodes.push(self.episode_info(id, item));
             }
+
+            on_page(&episodes[page_start..]);
         }
 
         info!(
             anime_id = %id,
-            fetched_links = links.len(),
+            fetched_links = episodes.len(),
             "finished fetching series episode links"
         );
-        Ok(links)
+        Ok(SeriesEpisodes {
+            reported_total,
+            episodes,
+        })
     }
 
-    /// parses all available mirrors/qualities from a play page.
-    pub async fn fetch_episode_variants(&self, play_link: &str) -> Result<Vec<EpisodeVariant>> {
-        info!(%play_link, "fetching episode variants");
+    fn episode_info(&self, id: &str, item: ReleaseItem) -> EpisodeInfo {
+        EpisodeInfo {
+            episode: item.episode,
+            title: non_blank(item.title),
+            play_link: self.play_link(id, &item.session),
+            session: item.session,
+            aired_at: non_blank(item.created_at),
+            snapshot: non_blank(item.snapshot),
+            duration: non_blank(item.duration),
+            filler: item.filler,
+        }
+    }
+
+    async fn fetch_play_page(&self, play_link: &str) -> Result<String> {
+        self.pace().await;
         let resp = self
             .client
             .get(play_link)
@@ -388,117 +1371,177 @@ impl PaheClient {
         )
         .await?;
 
-        let text = resp
-            .text()
-            .await
-            .map_err(|source| PaheError::ResponseBody {
-                context: "reading play page body".to_string(),
-                source,
-            })?;
+        resp.text().await.map_err(|source| PaheError::ResponseBody {
+            context: "reading play page body".to_string(),
+            source,
+        })
+    }
 
-        let doc = Html::parse_document(&text);
-        let anchor_sel =
-            Selector::parse(format!(r#"a[href^="https://{}"]"#, self.redirect_domain).as_ref())
-                .unwrap();
-        let span_sel = Selector::parse("span").unwrap();
+    /// parses all available mirrors/qualities from a play page.
+    ///
+    /// a play page that loads without any download anchors is re-fetched a couple of
+    /// times before giving up with [`PaheError::EmptyPlayPage`]; a page whose anchors
+    /// all point at other hosts fails immediately with [`PaheError::NoMirrors`].
+    pub async fn fetch_episode_variants(&self, play_link: &str) -> Result<Vec<EpisodeVariant>> {
+        info!(%play_link, "fetching episode variants");
+        let mut attempt = 0;
 
-        let mut variants = Vec::new();
+        loop {
+            let text = self.fetch_play_page(play_link).await?;
 
-        for a in doc.select(&anchor_sel) {
-            let dpahe_link = a.value().attr("href").unwrap_or_default().to_string();
+            match self.parse_episode_variants(&text) {
+                Err(PaheError::EmptyPlayPage) if attempt < PLAY_PAGE_RETRIES => {
+                    attempt += 1;
+                    info!(%play_link, attempt, "play page has no download anchors; retrying");
+                    tokio::time::sleep(PLAY_PAGE_RETRY_DELAY).await;
+                }
+                Err(err) => {
+                    info!(%play_link, error = %err, "no variants found on play page");
+                    return Err(err);
+                }
+                Ok(variants) => {
+                    info!(%play_link, variant_count = variants.len(), "finished parsing episode variants");
+                    return Ok(variants);
+                }
+            }
+        }
+    }
 
-            let block = a.inner_html();
-            let full_text = a.text().collect::<Vec<_>>().join(" ").to_lowercase();
-
-            // resolution
-            let resolution = full_text
-                .split_whitespace()
-                .find_map(|w| {
-                    if w.ends_with('p') {
-                        w.trim_end_matches('p').parse::<i32>().ok()
-                    } else {
-                        None
-                    }
-                })
-                .unwrap_or(0);
+    /// picks a variant by audio language and resolution.
+    ///
+    /// `lang` is `jp`, `en`, `zh` or `any`. variants sharing the chosen resolution are told
+    /// apart by declared size: the largest wins, except for [`QualityPreference::Lowest`]
+    /// which takes the smallest.
+    pub fn select_variant(
+        &self,
+        variants: Vec<EpisodeVariant>,
+        quality_pref: QualityPreference,
+        lang: &str,
+    ) -> Result<EpisodeVariant> {
+        let prefer_smaller = quality_pref == QualityPreference::Lowest;
+        self.select_variant_by_size(variants, quality_pref, lang, prefer_smaller)
+    }
 
-            // audio language
-            let mut lang = "jp".to_string();
+    /// like [`PaheClient::select_variant`], but breaks resolution ties towards the smaller
+    /// or larger file as asked.
+    pub fn select_variant_by_size(
+        &self,
+        variants: Vec<EpisodeVariant>,
+        quality_pref: QualityPreference,
+        lang: &str,
+        prefer_smaller: bool,
+    ) -> Result<EpisodeVariant> {
+        let pool = filter_by_lang(variants, lang);
 
-            let mut bluray = false;
+        debug!(
+            candidates = pool.len(),
+            ?quality_pref,
+            %lang,
+            prefer_smaller,
+            "selecting variant"
+        );
 
-            for span in a.select(&span_sel) {
-                let content = span.text().collect::<String>().trim().to_lowercase();
-                match content.as_str() {
-                    "bd" => {
-                        bluray = true;
-                    }
-                    "eng" => {
-                        lang = "en".to_string();
-                        break;
-                    }
-                    "chi" => {
-                        lang = "zh".to_string();
-                        break;
-                    }
-                    _ => {}
-                }
-            }
+        // a lenient exact resolution that isn't offered silently falls back to the highest one
+        let target = offered_resolution(&pool, quality_pref)
+            .or_else(|| match quality_pref {
+                QualityPreference::ExactStrict(_) => None,
+                _ => offered_resolution(&pool, QualityPreference::Highest),
+            })
+            .ok_or(PaheError::NoSelectableVariant)?;
+
+        pick_by_size(pool, target, prefer_smaller)
+    }
+
+    /// walks `preferences` in order and picks the first resolution that is actually offered.
+    ///
+    /// `langs` is a fallback list too: the first language with any variant is chosen, and
+    /// only then is the quality picked within it. unlike
+    /// [`PaheClient::select_variant_by_size`], an exact resolution never falls back to the
+    /// highest one; list [`QualityPreference::Highest`] last to get that behavior.
+    pub fn select_variant_from(
+        &self,
+        variants: Vec<EpisodeVariant>,
+        preferences: &[QualityPreference],
+        langs: &[&str],
+        prefer_smaller: bool,
+    ) -> Result<EpisodeVariant> {
+        let Some((lang, pool)) = langs.iter().find_map(|lang| {
+            let pool = filter_by_lang(variants.clone(), lang);
+            (!pool.is_empty()).then_some((lang, pool))
+        }) else {
+            return Err(PaheError::NoSelectableVariant);
+        };
+
+        debug!(
+            candidates = pool.len(),
+            ?preferences,
+            %lang,
+            prefer_smaller,
+            "selecting variant from fallback list"
+        );
 
-            variants.push(EpisodeVariant {
-                dpahe_link,
-                source_text: block,
-                resolution,
-                lang,
-                bluray,
+        let Some(target) = preferences
+            .iter()
+            .find_map(|pref| offered_resolution(&pool, *pref))
+        else {
+            let mut available: Vec<i32> = pool.iter().map(|variant| variant.resolution).collect();
+            available.sort_unstable_by(|a, b| b.cmp(a));
+            available.dedup();
+            return Err(PaheError::QualityUnavailable {
+                wanted: preferences
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>()
+                    .join(","),
+                available: available
+                    .iter()
+                    .map(|resolution| format!("{resolution}p"))
+                    .collect::<Vec<_>>()
+                    .join(", "),
             });
+        };
+
+        pick_by_size(pool, target, prefer_smaller)
+    }
+
+    fn parse_episode_variants(&self, text: &str) -> Result<Vec<EpisodeVariant>> {
+        let doc = Html::parse_document(text);
+        let download_sel = Selector::parse("#pickDownload a").unwrap();
+        let anchor_sel =
+            Selector::parse(format!(r#"a[href^="https://{}"]"#, self.redirect_domain).as_ref())
+                .unwrap();
+
+        let mut variants = Vec::new();
+
+        for a in doc.select(&anchor_sel) {
+            let dpahe_link = a.value().attr("href").unwrap_or_default().to_string();
+            variants.push(EpisodeVariant::from_anchor(dpahe_link, a));
             if let Some(last) = variants.last() {
                 debug!(
                     dpahe_link = %last.dpahe_link,
                     resolution = last.resolution,
                     lang = %last.lang,
                     bluray = last.bluray,
+                    group = ?last.group,
+                    size_bytes = ?last.size_bytes,
                     "parsed variant"
                 );
             }
         }
 
         if variants.is_empty() {
-            info!(%play_link, "no variants found on play page");
+            if doc.select(&download_sel).next().is_none() {
+                return Err(PaheError::EmptyPlayPage);
+            }
             return Err(PaheError::NoMirrors);
         }
 
-        info!(%play_link, variant_count = variants.len(), "finished parsing episode variants");
         Ok(variants)
     }
 
     pub async fn fetch_episode_index(&self, play_link: &str) -> Result<u32> {
         info!(%play_link, "fetching episode index");
-        let resp = self
-            .client
-            .get(play_link)
-            .headers(self.headers(play_link, false))
-            .send()
-            .await
-            .map_err(|source| PaheError::Request {
-                context: format!("getting play page {play_link}"),
-                source,
-            })?;
-
-        let resp = Self::ensure_success_or_ddg(
-            resp,
-            &format!("play page {play_link}"),
-            self.cookie_header.is_some(),
-        )
-        .await?;
-
-        let text = resp
-            .text()
-            .await
-            .map_err(|source| PaheError::ResponseBody {
-                context: "reading play page body".to_string(),
-                source,
-            })?;
+        let text = self.fetch_play_page(play_link).await?;
 
         let episode = Html::parse_document(&text)
             .select(&Selector::parse("button#episodeMenu").unwrap())
@@ -517,28 +1560,74 @@ impl PaheClient {
         Ok(episode)
     }
 
+    async fn resolve_kwik_file(
+        &self,
+        variant: &EpisodeVariant,
+    ) -> pahe_core::Result<(PaheLink, KwikFile)> {
+        let pahe_link = self.kwik.resolve_pahe_link(&variant.dpahe_link).await?;
+        let file = self
+            .kwik
+            .resolve_file_with_configured_retries(&pahe_link.file_url)
+            .await?;
+        Ok((pahe_link, file))
+    }
+
     /// resolves a `pahe.win` variant into a final downloadable direct link.
     pub async fn resolve_download(&self, variant: &EpisodeVariant) -> Result<DirectLink> {
         info!(dpahe_link = %variant.dpahe_link, "resolving direct link via kwik");
 
-        let pahe_link = self.kwik.resolve_pahe_link(&variant.dpahe_link).await?;
-        let file = self.kwik.resolve_file(&pahe_link.file_url, 3).await?;
+        let link = self
+            .kwik
+            .resolve_direct_link(&variant.dpahe_link)
+            .await
+            .map_err(|source| PaheError::ResolveDirectLink {
+                link: variant.dpahe_link.clone(),
+                source,
+            })?;
+
+        debug!(download = %link.direct_link, "resolved direct link");
 
-        debug!(download = %file.downloadable, "resolved direct link");
+        Ok(link)
+    }
 
-        Ok(DirectLink {
-            referer: pahe_link.url,
-            direct_link: file.downloadable,
+    /// checks that a resolved direct link hasn't expired yet, returning its declared size.
+    ///
+    /// see [`KwikClient::validate_link`].
+    pub async fn validate_download(&self, link: &DirectLink) -> Result<Option<u64>> {
+        Ok(self.kwik.validate_link(link).await?)
+    }
+
+    /// resolves many variants at once, with at most `concurrency` in flight.
+    ///
+    /// the output lines up with `variants`; a failed variant only fails its own entry.
+    pub async fn resolve_direct_links(
+        &self,
+        variants: &[EpisodeVariant],
+        concurrency: usize,
+    ) -> Vec<Result<DirectLink>> {
+        info!(
+            variants = variants.len(),
+            concurrency, "resolving direct links concurrently"
+        );
+        resolve_in_order(variants, concurrency, |variant| {
+            self.resolve_download(variant)
         })
+        .await
     }
 
     /// resolves a `pahe.win` variant into a stream source (m3u8) and referer.
     pub async fn resolve_stream(&self, variant: &EpisodeVariant) -> Result<Stream> {
         info!(dpahe_link = %variant.dpahe_link, "resolving stream link via kwik");
 
-        let pahe_link = self.kwik.resolve_pahe_link(&variant.dpahe_link).await?;
-        let file = self.kwik.resolve_file(&pahe_link.file_url, 3).await?;
-        let stream = self.kwik.extract_kwik_stream(file.embed).await?;
+        let stream = async {
+            let (_, file) = self.resolve_kwik_file(variant).await?;
+            self.kwik.extract_kwik_stream(file.embed).await
+        }
+        .await
+        .map_err(|source| PaheError::ResolveDirectLink {
+            link: variant.dpahe_link.clone(),
+            source,
+        })?;
 
         debug!(referer = %stream.referer, source = %stream.source, "resolved stream link");
 
@@ -549,12 +1638,323 @@ impl PaheClient {
     }
 }
 
+/// runs `resolve` over `items` with up to `concurrency` futures in flight, returning
+/// the results in input order.
+///
+/// this is the batching behind [`PaheClient::resolve_direct_links`], for callers that
+/// resolve more than a variant per item.
+pub async fn resolve_in_order<'a, T, R, F, Fut>(
+    items: &'a [T],
+    concurrency: usize,
+    resolve: F,
+) -> Vec<R>
+where
+    F: Fn(&'a T) -> Fut,
+    Fut: Future<Output = R>,
+{
+    let mut results: Vec<(usize, R)> = stream::iter(items.iter().enumerate())
+        .map(|(idx, item)| {
+            let fut = resolve(item);
+            async move { (idx, fut.await) }
+        })
+        .buffer_unordered(concurrency.max(1))
+        .collect()
+        .await;
+
+    results.sort_by_key(|(idx, _)| *idx);
+    results.into_iter().map(|(_, result)| result).collect()
+}
+
+/// extracts the release group from a variant label.
+///
+/// prefers the last bracketed segment that isn't a size, resolution or sub/dub tag, as in
+/// `1080p (350MB) [SubsPlease]`, and otherwise takes the leading segment of labels shaped
+/// like `SubsPlease · 1080p`.
+fn parse_group(label: &str) -> Option<String> {
+    let is_metadata = |text: &str| {
+        VARIANT_SIZE_RE.is_match(text)
+            || parse_resolution(text) != 0
+            || matches!(
+                text.to_ascii_lowercase().as_str(),
+                "dub" | "sub" | "bd" | "eng" | "chi"
+            )
+    };
+
+    let bracketed = VARIANT_BRACKETED_RE
+        .captures_iter(label)
+        .map(|caps| caps.get(1).map_or("", |m| m.as_str()))
+        .filter(|text| !is_metadata(text))
+        .last();
+    let leading = || {
+        let (head, _) = label.split_once('·')?;
+        Some(head.trim()).filter(|head| !head.is_empty() && !is_metadata(head))
+    };
+
+    bracketed.or_else(leading).map(str::to_string)
+}
+
+/// pixel heights accepted as bare numeric labels (without a trailing `p`).
+const KNOWN_RESOLUTIONS: [i32; 10] = [240, 360, 480, 540, 576, 720, 1080, 1440, 2160, 4320];
+
+/// extracts a vertical resolution from a variant label.
+///
+/// understands `1080p` in any case, `1920x1080` dimensions, shorthand such as `4k`, bare
+/// heights like `2160` and a `720p` glued to other text; returns `0` when nothing
+/// resolution-like is found.
+fn parse_resolution(text: &str) -> i32 {
+    let capture_height = |re: &Regex| {
+        re.captures(text)
+            .and_then(|caps| caps[1].parse::<i32>().ok())
+    };
+    if let Some(height) = capture_height(&VARIANT_DIMENSIONS_RE) {
+        return height;
+    }
+
+    text.split_whitespace()
+        .map(|w| w.trim_matches(|c: char| !c.is_ascii_alphanumeric()))
+        .map(str::to_ascii_lowercase)
+        .find_map(|w| match w.as_str() {
+            "8k" => Some(4320),
+            "4k" => Some(2160),
+            "2k" => Some(1440),
+            _ => {
+                if let Some(digits) = w.strip_suffix('p') {
+                    digits.parse::<i32>().ok()
+                } else {
+                    w.parse::<i32>()
+                        .ok()
+                        .filter(|height| KNOWN_RESOLUTIONS.contains(height))
+                }
+            }
+        })
+        .or_else(|| capture_height(&VARIANT_HEIGHT_RE))
+        .unwrap_or(0)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::builder::PaheBuilder;
+    use wiremock::matchers::{method, path, query_param};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
 
     const BASE_DOMAIN: &str = "animepahe.si";
 
+    #[test]
+    fn headers_use_configured_user_agent() {
+        let client = PaheBuilder::new()
+            .user_agent("pahe-test/1.0")
+            .build()
+            .expect("client should build");
+
+        let headers = client.headers("https://animepahe.si/", false);
+        assert_eq!(headers[USER_AGENT], "pahe-test/1.0");
+
+        let default = PaheBuilder::new().build().expect("client should build");
+        assert_eq!(
+            default.headers("https://animepahe.si/", true)[USER_AGENT],
+            pahe_core::DEFAULT_USER_AGENT
+        );
+    }
+
+    fn pick(quality: &str, lang: &str) -> Result<String> {
+        let variants = vec![
+            EpisodeVariant::fixture(720, "jp", "200MB"),
+            EpisodeVariant::fixture(1080, "jp", "1.2GB"),
+            EpisodeVariant::fixture(1080, "jp", "800MB"),
+            EpisodeVariant::fixture(360, "en", "90MB"),
+            EpisodeVariant::fixture(720, "jp", "150MB"),
+        ];
+        let client = PaheBuilder::new().build().expect("client should build");
+        let quality = quality.parse::<QualityPreference>()?;
+        Ok(client.select_variant(variants, quality, lang)?.dpahe_link)
+    }
+
+    #[tokio::test]
+    async fn resolve_in_order_keeps_input_order_and_isolates_failures() {
+        let variants: Vec<EpisodeVariant> = [1080, 720, 480, 360]
+            .into_iter()
+            .map(|resolution| EpisodeVariant::fixture(resolution, "jp", "100MB"))
+            .collect();
+
+        // later variants finish first, and the 480p one fails
+        let results = resolve_in_order(&variants, 4, |variant| async move {
+            let delay = variant.resolution as u64 / 20;
+            tokio::time::sleep(Duration::from_millis(delay)).await;
+            if variant.resolution == 480 {
+                return Err(PaheError::NoSelectableVariant);
+            }
+            Ok(variant.dpahe_link.clone())
+        })
+        .await;
+
+        assert_eq!(results.len(), 4);
+        assert_eq!(
+            results[0].as_deref().unwrap(),
+            "https://pahe.win/1080-jp-100MB"
+        );
+        assert_eq!(
+            results[1].as_deref().unwrap(),
+            "https://pahe.win/720-jp-100MB"
+        );
+        assert!(matches!(results[2], Err(PaheError::NoSelectableVariant)));
+        assert_eq!(
+            results[3].as_deref().unwrap(),
+            "https://pahe.win/360-jp-100MB"
+        );
+    }
+
+    #[test]
+    fn select_variant_takes_largest_highest() {
+        assert_eq!(
+            pick("highest", "jp").unwrap(),
+            "https://pahe.win/1080-jp-1.2GB"
+        );
+    }
+
+    #[test]
+    fn select_variant_takes_smallest_lowest() {
+        assert_eq!(
+            pick("lowest", "jp").unwrap(),
+            "https://pahe.win/720-jp-150MB"
+        );
+    }
+
+    #[test]
+    fn select_variant_exact_falls_back_to_highest() {
+        assert_eq!(pick("720p", "jp").unwrap(), "https://pahe.win/720-jp-200MB");
+        assert_eq!(pick("480", "jp").unwrap(), "https://pahe.win/1080-jp-1.2GB");
+    }
+
+    #[test]
+    fn select_variant_exact_strict_never_substitutes() {
+        let variants = vec![
+            EpisodeVariant::fixture(720, "jp", "200MB"),
+            EpisodeVariant::fixture(1080, "jp", "1.2GB"),
+        ];
+        let client = PaheBuilder::new().build().expect("client should build");
+
+        let strict = client
+            .select_variant(variants.clone(), QualityPreference::ExactStrict(720), "jp")
+            .expect("offered resolution should be picked");
+        assert_eq!(strict.dpahe_link, "https://pahe.win/720-jp-200MB");
+        assert!(matches!(
+            client.select_variant(variants.clone(), QualityPreference::ExactStrict(480), "jp"),
+            Err(PaheError::NoSelectableVariant)
+        ));
+
+        let lenient = client
+            .select_variant(variants, QualityPreference::Exact(480), "jp")
+            .expect("lenient exact should fall back");
+        assert_eq!(lenient.resolution, 1080);
+    }
+
+    #[test]
+    fn select_variant_from_walks_fallback_list_in_order() {
+        let variants = vec![
+            EpisodeVariant::fixture(720, "jp", "200MB"),
+            EpisodeVariant::fixture(1080, "jp", "1.2GB"),
+            EpisodeVariant::fixture(360, "en", "90MB"),
+        ];
+        let client = PaheBuilder::new().build().expect("client should build");
+        let prefs = QualityPreference::parse_list("480p, 720p,highest").unwrap();
+
+        let selected = client
+            .select_variant_from(variants, &prefs, &["jp"], false)
+            .unwrap();
+        assert_eq!(selected.dpahe_link, "https://pahe.win/720-jp-200MB");
+    }
+
+    #[test]
+    fn select_variant_from_is_strict_without_highest() {
+        let variants = vec![
+            EpisodeVariant::fixture(720, "jp", "200MB"),
+            EpisodeVariant::fixture(1080, "jp", "1.2GB"),
+        ];
+        let client = PaheBuilder::new().build().expect("client should build");
+        let prefs = QualityPreference::parse_list("480p,360").unwrap();
+
+        let err = client
+            .select_variant_from(variants, &prefs, &["jp"], false)
+            .unwrap_err();
+        assert!(matches!(
+            &err,
+            PaheError::QualityUnavailable { wanted, available }
+                if wanted == "480p,360p" && available == "1080p, 720p"
+        ));
+        assert!(QualityPreference::parse_list(" , ").is_err());
+    }
+
+    #[test]
+    fn select_variant_from_falls_back_to_next_language() {
+        let client = PaheBuilder::new().build().expect("client should build");
+        let dub_only = vec![
+            EpisodeVariant::fixture(720, "en", "200MB"),
+            EpisodeVariant::fixture(1080, "en", "1.1GB"),
+        ];
+
+        let selected = client
+            .select_variant_from(
+                dub_only,
+                &[QualityPreference::Highest],
+                &["jp", "en"],
+                false,
+            )
+            .unwrap();
+        assert_eq!(selected.dpahe_link, "https://pahe.win/1080-en-1.1GB");
+    }
+
+    #[test]
+    fn select_variant_from_picks_quality_within_first_language() {
+        let client = PaheBuilder::new().build().expect("client should build");
+        let both = vec![
+            EpisodeVariant::fixture(720, "jp", "200MB"),
+            EpisodeVariant::fixture(1080, "en", "1.1GB"),
+            EpisodeVariant::fixture(360, "jp", "80MB"),
+        ];
+
+        // the dub has 1080p, but japanese audio is listed first so quality is picked there
+        let selected = client
+            .select_variant_from(
+                both.clone(),
+                &[QualityPreference::Highest],
+                &["jp", "en"],
+                false,
+            )
+            .unwrap();
+        assert_eq!(selected.dpahe_link, "https://pahe.win/720-jp-200MB");
+
+        let err = client
+            .select_variant_from(
+                both,
+                &[QualityPreference::Exact(1080)],
+                &["jp", "en"],
+                false,
+            )
+            .unwrap_err();
+        assert!(matches!(err, PaheError::QualityUnavailable { .. }));
+    }
+
+    #[test]
+    fn select_variant_filters_by_language() {
+        assert_eq!(
+            pick("highest", "en").unwrap(),
+            "https://pahe.win/360-en-90MB"
+        );
+        assert_eq!(
+            pick("lowest", "any").unwrap(),
+            "https://pahe.win/360-en-90MB"
+        );
+        assert!(matches!(
+            pick("highest", "zh"),
+            Err(PaheError::NoSelectableVariant)
+        ));
+        assert!(matches!(
+            pick("best", "jp"),
+            Err(PaheError::InvalidQuality(raw)) if raw == "best"
+        ));
+    }
+
     #[test]
     fn anime_id_extracts_uuid_segment() {
         let link = format!("https://{BASE_DOMAIN}/anime/123e4567-e89b-12d3-a456-426614174000");
@@ -569,6 +1969,16 @@ mod tests {
         assert!(matches!(err, PaheError::InvalidAnimeLink { .. }));
     }
 
+    #[test]
+    fn links_use_the_configured_scheme_and_domain() {
+        let client = PaheClient::with_base_url("http://127.0.0.1:8080", "pahe.win").unwrap();
+        assert_eq!(client.anime_link("abc"), "http://127.0.0.1:8080/anime/abc");
+        assert_eq!(
+            client.play_link("abc", "s1"),
+            "http://127.0.0.1:8080/play/abc/s1"
+        );
+    }
+
     #[test]
     fn detect_ddos_guard_matches_known_markers() {
         assert!(PaheClient::detect_ddos_guard(
@@ -579,4 +1989,783 @@ mod tests {
         ));
         assert!(!PaheClient::detect_ddos_guard("<html>normal page</html>"));
     }
+
+    #[test]
+    fn detect_ddos_guard_matches_cloudflare_and_localized_pages() {
+        let cloudflare = r#"<!DOCTYPE html><html><head><title>Just a moment...</title></head>
+            <body><div id="cf-browser-verification"></div>
+            <script>window._cf_chl_opt={cvId: '3'};</script></body></html>"#;
+        assert!(PaheClient::detect_ddos_guard(cloudflare));
+        assert!(PaheClient::detect_ddos_guard(
+            r#"<form action="/?__cf_chl_tk=abc" method="POST"></form>"#
+        ));
+
+        let localized = r#"<html><head><title>Проверка браузера</title></head>
+            <body><script src="/.well-known/DDOS-GUARD/check?context=free_splash"></script></body></html>"#;
+        assert!(PaheClient::detect_ddos_guard(localized));
+    }
+
+    #[tokio::test]
+    async fn cloudflare_challenge_returns_cookie_hint() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/api"))
+            .respond_with(
+                ResponseTemplate::new(503).set_body_string(
+                    "<title>Just a moment...</title><script>_cf_chl_opt={}</script>",
+                ),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let client = PaheClient::with_base_url(&server.uri(), "pahe.win").unwrap();
+        match client.get_series_episode_count(ANIME_ID).await {
+            Err(PaheError::DdosGuard { hint, .. }) => assert!(hint.contains("cookies")),
+            other => panic!("expected a challenge error, got {other:?}"),
+        }
+    }
+
+    fn client() -> PaheClient {
+        PaheClient::new(BASE_DOMAIN.to_string(), "pahe.win".to_string())
+            .expect("client should build")
+    }
+
+    #[test]
+    fn parse_episode_variants_reads_download_anchors() {
+        let html = r#"<div id="pickDownload"><a href="https://pahe.win/abc">SubsPlease &middot; 1080p (350MB) <span>BD</span></a></div>"#;
+        let variants = client()
+            .parse_episode_variants(html)
+            .expect("variant should parse");
+        assert_eq!(variants.len(), 1);
+        assert_eq!(variants[0].resolution, 1080);
+        assert!(variants[0].bluray);
+        assert_eq!(variants[0].size_bytes, Some(350 * 1024 * 1024));
+        assert_eq!(variants[0].group.as_deref(), Some("SubsPlease"));
+    }
+
+    #[test]
+    fn parse_episode_variants_reads_group_and_dub() {
+        let html = r#"<div id="pickDownload">
+            <a href="https://pahe.win/abc" class="dropdown-item">720p Dub (140MB) [Erai-raws]</a>
+            <a href="https://pahe.win/def" class="dropdown-item"><span class="badge">BD</span> Judas &middot; 1080p (1.2GB) <span class="badge">chi</span></a>
+            <a href="https://pahe.win/ghi" class="dropdown-item">1080p Sub (350MB)</a>
+        </div>"#;
+        let variants = client()
+            .parse_episode_variants(html)
+            .expect("variants should parse");
+
+        assert_eq!(variants[0].lang, "en");
+        assert_eq!(variants[0].group.as_deref(), Some("Erai-raws"));
+        assert_eq!(variants[1].lang, "zh");
+        assert!(variants[1].bluray);
+        assert_eq!(variants[1].group.as_deref(), Some("Judas"));
+        assert_eq!(variants[2].lang, "jp");
+        assert_eq!(variants[2].group, None);
+    }
+
+    #[test]
+    fn builder_redirect_domain_selects_download_anchors() {
+        let html = r#"<div id="pickDownload">
+            <a href="https://pahe.mirror/abc">SubsPlease &middot; 1080p (350MB)</a>
+            <a href="https://pahe.win/def">SubsPlease &middot; 720p (150MB)</a>
+        </div>"#;
+        let client = PaheBuilder::new()
+            .redirect_domain("pahe.mirror")
+            .build()
+            .expect("client should build");
+
+        let variants = client
+            .parse_episode_variants(html)
+            .expect("mirror anchor should parse");
+        assert_eq!(variants.len(), 1);
+        assert_eq!(variants[0].dpahe_link, "https://pahe.mirror/abc");
+        assert_eq!(variants[0].resolution, 1080);
+    }
+
+    #[test]
+    fn parse_episode_variants_distinguishes_empty_page_from_foreign_hosts() {
+        let err = client()
+            .parse_episode_variants("<html><body></body></html>")
+            .expect_err("empty page should error");
+        assert!(matches!(err, PaheError::EmptyPlayPage));
+
+        let html = r#"<div id="pickDownload"><a href="https://example.com/abc">720p</a></div>"#;
+        let err = client()
+            .parse_episode_variants(html)
+            .expect_err("foreign host should error");
+        assert!(matches!(err, PaheError::NoMirrors));
+    }
+
+    #[test]
+    fn parse_resolution_handles_common_labels() {
+        assert_eq!(parse_resolution("subsplease · 1080p"), 1080);
+        assert_eq!(parse_resolution("subsplease · 4K"), 2160);
+        assert_eq!(parse_resolution("subsplease · 2160"), 2160);
+        assert_eq!(parse_resolution("subsplease · (720p)"), 720);
+        assert_eq!(parse_resolution("subsplease 2024"), 0);
+    }
+
+    #[test]
+    fn parse_resolution_handles_uppercase_dimensions_and_glued_labels() {
+        assert_eq!(parse_resolution("SubsPlease · 1080P"), 1080);
+        assert_eq!(parse_resolution("Erai-raws 1920x1080 HEVC"), 1080);
+        assert_eq!(parse_resolution("[Judas]HEVC-720p(140MB)"), 720);
+        assert_eq!(parse_resolution("episode 12 of 2024"), 0);
+    }
+
+    #[test]
+    fn parse_episode_variants_reads_resolution_from_markup_as_fallback() {
+        let html = r#"<div id="pickDownload"><a href="https://pahe.win/abc">SubsPlease <span title="720p"></span></a></div>"#;
+        let variants = client()
+            .parse_episode_variants(html)
+            .expect("variant should parse");
+        assert_eq!(variants[0].resolution, 720);
+    }
+
+    #[test]
+    fn variant_from_anchor_html_reads_the_label_and_badges() {
+        let variant = EpisodeVariant::from_anchor_html(
+            "https://pahe.win/abc",
+            "SubsPlease &middot; 1080p (1.5GB) <span class=\"badge\">BD</span> <span>eng</span>",
+        );
+
+        assert_eq!(variant.dpahe_link, "https://pahe.win/abc");
+        assert_eq!(variant.resolution, 1080);
+        assert_eq!(variant.lang, "en");
+        assert!(variant.bluray);
+        assert_eq!(variant.size_bytes, Some(1_610_612_736));
+        assert_eq!(variant.group.as_deref(), Some("SubsPlease"));
+    }
+
+    #[test]
+    fn parse_size_bytes_reads_label_size() {
+        const MIB: u64 = 1024 * 1024;
+
+        assert_eq!(
+            parse_size_bytes("SubsPlease &middot; 1080p (1.5GB)"),
+            Some(1_610_612_736)
+        );
+        assert_eq!(
+            parse_size_bytes("subsplease · 1080p (1.2gb)"),
+            Some((1.2 * 1024.0 * MIB as f64) as u64)
+        );
+        assert_eq!(
+            parse_size_bytes("subsplease · 720p · 700 MB"),
+            Some(700 * MIB)
+        );
+        assert_eq!(
+            parse_size_bytes("subsplease · 360p (349MiB)"),
+            Some(349 * MIB)
+        );
+        assert_eq!(parse_size_bytes("SubsPlease &middot; 360p"), None);
+    }
+
+    #[test]
+    fn search_response_parses_captured_results() {
+        let body = r#"{"total":2,"per_page":8,"current_page":1,"last_page":1,"from":1,"to":2,"data":[
+            {"id":4,"title":"Naruto","type":"TV","episodes":220,"status":"Finished Airing","season":"Fall","year":2002,"score":7.99,"poster":"https:\/\/i.animepahe.si\/posters\/naruto.jpg","session":"3b0b5c3e-2f86-7a3e-0c1a-1d9b1c6a0c1e"},
+            {"id":5646,"title":"Boruto: Naruto Next Generations","type":"TV","episodes":0,"status":"Currently Airing","season":"Spring","year":null,"score":6.1,"poster":null,"session":"0e1c1b5f-7a0e-3a1b-9b2f-2f6b1d9c5a7a"}
+        ]}"#;
+
+        let parsed: SearchResponse = serde_json::from_str(body).expect("search should parse");
+        let SearchResponse::Page(page) = parsed else {
+            panic!("expected a result page");
+        };
+
+        assert_eq!(page.data.len(), 2);
+        let naruto = &page.data[0];
+        assert_eq!(naruto.title, "Naruto");
+        assert_eq!(naruto.session, "3b0b5c3e-2f86-7a3e-0c1a-1d9b1c6a0c1e");
+        assert_eq!(naruto.kind.as_deref(), Some("TV"));
+        assert_eq!(naruto.episodes, Some(220));
+        assert_eq!(naruto.year, Some(2002));
+        assert_eq!(
+            naruto.poster.as_deref(),
+            Some("https://i.animepahe.si/posters/naruto.jpg")
+        );
+
+        let boruto = &page.data[1];
+        assert_eq!(boruto.episodes, None);
+        assert_eq!(boruto.year, None);
+        assert_eq!(boruto.poster, None);
+    }
+
+    #[tokio::test]
+    async fn search_series_reports_empty_results() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/api"))
+            .and(query_param("m", "search"))
+            .and(query_param("q", "no such anime"))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_raw(r#"{"total":0}"#, "application/json"),
+            )
+            .mount(&server)
+            .await;
+
+        let client = PaheClient::with_base_url(&server.uri(), "pahe.win").unwrap();
+        let result = client.search_series("no such anime").await;
+
+        assert!(matches!(
+            result,
+            Err(PaheError::NoSearchResults { query }) if query == "no such anime"
+        ));
+    }
+
+    #[test]
+    fn release_page_tolerates_missing_data() {
+        let page: ReleasePage =
+            serde_json::from_str(r#"{"total":12,"per_page":30,"current_page":2,"last_page":1}"#)
+                .expect("release page should parse");
+        assert_eq!(page.total, 12);
+        assert_eq!(page.last_page, Some(1));
+        assert!(page.data.is_empty());
+    }
+
+    #[test]
+    fn series_episodes_reports_observed_count() {
+        let episodes = SeriesEpisodes {
+            reported_total: 2,
+            episodes: ["a", "b", "c"]
+                .into_iter()
+                .zip(1..)
+                .map(|(play_link, episode)| EpisodeInfo {
+                    episode,
+                    play_link: play_link.to_string(),
+                    ..Default::default()
+                })
+                .collect(),
+        };
+        assert_eq!(episodes.observed_count(), 3);
+        assert!(!episodes.is_consistent());
+    }
+
+    const ANIME_ID: &str = "123e4567-e89b-12d3-a456-426614174000";
+
+    fn release_page(total: i32, page: i32, episodes: std::ops::RangeInclusive<u32>) -> String {
+        let data = episodes
+            .map(|ep| format!(r#"{{"episode":{ep},"session":"s{ep}"}}"#))
+            .collect::<Vec<_>>()
+            .join(",");
+        let last_page = (total + 29) / 30;
+        format!(
+            r#"{{"total":{total},"per_page":30,"current_page":{page},"last_page":{last_page},"data":[{data}]}}"#
+        )
+    }
+
+    async fn mount_release_page(server: &MockServer, page: i32, body: String) {
+        Mock::given(method("GET"))
+            .and(path("/api"))
+            .and(query_param("m", "release"))
+            .and(query_param("page", page.to_string()))
+            .respond_with(ResponseTemplate::new(200).set_body_raw(body, "application/json"))
+            .mount(server)
+            .await;
+    }
+
+    #[test]
+    fn parse_anime_page_reads_fixture() {
+        let anime = parse_anime_page(
+            ANIME_ID.to_string(),
+            include_str!("../tests/fixtures/anime_page.html"),
+        );
+
+        assert_eq!(anime.title.as_deref(), Some("Sousou no Frieren"));
+        let synopsis = anime.synopsis.expect("synopsis should be parsed");
+        assert!(synopsis.starts_with("During their decade-long quest"));
+        assert!(synopsis.ends_with("\n(Source: MAL Rewrite)"));
+        assert_eq!(anime.year, Some(2023));
+        assert_eq!(anime.season.as_deref(), Some("Fall 2023"));
+        assert_eq!(anime.kind.as_deref(), Some("TV"));
+        assert_eq!(anime.status.as_deref(), Some("Finished Airing"));
+        assert_eq!(
+            anime.poster.as_deref(),
+            Some("https://i.animepahe.si/posters/5d1c3c5bd3b2f2d7c8c9e0a1b2c3d4e5.jpg")
+        );
+        assert_eq!(anime.genres, vec!["Adventure", "Drama", "Fantasy"]);
+        assert_eq!(anime.episodes, Some(28));
+    }
+
+    #[tokio::test]
+    async fn get_series_metadata_reads_title_from_series_page() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path(format!("/anime/{ANIME_ID}")))
+            .respond_with(ResponseTemplate::new(200).set_body_string(
+                r#"<div class="title-wrapper"><h1><span>Frieren</span></h1></div>"#,
+            ))
+            .mount(&server)
+            .await;
+
+        let client = PaheClient::with_base_url(&server.uri(), "pahe.win").unwrap();
+        let anime = client
+            .get_series_metadata(&format!("{}/anime/{ANIME_ID}", server.uri()))
+            .await
+            .expect("metadata should load");
+
+        assert_eq!(anime.id, ANIME_ID);
+        assert_eq!(anime.title.as_deref(), Some("Frieren"));
+    }
+
+    #[tokio::test]
+    async fn fetch_series_episode_links_slices_within_first_page() {
+        let server = MockServer::start().await;
+        mount_release_page(&server, 1, release_page(40, 1, 1..=30)).await;
+
+        let client = PaheClient::with_base_url(&server.uri(), "pahe.win").unwrap();
+        let links = client
+            .fetch_series_episode_links(ANIME_ID, 3, 5)
+            .await
+            .expect("links should load");
+
+        let episodes: Vec<u32> = links.iter().map(|(ep, _)| *ep).collect();
+        assert_eq!(episodes, vec![3, 4, 5]);
+        assert_eq!(links[0].1, format!("{}/play/{ANIME_ID}/s3", server.uri()));
+    }
+
+    #[tokio::test]
+    async fn fetch_series_episode_links_starts_on_later_page() {
+        let server = MockServer::start().await;
+        mount_release_page(&server, 2, release_page(40, 2, 31..=40)).await;
+
+        let client = PaheClient::with_base_url(&server.uri(), "pahe.win").unwrap();
+        let links = client
+            .fetch_series_episode_links(ANIME_ID, 31, 33)
+            .await
+            .expect("links should load");
+
+        let episodes: Vec<u32> = links.iter().map(|(ep, _)| *ep).collect();
+        assert_eq!(episodes, vec![31, 32, 33]);
+    }
+
+    #[tokio::test]
+    async fn fetch_series_episode_links_spans_pages() {
+        let server = MockServer::start().await;
+        mount_release_page(&server, 1, release_page(40, 1, 1..=30)).await;
+        mount_release_page(&server, 2, release_page(40, 2, 31..=40)).await;
+
+        let client = PaheClient::with_base_url(&server.uri(), "pahe.win").unwrap();
+        let links = client
+            .fetch_series_episode_links(ANIME_ID, 25, 40)
+            .await
+            .expect("links should load");
+
+        let episodes: Vec<u32> = links.iter().map(|(ep, _)| *ep).collect();
+        assert_eq!(episodes, (25..=40).collect::<Vec<_>>());
+        let (_, link) = links.iter().find(|(ep, _)| *ep == 31).unwrap();
+        assert_eq!(*link, format!("{}/play/{ANIME_ID}/s31", server.uri()));
+    }
+
+    #[tokio::test]
+    async fn injected_client_sends_the_requests() {
+        use wiremock::matchers::header;
+
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/api"))
+            .and(query_param("m", "release"))
+            .and(header("x-client", "injected"))
+            .and(header("user-agent", "my-agent/1.0"))
+            .and(header("cookie", "__ddg2_=abc"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_raw(release_page(12, 1, 1..=12), "application/json"),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let mut headers = HeaderMap::new();
+        headers.insert("x-client", HeaderValue::from_static("injected"));
+        let injected = ReqwestClient::builder()
+            .default_headers(headers)
+            .user_agent("my-agent/1.0")
+            .build()
+            .unwrap();
+        let client = PaheBuilder::new()
+            .base_url(&server.uri())
+            .unwrap()
+            .cookies_str("__ddg2_=abc")
+            .with_client(injected)
+            .build()
+            .expect("client should build");
+
+        let total = client
+            .get_series_episode_count(ANIME_ID)
+            .await
+            .expect("count should load through the injected client");
+        assert_eq!(total, 12);
+        server.verify().await;
+    }
+
+    #[tokio::test]
+    async fn builder_timeout_fails_slow_requests() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/api"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_raw(release_page(12, 1, 1..=12), "application/json")
+                    .set_delay(Duration::from_secs(2)),
+            )
+            .mount(&server)
+            .await;
+
+        let client = PaheBuilder::new()
+            .base_url(&server.uri())
+            .unwrap()
+            .timeout(Duration::from_millis(200))
+            .build()
+            .expect("client should build");
+
+        let started = std::time::Instant::now();
+        let err = client
+            .get_series_episode_count(ANIME_ID)
+            .await
+            .expect_err("slow response should time out");
+        assert!(started.elapsed() < Duration::from_secs(2));
+        assert!(
+            matches!(&err, PaheError::Request { source, .. } if source.is_timeout()),
+            "unexpected error: {err:?}"
+        );
+    }
+
+    #[tokio::test]
+    async fn release_api_retries_server_errors() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/api"))
+            .respond_with(ResponseTemplate::new(503).set_body_string("busy"))
+            .up_to_n_times(2)
+            .expect(2)
+            .mount(&server)
+            .await;
+        mount_release_page(&server, 1, release_page(12, 1, 1..=12)).await;
+
+        let client = PaheClient::with_base_url(&server.uri(), "pahe.win").unwrap();
+        let total = client
+            .get_series_episode_count(ANIME_ID)
+            .await
+            .expect("third attempt should succeed");
+        assert_eq!(total, 12);
+        server.verify().await;
+
+        let strict = PaheBuilder::new()
+            .base_url(&server.uri())
+            .unwrap()
+            .api_retries(0)
+            .build()
+            .unwrap();
+        server.reset().await;
+        Mock::given(method("GET"))
+            .and(path("/api"))
+            .respond_with(ResponseTemplate::new(503))
+            .expect(1)
+            .mount(&server)
+            .await;
+        assert!(matches!(
+            strict.get_series_episode_count(ANIME_ID).await,
+            Err(PaheError::HttpStatus { status, .. }) if status.as_u16() == 503
+        ));
+    }
+
+    #[tokio::test]
+    async fn release_api_does_not_retry_ddos_guard() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/api"))
+            .respond_with(ResponseTemplate::new(403).set_body_string("<title>DDoS-Guard</title>"))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let client = PaheClient::with_base_url(&server.uri(), "pahe.win").unwrap();
+        assert!(matches!(
+            client.get_series_episode_count(ANIME_ID).await,
+            Err(PaheError::DdosGuard { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn requests_are_spaced_by_the_minimum_interval() {
+        let server = MockServer::start().await;
+        mount_release_page(&server, 1, release_page(12, 1, 1..=12)).await;
+
+        let client = PaheBuilder::new()
+            .base_url(&server.uri())
+            .unwrap()
+            .min_request_interval(Duration::from_millis(250))
+            .build()
+            .unwrap();
+
+        let started = std::time::Instant::now();
+        client.get_series_episode_count(ANIME_ID).await.unwrap();
+        client.get_series_episode_count(ANIME_ID).await.unwrap();
+        assert!(started.elapsed() >= Duration::from_millis(250));
+
+        let requests = server.received_requests().await.unwrap();
+        assert_eq!(requests.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn series_without_releases_is_reported_distinctly() {
+        let server = MockServer::start().await;
+        mount_release_page(
+            &server,
+            1,
+            r#"{"total":0,"per_page":30,"current_page":1,"last_page":1,"data":[]}"#.to_string(),
+        )
+        .await;
+
+        let client = PaheClient::with_base_url(&server.uri(), "pahe.win").unwrap();
+        assert!(matches!(
+            client.get_series_episode_count(ANIME_ID).await,
+            Err(PaheError::NoEpisodesAvailable { anime_id }) if anime_id == ANIME_ID
+        ));
+        assert!(matches!(
+            client.fetch_series_episode_links(ANIME_ID, 1, 1).await,
+            Err(PaheError::NoEpisodesAvailable { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn fetch_all_episode_links_walks_every_page() {
+        let server = MockServer::start().await;
+        mount_release_page(&server, 1, release_page(65, 1, 1..=30)).await;
+        mount_release_page(&server, 2, release_page(65, 2, 31..=60)).await;
+        mount_release_page(&server, 3, release_page(65, 3, 61..=65)).await;
+
+        let client = PaheBuilder::new()
+            .base_url(&server.uri())
+            .unwrap()
+            .min_request_interval(Duration::ZERO)
+            .build()
+            .unwrap();
+        let links = client
+            .fetch_all_episode_links(ANIME_ID)
+            .await
+            .expect("links should load");
+
+        assert_eq!(links.len(), 65);
+        assert_eq!(
+            links.iter().map(|(ep, _)| *ep).collect::<Vec<_>>(),
+            (1..=65).collect::<Vec<_>>()
+        );
+        assert_eq!(links[64].1, format!("{}/play/{ANIME_ID}/s65", server.uri()));
+    }
+
+    #[tokio::test]
+    async fn release_api_error_object_is_surfaced() {
+        let server = MockServer::start().await;
+        mount_release_page(
+            &server,
+            1,
+            r#"{"error":"no anime found with that id"}"#.to_string(),
+        )
+        .await;
+
+        let client = PaheClient::with_base_url(&server.uri(), "pahe.win").unwrap();
+        let err = client
+            .get_series_episode_count(ANIME_ID)
+            .await
+            .expect_err("error object should fail");
+
+        match err {
+            PaheError::ApiError { message, .. } => {
+                assert_eq!(message, "no anime found with that id")
+            }
+            other => panic!("unexpected error: {other:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn check_cookies_classifies_challenges() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/"))
+            .respond_with(ResponseTemplate::new(403).set_body_string(
+                "<title>DDoS-Guard</title><p>Checking your browser before accessing</p>",
+            ))
+            .mount(&server)
+            .await;
+
+        let mut client = PaheClient::with_base_url(&server.uri(), "pahe.win").unwrap();
+        assert_eq!(client.check_cookies().await.unwrap(), CookieStatus::Missing);
+
+        client.cookie_header = Some("__ddg2_=stale".to_string());
+        assert_eq!(client.check_cookies().await.unwrap(), CookieStatus::Stale);
+    }
+
+    #[tokio::test]
+    async fn check_cookies_accepts_successful_response() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/"))
+            .respond_with(ResponseTemplate::new(200).set_body_string("<html></html>"))
+            .mount(&server)
+            .await;
+
+        let client = PaheClient::with_base_url(&server.uri(), "pahe.win").unwrap();
+        assert_eq!(client.check_cookies().await.unwrap(), CookieStatus::Ok);
+    }
+
+    #[tokio::test]
+    async fn save_cookies_picks_up_refreshed_clearance() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .insert_header("set-cookie", "__ddg2_=fresh; Path=/")
+                    .set_body_string("<html></html>"),
+            )
+            .mount(&server)
+            .await;
+
+        let mut client = PaheClient::with_base_url(&server.uri(), "pahe.win").unwrap();
+        client.cookies_file = vec![
+            NetscapeCookie::session("127.0.0.1", "__ddg2_", "stale"),
+            NetscapeCookie::session("kwik.si", "kwik_session", "abc123"),
+        ];
+        client.check_cookies().await.unwrap();
+
+        let output = std::env::temp_dir().join(format!("pahe-cookies-{}.txt", std::process::id()));
+        client.save_cookies(&output).unwrap();
+        let saved = crate::cookies::parse_cookies_txt(&std::fs::read_to_string(&output).unwrap());
+        std::fs::remove_file(&output).unwrap();
+
+        assert_eq!(saved.len(), 2);
+        assert_eq!(saved[0].value, "fresh");
+        assert_eq!(saved[1].value, "abc123");
+    }
+
+    #[tokio::test]
+    async fn fetch_all_episodes_walks_every_page() {
+        let server = MockServer::start().await;
+        mount_release_page(&server, 1, release_page(35, 1, 1..=30)).await;
+        mount_release_page(&server, 2, release_page(35, 2, 31..=36)).await;
+
+        let client = PaheClient::with_base_url(&server.uri(), "pahe.win").unwrap();
+        let mut page_sizes = Vec::new();
+        let episodes = client
+            .fetch_all_episodes_with(ANIME_ID, |links| page_sizes.push(links.len()))
+            .await
+            .expect("episodes should load");
+
+        assert_eq!(page_sizes, vec![30, 6]);
+        assert_eq!(episodes.reported_total, 35);
+        assert_eq!(episodes.observed_count(), 36);
+    }
+
+    #[tokio::test]
+    async fn fetch_all_episodes_stops_on_an_empty_page_or_the_page_cap() {
+        let server = MockServer::start().await;
+        let unbounded = |data: &str| format!(r#"{{"total":0,"data":[{data}]}}"#);
+        mount_release_page(&server, 1, unbounded(r#"{"episode":1,"session":"s1"}"#)).await;
+        mount_release_page(&server, 2, unbounded("")).await;
+
+        let client = PaheClient::with_base_url(&server.uri(), "pahe.win")
+            .unwrap()
+            .with_min_request_interval(Duration::ZERO);
+        let episodes = client
+            .fetch_all_episodes(ANIME_ID)
+            .await
+            .expect("episodes should load");
+        assert_eq!(episodes.observed_count(), 1);
+
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/api"))
+            .and(query_param("m", "release"))
+            .respond_with(ResponseTemplate::new(200).set_body_raw(
+                unbounded(r#"{"episode":1,"session":"s1"}"#),
+                "application/json",
+            ))
+            .expect(MAX_RELEASE_PAGES as u64)
+            .mount(&server)
+            .await;
+
+        let client = PaheClient::with_base_url(&server.uri(), "pahe.win")
+            .unwrap()
+            .with_min_request_interval(Duration::ZERO);
+        let episodes = client
+            .fetch_all_episodes(ANIME_ID)
+            .await
+            .expect("episodes should load");
+        assert_eq!(episodes.observed_count(), MAX_RELEASE_PAGES);
+    }
+
+    #[tokio::test]
+    async fn fetch_series_episodes_keeps_non_blank_titles() {
+        let server = MockServer::start().await;
+        let body = r#"{"total":2,"last_page":1,"data":[
+            {"episode":1,"session":"s1","title":" Friend "},
+            {"episode":2,"session":"s2","title":""}
+        ]}"#;
+        mount_release_page(&server, 1, body.to_string()).await;
+
+        let client = PaheClient::with_base_url(&server.uri(), "pahe.win").unwrap();
+        let episodes = client
+            .fetch_series_episodes_with(ANIME_ID, 1, 2, |_| {})
+            .await
+            .expect("episodes should load");
+
+        assert_eq!(episodes[0].title.as_deref(), Some("Friend"));
+        assert_eq!(episodes[1].title, None);
+    }
+
+    #[tokio::test]
+    async fn fetch_series_episodes_reads_metadata_and_filler() {
+        let server = MockServer::start().await;
+        let body = r#"{"total":2,"last_page":1,"data":[
+            {"id":1,"anime_id":7,"episode":1,"episode2":0,"edition":"","title":"Pilot","snapshot":"https:\/\/i.animepahe.si\/snapshots\/s1.jpg","disc":"","audio":"jpn","duration":"00:24:10","session":"s1","filler":0,"created_at":"2023-10-06 14:20:43"},
+            {"id":2,"anime_id":7,"episode":2,"episode2":0,"edition":"","title":"","snapshot":"","disc":"","audio":"jpn","duration":"00:23:50","session":"s2","filler":1,"created_at":"2023-10-13 14:21:02"}
+        ]}"#;
+        mount_release_page(&server, 1, body.to_string()).await;
+
+        let client = PaheClient::with_base_url(&server.uri(), "pahe.win").unwrap();
+        let episodes = client
+            .fetch_series_episodes(ANIME_ID, 1, 2)
+            .await
+            .expect("episodes should load");
+
+        assert_eq!(
+            episodes[0],
+            EpisodeInfo {
+                episode: 1,
+                title: Some("Pilot".to_string()),
+                session: "s1".to_string(),
+                play_link: format!("{}/play/{ANIME_ID}/s1", server.uri()),
+                aired_at: Some("2023-10-06 14:20:43".to_string()),
+                snapshot: Some("https://i.animepahe.si/snapshots/s1.jpg".to_string()),
+                duration: Some("00:24:10".to_string()),
+                filler: false,
+            }
+        );
+        assert!(episodes[1].filler);
+        assert_eq!(episodes[1].session, "s2");
+        assert_eq!(episodes[1].snapshot, None);
+        assert_eq!(episodes[1].duration.as_deref(), Some("00:23:50"));
+    }
+
+    #[tokio::test]
+    async fn fetch_series_episodes_accepts_null_and_boolean_filler_flags() {
+        let server = MockServer::start().await;
+        let body = r#"{"total":3,"last_page":1,"data":[
+            {"episode":1,"session":"s1","filler":null},
+            {"episode":2,"session":"s2","filler":true},
+            {"episode":3,"session":"s3","filler":false}
+        ]}"#;
+        mount_release_page(&server, 1, body.to_string()).await;
+
+        let client = PaheClient::with_base_url(&server.uri(), "pahe.win").unwrap();
+        let episodes = client
+            .fetch_series_episodes(ANIME_ID, 1, 3)
+            .await
+            .expect("episodes should load");
+
+        let filler: Vec<bool> = episodes.iter().map(|episode| episode.filler).collect();
+        assert_eq!(filler, [false, true, false]);
+    }
 }
@@ -0,0 +1,162 @@
+//! reading and writing netscape `cookies.txt` files, as exported by browser extensions
+//! and curl.
+
+/// one cookie line of a `cookies.txt` file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NetscapeCookie {
+    /// host the cookie belongs to, e.g. `.animepahe.si`.
+    pub domain: String,
+    /// whether subdomains of `domain` receive the cookie too.
+    pub include_subdomains: bool,
+    pub path: String,
+    /// only sent over https.
+    pub secure: bool,
+    /// unix timestamp the cookie expires at; `0` for session cookies.
+    pub expires: u64,
+    pub name: String,
+    pub value: String,
+    /// written with the `#HttpOnly_` prefix curl uses.
+    pub http_only: bool,
+}
+
+const HTTP_ONLY_PREFIX: &str = "#HttpOnly_";
+
+impl NetscapeCookie {
+    /// a session cookie for `domain` and its subdomains.
+    pub fn session(domain: &str, name: &str, value: &str) -> Self {
+        Self {
+            domain: format!(".{}", domain.trim_start_matches('.')),
+            include_subdomains: true,
+            path: "/".to_string(),
+            secure: true,
+            expires: 0,
+            name: name.to_string(),
+            value: value.to_string(),
+            http_only: false,
+        }
+    }
+
+    /// whether this cookie would be sent to `host`.
+    pub fn matches_host(&self, host: &str) -> bool {
+        let domain = self.domain.trim_start_matches('.');
+        host == domain
+            || (self.include_subdomains || self.domain.starts_with('.'))
+                && host.ends_with(&format!(".{domain}"))
+    }
+
+    fn parse_line(line: &str) -> Option<Self> {
+        let (line, http_only) = match line.strip_prefix(HTTP_ONLY_PREFIX) {
+            Some(rest) => (rest, true),
+            None if line.starts_with('#') => return None,
+            None => (line, false),
+        };
+
+        let fields: Vec<&str> = line.split('\t').collect();
+        let [
+            domain,
+            include_subdomains,
+            path,
+            secure,
+            expires,
+            name,
+            value,
+        ] = fields[..]
+        else {
+            return None;
+        };
+
+        Some(Self {
+            domain: domain.to_string(),
+            include_subdomains: include_subdomains.eq_ignore_ascii_case("TRUE"),
+            path: path.to_string(),
+            secure: secure.eq_ignore_ascii_case("TRUE"),
+            expires: expires.parse().ok()?,
+            name: name.to_string(),
+            value: value.to_string(),
+            http_only,
+        })
+    }
+}
+
+impl std::fmt::Display for NetscapeCookie {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let flag = |value: bool| if value { "TRUE" } else { "FALSE" };
+        write!(
+            f,
+            "{}{}\t{}\t{}\t{}\t{}\t{}\t{}",
+            if self.http_only { HTTP_ONLY_PREFIX } else { "" },
+            self.domain,
+            flag(self.include_subdomains),
+            self.path,
+            flag(self.secure),
+            self.expires,
+            self.name,
+            self.value
+        )
+    }
+}
+
+/// parses a `cookies.txt` file, skipping comments, blank and malformed lines.
+pub fn parse_cookies_txt(text: &str) -> Vec<NetscapeCookie> {
+    text.lines()
+        .map(|line| line.trim_end_matches('\r'))
+        .filter(|line| !line.trim().is_empty())
+        .filter_map(NetscapeCookie::parse_line)
+        .collect()
+}
+
+/// joins the cookies sent to `host` into a `name=value; ...` header.
+///
+/// returns `None` when no cookie matches.
+pub fn cookie_header(cookies: &[NetscapeCookie], host: &str) -> Option<String> {
+    let pairs: Vec<String> = cookies
+        .iter()
+        .filter(|cookie| cookie.matches_host(host))
+        .map(|cookie| format!("{}={}", cookie.name, cookie.value))
+        .collect();
+
+    (!pairs.is_empty()).then(|| pairs.join("; "))
+}
+
+/// serializes cookies into the `cookies.txt` format, header comment included.
+pub fn write_cookies_txt(cookies: &[NetscapeCookie]) -> String {
+    let mut out = String::from("# Netscape HTTP Cookie File\n");
+    for cookie in cookies {
+        out.push_str(&cookie.to_string());
+        out.push('\n');
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FIXTURE: &str = include_str!("../tests/fixtures/cookies.txt");
+
+    #[test]
+    fn cookies_txt_round_trips() {
+        let cookies = parse_cookies_txt(FIXTURE);
+
+        assert_eq!(cookies.len(), 4);
+        assert_eq!(cookies[0].domain, ".animepahe.si");
+        assert_eq!(cookies[0].name, "__ddg1_");
+        assert_eq!(cookies[0].value, "UgXYjtJdbr7gS8ZiQH8z");
+        assert!(cookies[0].include_subdomains);
+        assert_eq!(cookies[0].expires, 1767225600);
+        assert!(cookies[2].http_only);
+        assert_eq!(cookies[3].expires, 0);
+
+        let written = write_cookies_txt(&cookies);
+        assert_eq!(parse_cookies_txt(&written), cookies);
+    }
+
+    #[test]
+    fn matches_host_honors_subdomain_flag() {
+        let cookie = NetscapeCookie::session("animepahe.si", "__ddg2_", "abc");
+
+        assert!(cookie.matches_host("animepahe.si"));
+        assert!(cookie.matches_host("www.animepahe.si"));
+        assert!(!cookie.matches_host("notanimepahe.si"));
+    }
+}
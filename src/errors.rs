@@ -39,7 +39,35 @@ pub enum PaheError {
         source: reqwest::Error,
     },
 
-    #[error("{context} returned 403 Forbidden (DDoS-Guard). {hint}")]
+    #[error("{context} returned an api error: {message}")]
+    ApiError { context: String, message: String },
+
+    #[error("invalid cookie header: {issue}")]
+    InvalidCookies {
+        issue: crate::builder::CookieHeaderIssue,
+    },
+
+    #[error("invalid proxy url {url}: {source}")]
+    InvalidProxy {
+        url: String,
+        #[source]
+        source: reqwest::Error,
+    },
+
+    #[error(
+        "the {setting} setting can't be applied to a client given to with_client; configure it on that client instead"
+    )]
+    InjectedClientSetting { setting: &'static str },
+
+    #[error("failed to {action} cookies file {}: {source}", path.display())]
+    CookiesFile {
+        action: &'static str,
+        path: std::path::PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+
+    #[error("{context} was blocked by an anti-bot challenge (DDoS-Guard or Cloudflare). {hint}")]
     DdosGuard { context: String, hint: String },
 
     #[error("{context} returned {status}\nresponse text:\n{body}")]
@@ -55,14 +83,33 @@ pub enum PaheError {
     #[error("no pahe.win mirrors found in play page")]
     NoMirrors,
 
+    #[error("play page loaded but contained no download links")]
+    EmptyPlayPage,
+
+    #[error("invalid quality {0:?}; expected highest, lowest or a resolution such as 720p")]
+    InvalidQuality(String),
+
     #[error("no selectable variant found")]
     NoSelectableVariant,
 
-    #[error("failed resolving direct link through kwik: {0}")]
-    ResolveDirectLink(#[source] anyhow::Error),
+    #[error("none of the wanted qualities ({wanted}) is offered; available: {available}")]
+    QualityUnavailable { wanted: String, available: String },
+
+    #[error("failed resolving {link} through kwik: {source}")]
+    ResolveDirectLink {
+        link: String,
+        #[source]
+        source: KwikError,
+    },
+
+    #[error("no series found matching \"{query}\"")]
+    NoSearchResults { query: String },
+
+    #[error("episode {episode} not found; the series has {total} episode(s)")]
+    EpisodeNotFound { episode: i32, total: i32 },
 
-    #[error("episode not found: {0}")]
-    EpisodeNotFound(i32),
+    #[error("series {anime_id} has no episodes released yet")]
+    NoEpisodesAvailable { anime_id: String },
 
     #[error("{0}")]
     Message(String),
@@ -70,3 +117,25 @@ pub enum PaheError {
     #[error("command error")]
     CommandError(#[from] std::io::Error),
 }
+
+#[cfg(test)]
+mod tests {
+    use std::error::Error;
+
+    use super::*;
+
+    #[test]
+    fn resolve_direct_link_keeps_kwik_source() {
+        let err = PaheError::ResolveDirectLink {
+            link: "https://pahe.win/abc".to_string(),
+            source: KwikError::MissingKwikLink,
+        };
+
+        assert!(err.to_string().contains("https://pahe.win/abc"));
+        let source = err.source().expect("source should be kept");
+        assert!(matches!(
+            source.downcast_ref::<KwikError>(),
+            Some(KwikError::MissingKwikLink)
+        ));
+    }
+}
@@ -26,5 +26,8 @@
 
 pub mod builder;
 pub mod client;
+pub mod cookies;
 pub mod errors;
 pub mod prelude;
+
+pub use pahe_core::ClientOptions;
@@ -1,3 +1,4 @@
 pub use crate::builder::*;
 pub use crate::client::*;
 pub use crate::errors::*;
+pub use pahe_core::{ClientOptions, DirectLink};